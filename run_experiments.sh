@@ -13,6 +13,7 @@ cargo build --release --bin experiments
 ./target/release/experiments --concurrent-get bench_concurrent_get_16.csv --num-threads 16 --ops-per-sample 1000 --get-success-percentage 1.0
 ./target/release/experiments --concurrent-get bench_concurrent_get_64.csv --num-threads 64 --ops-per-sample 1000 --get-success-percentage 1.0
 ./target/release/experiments --concurrent-get bench_concurrent_get_256.csv --num-threads 256 --ops-per-sample 500 --get-success-percentage 1.0
+./target/release/experiments --concurrent-get bench_concurrent_get_64_sharded.csv --num-threads 64 --ops-per-sample 1000 --get-success-percentage 1.0 --buffer-pool-shards 16
 
 ./target/release/experiments --scan bench_scan.csv --ops-per-sample 300 --entries-per-scan 1000
 
@@ -26,13 +27,42 @@ cargo build --release --bin experiments
 ./target/release/experiments --get bench_get_50pct.csv --ops-per-sample 1000 --get-success-percentage 0.5
 ./target/release/experiments --get bench_get_100pct.csv --ops-per-sample 1000 --get-success-percentage 1.0
 
+./target/release/experiments --get bench_get_binary_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0 --search-strategy binary
+./target/release/experiments --get bench_get_binary_50pct.csv --ops-per-sample 1000 --get-success-percentage 0.5 --search-strategy binary
+./target/release/experiments --get bench_get_binary_100pct.csv --ops-per-sample 1000 --get-success-percentage 1.0 --search-strategy binary
 
-cargo build --release --bin experiments --features binary_search
-./target/release/experiments --get bench_get_binary_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0
-./target/release/experiments --get bench_get_binary_50pct.csv --ops-per-sample 1000 --get-success-percentage 0.5
-./target/release/experiments --get bench_get_binary_100pct.csv --ops-per-sample 1000 --get-success-percentage 1.0
+./target/release/experiments --get bench_get_interpolation_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0 --search-strategy interpolation
+./target/release/experiments --get bench_get_interpolation_50pct.csv --ops-per-sample 1000 --get-success-percentage 0.5 --search-strategy interpolation
+./target/release/experiments --get bench_get_interpolation_100pct.csv --ops-per-sample 1000 --get-success-percentage 1.0 --search-strategy interpolation
 
-cargo build --release --bin experiments --features uniform_bits
-./target/release/experiments --get bench_get_uniform_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0
-./target/release/experiments --get bench_get_uniform_50pct.csv --ops-per-sample 1000 --get-success-percentage 0.5
-./target/release/experiments --get bench_get_uniform_100pct.csv --ops-per-sample 1000 --get-success-percentage 1.0
+./target/release/experiments --get bench_get_uniform_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0 --uniform-bits
+./target/release/experiments --get bench_get_uniform_50pct.csv --ops-per-sample 1000 --get-success-percentage 0.5 --uniform-bits
+./target/release/experiments --get bench_get_uniform_100pct.csv --ops-per-sample 1000 --get-success-percentage 1.0 --uniform-bits
+
+./target/release/experiments --get bench_get_blocked_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0 --filter-type blocked
+./target/release/experiments --get bench_get_blocked_50pct.csv --ops-per-sample 1000 --get-success-percentage 0.5 --filter-type blocked
+./target/release/experiments --get bench_get_blocked_100pct.csv --ops-per-sample 1000 --get-success-percentage 1.0 --filter-type blocked
+
+./target/release/experiments --get bench_get_xor_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0 --filter-type xor
+./target/release/experiments --get bench_get_xor_50pct.csv --ops-per-sample 1000 --get-success-percentage 0.5 --filter-type xor
+./target/release/experiments --get bench_get_xor_100pct.csv --ops-per-sample 1000 --get-success-percentage 1.0 --filter-type xor
+
+./target/release/experiments --get bench_get_partitioned_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0 --filter-type partitioned --pinned-filter-levels 2
+./target/release/experiments --get bench_get_partitioned_50pct.csv --ops-per-sample 1000 --get-success-percentage 0.5 --filter-type partitioned --pinned-filter-levels 2
+./target/release/experiments --get bench_get_partitioned_100pct.csv --ops-per-sample 1000 --get-success-percentage 1.0 --filter-type partitioned --pinned-filter-levels 2
+
+./target/release/experiments --get bench_get_murmur64_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0 --filter-hash murmur64
+./target/release/experiments --get bench_get_xxhash64_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0 --filter-hash xxhash64
+./target/release/experiments --get bench_get_u64_mixer_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0 --filter-hash u64-mixer
+
+./target/release/experiments --get bench_get_lru.csv --ops-per-sample 1000 --get-success-percentage 1.0 --eviction lru
+./target/release/experiments --get bench_get_clock.csv --ops-per-sample 1000 --get-success-percentage 1.0 --eviction clock
+./target/release/experiments --get bench_get_lru_k.csv --ops-per-sample 1000 --get-success-percentage 1.0 --eviction lru-k
+./target/release/experiments --get bench_get_arc.csv --ops-per-sample 1000 --get-success-percentage 1.0 --eviction arc
+
+./target/release/experiments --get bench_get_workload_aware_0pct.csv --ops-per-sample 1000 --get-success-percentage 0.0 --workload-aware-bits
+./target/release/experiments --get bench_get_workload_aware_50pct.csv --ops-per-sample 1000 --get-success-percentage 0.5 --workload-aware-bits
+./target/release/experiments --get bench_get_workload_aware_100pct.csv --ops-per-sample 1000 --get-success-percentage 1.0 --workload-aware-bits
+
+./target/release/experiments --scan bench_short_scan.csv --ops-per-sample 1000 --entries-per-scan 1000 --scan-width 1024
+./target/release/experiments --scan bench_short_scan_range_filter.csv --ops-per-sample 1000 --entries-per-scan 1000 --scan-width 1024 --range-filter-bucket-bits 10
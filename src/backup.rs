@@ -0,0 +1,569 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Database, DbError, database::MUTABLE_FILENAMES, hash::MurmurHash};
+
+const SHARED_DIRNAME: &str = "shared";
+const PRIVATE_DIRNAME: &str = "private";
+const MANIFEST_DIRNAME: &str = "meta";
+/// The file in the manifest folder holding the largest backup ID ever given out.
+const LAST_ID_FILENAME: &str = "last_id";
+
+/// Number of bytes to read at a time when checksumming and copying files.
+const COPY_CHUNK_SIZE: usize = 1 << 16;
+
+/// Manages numbered, incremental backups of databases inside a backup root folder.
+///
+/// The backup root is laid out as follows:
+/// - `shared/` holds SST files. SSTs are immutable, so an SST already stored for an earlier backup
+///   is referenced again instead of being copied. Files are named by their original name, size,
+///   and checksum, so two different SSTs that happened to share a name are kept apart.
+/// - `private/<id>/` holds the configuration, metadata, and write-ahead log of backup `<id>`.
+/// - `meta/<id>.json` is the manifest of backup `<id>`, listing every file in the backup.
+///   It is written last, so a backup that failed partway through is never listed.
+/// - `meta/last_id` holds the largest ID given to a backup so far, even if it has since been purged,
+///   so that IDs are never reused.
+pub struct BackupEngine {
+    root: PathBuf,
+}
+
+/// Summary information about a backup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// The number identifying the backup. Later backups have larger IDs.
+    pub id: u64,
+    /// When the backup was created, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The total size of the files in the backup, in bytes.
+    pub size: u64,
+    /// The number of files in the backup.
+    pub num_files: usize,
+}
+
+/// The list of files making up a backup, persisted in the backup root.
+#[derive(Serialize, Deserialize, Debug)]
+struct BackupManifest {
+    id: u64,
+    timestamp: u64,
+    files: Vec<BackupFile>,
+}
+
+/// A single file in a backup.
+#[derive(Serialize, Deserialize, Debug)]
+struct BackupFile {
+    /// The name of the file in the database folder.
+    name: String,
+    /// Where the file is stored, relative to the backup root.
+    location: PathBuf,
+    size: u64,
+    checksum: u32,
+}
+
+impl BackupEngine {
+    /// Opens the backup root at the given path, creating it if it doesn't exist.
+    ///
+    /// Returns `DbError::IoError` if the folders of the backup root can't be created.
+    pub fn open(root: impl AsRef<Path>) -> Result<Self, DbError> {
+        let root = root.as_ref().to_path_buf();
+        for dirname in [SHARED_DIRNAME, PRIVATE_DIRNAME, MANIFEST_DIRNAME] {
+            fs::create_dir_all(root.join(dirname))?;
+        }
+        Ok(Self { root })
+    }
+
+    /// Flushes the given database and backs it up, returning the ID of the new backup.
+    ///
+    /// SST files that are already stored for an earlier backup are not copied again.
+    ///
+    /// Returns an error if flushing the database fails, or if there are problems with reading/writing files.
+    pub fn create_backup(&self, db: &mut Database) -> Result<u64, DbError> {
+        db.flush()?;
+
+        let id = self
+            .last_id()?
+            .max(self.manifest_ids()?.last().copied().unwrap_or(0))
+            + 1;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        let mut files = Vec::new();
+
+        for name in db.sst_filenames() {
+            let source = db.path().join(&name);
+            let (size, checksum) = checksum_file(&source)?;
+            let location = Path::new(SHARED_DIRNAME).join(format!("{name}-{size}-{checksum:08x}"));
+
+            let destination = self.root.join(&location);
+            if !destination.try_exists()? {
+                // Copy under a temporary name so that a partial copy is never mistaken for a complete one
+                let temporary = destination.with_extension("tmp");
+                _ = fs::remove_file(&temporary);
+                copy_file(&source, &temporary)?;
+                fs::rename(temporary, destination)?;
+            }
+
+            files.push(BackupFile {
+                name,
+                location,
+                size,
+                checksum,
+            });
+        }
+
+        let private_dir = Path::new(PRIVATE_DIRNAME).join(id.to_string());
+        // Clear out leftovers from an earlier attempt at this backup that didn't finish
+        _ = fs::remove_dir_all(self.root.join(&private_dir));
+        fs::create_dir(self.root.join(&private_dir))?;
+
+        for name in MUTABLE_FILENAMES {
+            let location = private_dir.join(name);
+            let (size, checksum) = copy_file(&db.path().join(name), &self.root.join(&location))?;
+            files.push(BackupFile {
+                name: name.to_owned(),
+                location,
+                size,
+                checksum,
+            });
+        }
+
+        let manifest = BackupManifest {
+            id,
+            timestamp,
+            files,
+        };
+        let manifest_path = self.manifest_path(id);
+        let temporary = manifest_path.with_extension("tmp");
+        let manifest_file = File::create(&temporary)?;
+        serde_json::to_writer_pretty(&manifest_file, &manifest)?;
+        manifest_file.sync_all()?;
+        fs::rename(temporary, manifest_path)?;
+
+        let last_id_path = self.root.join(MANIFEST_DIRNAME).join(LAST_ID_FILENAME);
+        let temporary = last_id_path.with_extension("tmp");
+        let mut last_id_file = File::create(&temporary)?;
+        last_id_file.write_all(id.to_string().as_bytes())?;
+        last_id_file.sync_all()?;
+        fs::rename(temporary, last_id_path)?;
+
+        Ok(id)
+    }
+
+    /// Returns information about every backup, sorted from oldest to newest.
+    ///
+    /// Returns an error if a manifest can't be read.
+    pub fn list_backups(&self) -> Result<Vec<BackupInfo>, DbError> {
+        self.manifest_ids()?
+            .into_iter()
+            .map(|id| {
+                let manifest = self.read_manifest(id)?;
+                Ok(BackupInfo {
+                    id: manifest.id,
+                    timestamp: manifest.timestamp,
+                    size: manifest.files.iter().map(|file| file.size).sum(),
+                    num_files: manifest.files.len(),
+                })
+            })
+            .collect()
+    }
+
+    /// Checks that every file of the backup with the given ID exists and has the expected size and checksum.
+    ///
+    /// Returns `DbError::CorruptBackup` naming the first file that fails the check.
+    ///
+    /// Returns `DbError::IoError` if the backup doesn't exist.
+    pub fn verify_backup(&self, id: u64) -> Result<(), DbError> {
+        let manifest = self.read_manifest(id)?;
+        for file in &manifest.files {
+            let path = self.root.join(&file.location);
+            let Ok((size, checksum)) = checksum_file(&path) else {
+                return Err(DbError::CorruptBackup(format!(
+                    "backup {id}: cannot read {}",
+                    file.location.display()
+                )));
+            };
+            if size != file.size || checksum != file.checksum {
+                return Err(DbError::CorruptBackup(format!(
+                    "backup {id}: {} does not match its recorded size and checksum",
+                    file.location.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes all but the given number of most recent backups,
+    /// along with any stored SST files that no remaining backup refers to.
+    ///
+    /// The manifests of the remaining backups are all read before anything is deleted,
+    /// so failing to read one leaves every backup intact.
+    ///
+    /// Returns an error if there are problems with reading/deleting files.
+    pub fn purge_old_backups(&self, num_to_keep: usize) -> Result<(), DbError> {
+        let ids = self.manifest_ids()?;
+        let num_to_purge = ids.len().saturating_sub(num_to_keep);
+
+        let mut referenced = HashSet::new();
+        for &id in &ids[num_to_purge..] {
+            for file in self.read_manifest(id)?.files {
+                referenced.insert(self.root.join(file.location));
+            }
+        }
+
+        for &id in &ids[..num_to_purge] {
+            fs::remove_file(self.manifest_path(id))?;
+            _ = fs::remove_dir_all(self.root.join(PRIVATE_DIRNAME).join(id.to_string()));
+        }
+
+        for entry in fs::read_dir(self.root.join(SHARED_DIRNAME))? {
+            let path = entry?.path();
+            if !referenced.contains(&path) {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restores the backup with the given ID into a new database folder at the given path.
+    /// The backup is verified before anything is written.
+    ///
+    /// The files are restored into a temporary folder next to the path, which is renamed to the path
+    /// once every file is copied and matches its checksum, and removed if anything fails.
+    /// So the path never holds a partially restored database.
+    ///
+    /// Returns `DbError::CorruptBackup` if verification fails, or if a copied file doesn't match its checksum.
+    ///
+    /// Returns `DbError::IoError` if:
+    /// - The backup doesn't exist.
+    /// - The path already exists.
+    /// - A parent of the path does not exist.
+    /// - There are problems with reading/writing files.
+    pub fn restore(&self, id: u64, target: impl AsRef<Path>) -> Result<(), DbError> {
+        let target = target.as_ref();
+
+        self.verify_backup(id)?;
+        let manifest = self.read_manifest(id)?;
+
+        if target.try_exists()? {
+            return Err(DbError::IoError(format!(
+                "restore target {} already exists",
+                target.display()
+            )));
+        }
+        let mut temporary = target.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        // Clear out leftovers from an earlier restore that didn't finish
+        _ = fs::remove_dir_all(&temporary);
+        fs::create_dir(&temporary)?;
+
+        let restore_files = || {
+            for file in &manifest.files {
+                let (size, checksum) =
+                    copy_file(&self.root.join(&file.location), &temporary.join(&file.name))?;
+                if size != file.size || checksum != file.checksum {
+                    return Err(DbError::CorruptBackup(format!(
+                        "backup {id}: {} changed while it was restored",
+                        file.location.display()
+                    )));
+                }
+            }
+            fs::rename(&temporary, target)?;
+            Ok(())
+        };
+        restore_files().inspect_err(|_| _ = fs::remove_dir_all(&temporary))
+    }
+
+    /// Returns the IDs of all backups with a manifest, sorted in ascending order.
+    fn manifest_ids(&self) -> Result<Vec<u64>, DbError> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(self.root.join(MANIFEST_DIRNAME))? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+                && let Some(id) = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Returns the largest ID given to a backup so far, or 0 if none has been recorded.
+    /// Backup roots from before this was recorded only have their manifests to go by.
+    fn last_id(&self) -> Result<u64, DbError> {
+        let path = self.root.join(MANIFEST_DIRNAME).join(LAST_ID_FILENAME);
+        if !path.try_exists()? {
+            return Ok(0);
+        }
+        fs::read_to_string(&path)?
+            .trim()
+            .parse()
+            .map_err(|e| DbError::CorruptBackup(format!("{}: {e}", path.display())))
+    }
+
+    fn manifest_path(&self, id: u64) -> PathBuf {
+        self.root.join(MANIFEST_DIRNAME).join(format!("{id}.json"))
+    }
+
+    fn read_manifest(&self, id: u64) -> Result<BackupManifest, DbError> {
+        let manifest_file = File::open(self.manifest_path(id))?;
+        Ok(serde_json::from_reader(manifest_file)?)
+    }
+}
+
+/// Returns the size and checksum of the file at the given path.
+fn checksum_file(path: &Path) -> Result<(u64, u32), DbError> {
+    process_file(path, |_| Ok(()))
+}
+
+/// Copies the file at the given path to a new file, syncing it to disk.
+/// Returns the size and checksum of the file.
+fn copy_file(source: &Path, destination: &Path) -> Result<(u64, u32), DbError> {
+    let mut out = File::create_new(destination)?;
+    let result = process_file(source, |chunk| Ok(out.write_all(chunk)?))?;
+    out.sync_all()?;
+    Ok(result)
+}
+
+/// Reads the file at the given path in chunks, passing each chunk to the given closure.
+/// Returns the size and checksum of the file.
+///
+/// The checksum chains MurmurHash over the chunks, using the hash of each chunk as the seed of the next.
+fn process_file(
+    path: &Path,
+    mut on_chunk: impl FnMut(&[u8]) -> Result<(), DbError>,
+) -> Result<(u64, u32), DbError> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; COPY_CHUNK_SIZE];
+    let mut size = 0;
+    let mut checksum = 0;
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            return Ok((size, checksum));
+        }
+        on_chunk(&buffer[..n])?;
+        size += n as u64;
+        checksum = MurmurHash::hash32(&buffer[..n], checksum);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::{
+        DbConfiguration, EvictionType, FilterType, LevelCompression, LsmConfiguration,
+        SearchStrategy,
+        test_util::{TestPath, test_seed},
+    };
+
+    use super::*;
+
+    fn test_path(name: &str) -> TestPath {
+        TestPath::create("backup", name)
+    }
+
+    fn create_db(path: &TestPath) -> Result<Database> {
+        Ok(Database::create(
+            path,
+            DbConfiguration {
+                buffer_pool_capacity: 16,
+                write_buffering: 1,
+                readahead_buffering: 1,
+                wal_buffer_size: Some(4),
+                wal_archiving: false,
+                seed: Some(test_seed()),
+                buffer_pool_shards: None,
+                eviction: EvictionType::default(),
+                lsm_configuration: LsmConfiguration {
+                    size_ratio: 3,
+                    memtable_capacity: 4,
+                    bloom_filter_bits: 2,
+                    compression: LevelCompression::default(),
+                    pin_internal_nodes: false,
+                    search_strategy: SearchStrategy::BTree,
+                    uniform_bits: false,
+                    workload_aware_bits: false,
+                    filter_type: FilterType::Bloom,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
+                },
+            },
+        )?)
+    }
+
+    fn contents(db: &Database) -> Result<Vec<(u64, u64)>> {
+        Ok(db.scan(u64::MIN..=u64::MAX)?.collect::<Result<_, _>>()?)
+    }
+
+    fn num_shared_files(root: &TestPath) -> Result<usize> {
+        Ok(fs::read_dir(root.as_ref().join(SHARED_DIRNAME))?.count())
+    }
+
+    #[test]
+    fn test_backup_restore() -> Result<()> {
+        let db_path = &test_path("backup_restore_db");
+        let root = &test_path("backup_restore_root");
+        let restore_1 = &test_path("backup_restore_1");
+        let restore_2 = &test_path("backup_restore_2");
+
+        let engine = BackupEngine::open(root)?;
+        let mut db = create_db(db_path)?;
+
+        for i in 0..16 {
+            db.put(i, i * 10)?;
+        }
+        let id_1 = engine.create_backup(&mut db)?;
+        let contents_1 = contents(&db)?;
+        let shared_after_1 = num_shared_files(root)?;
+        assert_eq!(shared_after_1, db.sst_filenames().len());
+
+        // The SSTs from the first backup are still in the database, so they aren't copied again
+        for i in 16..18 {
+            db.put(i, i * 10)?;
+        }
+        db.delete(3)?;
+        let id_2 = engine.create_backup(&mut db)?;
+        let contents_2 = contents(&db)?;
+        assert!(num_shared_files(root)? < shared_after_1 + db.sst_filenames().len());
+
+        let backups = engine.list_backups()?;
+        assert_eq!(
+            backups.iter().map(|info| info.id).collect::<Vec<_>>(),
+            vec![id_1, id_2]
+        );
+        engine.verify_backup(id_1)?;
+        engine.verify_backup(id_2)?;
+
+        engine.restore(id_1, restore_1)?;
+        assert_eq!(contents(&Database::open(restore_1)?)?, contents_1);
+
+        engine.purge_old_backups(1)?;
+        assert_eq!(engine.list_backups()?.len(), 1);
+        assert!(engine.verify_backup(id_1).is_err());
+        engine.verify_backup(id_2)?;
+
+        engine.restore(id_2, restore_2)?;
+        assert_eq!(contents(&Database::open(restore_2)?)?, contents_2);
+
+        // IDs keep increasing after every backup has been purged
+        engine.purge_old_backups(0)?;
+        assert!(engine.list_backups()?.is_empty());
+        let id_3 = engine.create_backup(&mut db)?;
+        assert!(id_3 > id_2);
+        assert!(BackupEngine::open(root)?.create_backup(&mut db)? > id_3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_detects_corruption() -> Result<()> {
+        let db_path = &test_path("verify_db");
+        let root = &test_path("verify_root");
+        let restore = &test_path("verify_restore");
+
+        let engine = BackupEngine::open(root)?;
+        let mut db = create_db(db_path)?;
+        for i in 0..8 {
+            db.put(i, i)?;
+        }
+        let id = engine.create_backup(&mut db)?;
+
+        let shared_file = fs::read_dir(root.as_ref().join(SHARED_DIRNAME))?
+            .next()
+            .unwrap()?
+            .path();
+        let mut bytes = fs::read(&shared_file)?;
+        bytes[100] ^= 1;
+        fs::write(&shared_file, bytes)?;
+
+        assert!(matches!(
+            engine.verify_backup(id),
+            Err(DbError::CorruptBackup(_))
+        ));
+        assert!(matches!(
+            engine.restore(id, restore),
+            Err(DbError::CorruptBackup(_))
+        ));
+        assert!(!restore.as_ref().try_exists()?);
+
+        Ok(())
+    }
+
+    /// Tests that a restore that fails partway through removes what it copied,
+    /// and leaves nothing at the target path.
+    #[test]
+    fn test_failed_restore_cleans_up() -> Result<()> {
+        let db_path = &test_path("failed_restore_db");
+        let root = &test_path("failed_restore_root");
+        let restore = &test_path("failed_restore");
+
+        let engine = BackupEngine::open(root)?;
+        let mut db = create_db(db_path)?;
+        for i in 0..8 {
+            db.put(i, i)?;
+        }
+        let id = engine.create_backup(&mut db)?;
+
+        // Make the last file fail to copy after the others have been, without failing verification
+        let mut manifest = engine.read_manifest(id)?;
+        manifest.files.last_mut().unwrap().name = "missing/folder".to_owned();
+        fs::write(engine.manifest_path(id), serde_json::to_string(&manifest)?)?;
+
+        assert!(matches!(
+            engine.restore(id, restore),
+            Err(DbError::IoError(_))
+        ));
+        assert!(!restore.as_ref().try_exists()?);
+        let mut temporary = restore.as_ref().as_os_str().to_owned();
+        temporary.push(".tmp");
+        assert!(!Path::new(&temporary).try_exists()?);
+
+        Ok(())
+    }
+
+    /// Tests that purging deletes nothing if the manifest of a remaining backup can't be read.
+    #[test]
+    fn test_failed_purge_keeps_backups() -> Result<()> {
+        let db_path = &test_path("failed_purge_db");
+        let root = &test_path("failed_purge_root");
+
+        let engine = BackupEngine::open(root)?;
+        let mut db = create_db(db_path)?;
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            for j in 0..8 {
+                db.put(i * 8 + j, j)?;
+            }
+            db.delete(i * 8)?;
+            ids.push(engine.create_backup(&mut db)?);
+        }
+        let shared_files = num_shared_files(root)?;
+
+        fs::write(engine.manifest_path(ids[2]), "not a manifest")?;
+        assert!(engine.purge_old_backups(1).is_err());
+
+        assert!(engine.manifest_path(ids[0]).try_exists()?);
+        engine.verify_backup(ids[0])?;
+        engine.verify_backup(ids[1])?;
+        assert_eq!(num_shared_files(root)?, shared_files);
+
+        Ok(())
+    }
+}
@@ -1,74 +1,220 @@
 use bit_vec::BitVec;
 
-use crate::hash::HashFunction;
+use crate::{
+    filter::Filter,
+    hash::{HashType, mix64},
+};
+
+/// The number of bits in a block of a blocked filter.
+const BLOCK_BITS: usize = 512;
+/// The number of bytes in a block of a blocked filter.
+pub const BLOCK_SIZE: usize = BLOCK_BITS / 8;
+
+/// A block of a blocked filter, aligned to a cache line.
+#[repr(C, align(64))]
+#[derive(Clone, Copy, Debug, Default)]
+struct Block([u64; BLOCK_BITS / 64]);
+
+/// The bits of a bloom filter, either flat or split into blocks.
+#[derive(Clone, Debug)]
+enum Bitmap {
+    Flat(BitVec),
+    Blocked {
+        blocks: Vec<Block>,
+        num_probes: usize,
+    },
+}
 
 #[derive(Clone, Debug)]
 pub struct BloomFilter {
-    pub hash_functions: Vec<HashFunction>, // Bloom filter's hash functions
-    bits: BitVec,                          // Bloom filter's bitmap
+    hash: HashType,  // Bloom filter's hash algorithm
+    seeds: Vec<u32>, // Seed of each of the bloom filter's hash functions, or the one seed of a blocked filter
+    bits: Bitmap,    // Bloom filter's bitmap
 }
 
 impl BloomFilter {
-    /// Create an empty bloom filter having the number of entries and bits per each entry
-    pub fn empty(n_entries: usize, bits_per_entry: usize) -> Self {
+    /// Create an empty bloom filter having the number of entries and bits per each entry,
+    /// blocked by cache line if `blocked` is set, that hashes keys with the given algorithm.
+    /// The seeds of its hash functions derive from the given seed.
+    pub fn empty(
+        n_entries: usize,
+        bits_per_entry: usize,
+        blocked: bool,
+        hash: HashType,
+        seed: u64,
+    ) -> Self {
         // Calculate how many hash functions it needs to be optimal: bits_per_entry * ln(2)
-        let bits = BitVec::from_elem((n_entries * bits_per_entry).next_multiple_of(8), false);
         let num_hashes = (bits_per_entry as f32 * f32::ln(2.0)).ceil() as usize;
+        let mut rng = fastrand::Rng::with_seed(seed);
 
-        let hash_functions = (0..num_hashes).map(|_| HashFunction::new()).collect();
+        if !blocked {
+            let bits = BitVec::from_elem((n_entries * bits_per_entry).next_multiple_of(8), false);
+            let seeds = (0..num_hashes).map(|_| rng.u32(..)).collect();
 
-        Self {
-            hash_functions,
-            bits,
-        }
-    }
+            Self {
+                hash,
+                seeds,
+                bits: Bitmap::Flat(bits),
+            }
+        } else {
+            // At least one block, so that every key has a block to go to
+            let n_blocks = (n_entries * bits_per_entry).div_ceil(BLOCK_BITS).max(1);
 
-    /// Turn a vector of bits into bloom filter by having the number of hashes
-    pub fn from_bytes(filter_bytes: &[u8], num_hashes: usize) -> Self {
-        let bits_offset = num_hashes * size_of::<HashFunction>();
-        let hash_functions: &[HashFunction] = bytemuck::cast_slice(&filter_bytes[0..bits_offset]);
-        let hash_functions = hash_functions.to_vec();
-        let bits = BitVec::from_bytes(&filter_bytes[bits_offset..]);
-        Self {
-            hash_functions,
-            bits,
+            Self {
+                hash,
+                seeds: vec![rng.u32(..)],
+                bits: Bitmap::Blocked {
+                    blocks: vec![Block::default(); n_blocks],
+                    num_probes: num_hashes,
+                },
+            }
         }
     }
 
-    /// Turn filter into a vector of bytes
-    pub fn turn_to_bytes(&self) -> Vec<u8> {
-        let hash_bytes = bytemuck::cast_slice(&self.hash_functions);
-        let mut hash_bytes = hash_bytes.to_vec();
-        hash_bytes.append(&mut self.bits.to_bytes());
-        hash_bytes
+    /// Turn a vector of bits into bloom filter by having the number of hashes, blocked if `blocked` is set,
+    /// that hashes keys with the algorithm it was created with
+    ///
+    /// The bytes start with the seed of each hash function as a little-endian `u32`,
+    /// followed by the bitmap, with the first bit in the most significant bit of the first byte.
+    /// A blocked filter has a single seed, and each block is stored as eight little-endian `u64`s,
+    /// with bit `i` of the block in bit `i % 64` of word `i / 64`.
+    pub fn from_bytes(
+        filter_bytes: &[u8],
+        num_hashes: usize,
+        blocked: bool,
+        hash: HashType,
+    ) -> Self {
+        let bits_offset = if blocked { 1 } else { num_hashes } * size_of::<u32>();
+        let (seeds, _) = filter_bytes[0..bits_offset].as_chunks::<4>();
+        let seeds = seeds.iter().map(|&seed| u32::from_le_bytes(seed)).collect();
+        let bits = if !blocked {
+            Bitmap::Flat(BitVec::from_bytes(&filter_bytes[bits_offset..]))
+        } else {
+            let (blocks, _) = filter_bytes[bits_offset..].as_chunks::<BLOCK_SIZE>();
+            let blocks = blocks
+                .iter()
+                .map(|block| {
+                    let (words, _) = block.as_chunks::<8>();
+                    Block(std::array::from_fn(|i| u64::from_le_bytes(words[i])))
+                })
+                .collect();
+            Bitmap::Blocked {
+                blocks,
+                num_probes: num_hashes,
+            }
+        };
+        Self { hash, seeds, bits }
     }
 
     /// Insert a key into the bloom filter
     pub fn insert(&mut self, key: u64) {
-        let bitmap_len = self.bits.len();
-        let hash_functions = &self.hash_functions;
-        for hashed_index in hash_functions
-            .iter()
-            .map(|func| func.hash_to_index(key, bitmap_len))
-        {
-            self.bits.set(hashed_index, true);
+        match &mut self.bits {
+            Bitmap::Flat(bits) => {
+                let bitmap_len = bits.len();
+                for &seed in &self.seeds {
+                    bits.set(self.hash.hash_to_index(key, seed, bitmap_len), true);
+                }
+            }
+            Bitmap::Blocked { blocks, num_probes } => {
+                let hash = self.hash.hash64(key, self.seeds[0] as u64);
+                let index = block_index(hash, blocks.len());
+                let block = &mut blocks[index];
+                for bit in block_probes(hash, *num_probes) {
+                    block.0[bit / 64] |= 1 << (bit % 64);
+                }
+            }
         }
     }
+}
 
+impl Filter for BloomFilter {
     /// Search for a key inside the bloom filter
-    pub fn query(&self, key: u64) -> bool {
-        let bitmap_len = self.bits.len();
-        for hashed_index in self
-            .hash_functions
-            .iter()
-            .map(|func| func.hash_to_index(key, bitmap_len))
-        {
-            if !self.bits.get(hashed_index).unwrap() {
-                return false;
+    fn query(&self, key: u64) -> bool {
+        match &self.bits {
+            Bitmap::Flat(bits) => {
+                let bitmap_len = bits.len();
+                self.seeds.iter().all(|&seed| {
+                    bits.get(self.hash.hash_to_index(key, seed, bitmap_len))
+                        .unwrap()
+                })
+            }
+            Bitmap::Blocked { blocks, num_probes } => {
+                let Some(&seed) = self.seeds.first() else {
+                    return true;
+                };
+                let hash = self.hash.hash64(key, seed as u64);
+                let Some(block) = blocks.get(block_index(hash, blocks.len())) else {
+                    return true;
+                };
+                block_probes(hash, *num_probes)
+                    .all(|bit| block.0[bit / 64] & (1 << (bit % 64)) != 0)
             }
         }
-        true
     }
+
+    /// Turn filter into a vector of bytes
+    fn turn_to_bytes(&self) -> Vec<u8> {
+        let mut hash_bytes: Vec<u8> = self
+            .seeds
+            .iter()
+            .flat_map(|seed| seed.to_le_bytes())
+            .collect();
+        match &self.bits {
+            Bitmap::Flat(bits) => hash_bytes.append(&mut bits.to_bytes()),
+            Bitmap::Blocked { blocks, .. } => hash_bytes.extend(
+                blocks
+                    .iter()
+                    .flat_map(|block| block.0)
+                    .flat_map(u64::to_le_bytes),
+            ),
+        }
+        hash_bytes
+    }
+
+    /// The number of bits that are probed for each key
+    fn num_hashes(&self) -> usize {
+        match self.bits {
+            Bitmap::Flat(_) => self.seeds.len(),
+            Bitmap::Blocked { num_probes, .. } => num_probes,
+        }
+    }
+
+    /// Blocked filters are treated as one bitmap, which slightly underestimates their rate.
+    fn false_positive_rate(&self, n_entries: usize) -> f64 {
+        let n_bits = match &self.bits {
+            Bitmap::Flat(bits) => bits.len(),
+            Bitmap::Blocked { blocks, .. } => blocks.len() * BLOCK_BITS,
+        };
+        false_positive_rate(n_bits, self.num_hashes(), n_entries)
+    }
+}
+
+/// The expected rate of false positives of a bloom filter with the given number of bits and probes per key,
+/// given the number of keys that were inserted: `(1 - e^(-kn/m))^k` for `k` probes into `m` bits.
+pub fn false_positive_rate(n_bits: usize, num_probes: usize, n_entries: usize) -> f64 {
+    let k = num_probes as f64;
+    if n_bits == 0 || k == 0.0 {
+        return 1.0;
+    }
+    (1.0 - (-k * n_entries as f64 / n_bits as f64).exp()).powf(k)
+}
+
+/// The block of a blocked filter with the given number of blocks that a hash goes to,
+/// from the upper 32 bits of the hash.
+fn block_index(hash: u64, n_blocks: usize) -> usize {
+    (((hash >> 32) * n_blocks as u64) >> 32) as usize
+}
+
+/// The bits within a block that a hash sets, by double hashing:
+/// probe `i` is the top 9 bits of `h1 + i * h2`, where `h1` is the lower half of the hash
+/// and `h2` comes from mixing the whole hash again.
+/// Taking `h2` from any bits of the upper half, which `block_index` uses,
+/// would give keys in the same block similar probe patterns.
+fn block_probes(hash: u64, num_probes: usize) -> impl Iterator<Item = usize> {
+    let h1 = hash as u32;
+    // Odd, so that the probes are spread over the whole block
+    let h2 = (mix64(hash, 1) >> 32) as u32 | 1;
+    (0..num_probes as u32).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) >> 23) as usize)
 }
 
 /* Tests for SSTs */
@@ -87,13 +233,44 @@ mod tests {
         let entries: Vec<u64> = repeat_with(|| fastrand::u64(..))
             .take(num_elements)
             .collect();
-        let mut filter = BloomFilter::empty(entries_num, bits_per_entry);
-        for entry in &entries {
-            filter.insert(*entry);
+        for blocked in [false, true] {
+            let mut filter =
+                BloomFilter::empty(entries_num, bits_per_entry, blocked, HashType::Murmur, 0);
+            for entry in &entries {
+                filter.insert(*entry);
+            }
+
+            for entry in &entries {
+                assert!(filter.query(*entry));
+            }
         }
+    }
+
+    /// Tests that each key of a blocked filter sets bits in a single block,
+    /// and that the blocks are stored after a single seed.
+    #[test]
+    fn test_blocked_filter() {
+        let n_entries = 10_000;
+        let mut filter = BloomFilter::empty(n_entries, 10, true, HashType::U64Mixer, 0);
+        assert_eq!(filter.num_hashes(), 7);
 
-        for entry in &entries {
-            assert!(filter.query(*entry));
+        let bytes = filter.turn_to_bytes();
+        let n_blocks = (n_entries * 10).div_ceil(BLOCK_BITS);
+        assert_eq!(bytes.len(), 4 + n_blocks * BLOCK_SIZE);
+
+        for key in 0..100 {
+            let before = filter.turn_to_bytes();
+            filter.insert(key);
+            let after = filter.turn_to_bytes();
+            let changed_blocks: Vec<usize> = (4..after.len())
+                .filter(|&i| before[i] != after[i])
+                .map(|i| (i - 4) / BLOCK_SIZE)
+                .collect();
+            assert!(changed_blocks.windows(2).all(|pair| pair[0] == pair[1]));
+            assert!(filter.query(key));
         }
+
+        let filter = BloomFilter::from_bytes(&filter.turn_to_bytes(), 7, true, HashType::U64Mixer);
+        assert!((0..100).all(|key| filter.query(key)));
     }
 }
@@ -1,11 +1,25 @@
-use std::{ops::RangeInclusive, sync::Arc};
+use std::{
+    ops::{Range, RangeInclusive},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    DbError, PAGE_SIZE,
-    bloom_filter::BloomFilter,
+    DbError, FORMAT_VERSION, PAGE_SIZE,
+    compression::{Compression, Compressor},
+    delta_leaf::{self, DeltaLeaf},
+    endian::LeU64,
     file_system::FileSystem,
-    file_system::{Aligned, FileId},
-    sst::Sst,
+    file_system::{Aligned, CHECKSUM_SIZE, FileId, PAGE_DATA_SIZE, PageId},
+    filter::{self, FilterBuilder, FilterType},
+    hash::HashType,
+    lsm::TOMBSTONE,
+    partitioned_filter::{PARTITION_SIZE, PartitionIndex},
+    range_filter::{RangeFilter, RangeFilterBuilder, RangeFilterConfiguration},
+    sst::{Sst, SstFilter},
+    verify::{ProblemKind, VerifyProblem, VerifyReport},
 };
 
 const PAIRS_PER_CHUNK: usize = (PAGE_SIZE - 8) / 16;
@@ -13,35 +27,195 @@ const PADDING: usize = PAGE_SIZE - 8 - PAIRS_PER_CHUNK * 16;
 
 /// The smallest granuallity of storage we use in our system
 /// It has a length, it has a bunch of pairs and since it is aligned to PAGE_SIZE,
-/// the rest is a padding
+/// the rest is a padding, whose last bytes are taken by the file system's checksum
+///
+/// On disk, the length takes bytes 0..8 and pair `i` takes bytes `8 + 16 * i..24 + 16 * i`,
+/// with the key before the value, all as little-endian `u64`s.
 #[repr(C, align(4096))]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
 struct Page {
     /// Number of pairs stored in this page
-    length: u64,
-    pairs: [[u64; 2]; PAIRS_PER_CHUNK],
+    length: LeU64,
+    pairs: [[LeU64; 2]; PAIRS_PER_CHUNK],
     padding: [u8; PADDING],
 }
 
 impl Default for Page {
     fn default() -> Self {
         Self {
-            length: 0,
+            length: LeU64::default(),
             pairs: [Default::default(); _],
             padding: [Default::default(); _],
         }
     }
 }
 
+impl Page {
+    /// Number of pairs stored in this page
+    fn len(&self) -> usize {
+        self.length.get() as usize
+    }
+
+    /// The pairs stored in this page
+    fn stored_pairs(&self) -> &[[LeU64; 2]] {
+        &self.pairs[..self.len()]
+    }
+
+    /// The key of the pair at the given index
+    fn key(&self, index: usize) -> u64 {
+        self.pairs[index][0].get()
+    }
+
+    /// The value of the pair at the given index
+    fn value(&self, index: usize) -> u64 {
+        self.pairs[index][1].get()
+    }
+}
+
 /// Making sure our page is the size of PAGE_SIZE
 const _: () = assert!(size_of::<Page>() == PAGE_SIZE);
+/// Making sure the checksum doesn't overwrite any pairs
+const _: () = assert!(PADDING >= CHECKSUM_SIZE);
+
+/// The number of bytes in a compressed leaf page that hold the compressed block
+const BLOCK_CAPACITY: usize = PAGE_DATA_SIZE - 16;
+/// The most pairs a compressed leaf page can hold, which bounds the cost of decompressing it
+const MAX_BLOCK_PAIRS: usize = 4096;
+
+/// A leaf page of an SST with compressed leaves.
+/// It holds as many pairs as fit in the page once compressed.
+///
+/// On disk, the number of pairs takes bytes 0..8 and the size of the compressed block takes bytes 8..16,
+/// both as little-endian `u64`s, and the block follows.
+#[repr(C, align(4096))]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
+struct CompressedLeaf {
+    /// Number of pairs stored in this page
+    length: LeU64,
+    /// Number of bytes used in `block`
+    block_size: LeU64,
+    block: [u8; BLOCK_CAPACITY],
+    checksum: [u8; CHECKSUM_SIZE],
+}
+
+/// Making sure our compressed leaf is the size of PAGE_SIZE
+const _: () = assert!(size_of::<CompressedLeaf>() == PAGE_SIZE);
+
+impl CompressedLeaf {
+    /// Compresses as many of the given pairs as fit into the page, and returns how many that is.
+    fn fill(&mut self, pairs: &[[u64; 2]], compressor: &dyn Compressor) -> usize {
+        let mut block = Vec::new();
+        let mut compress = |n_pairs: usize| {
+            block.clear();
+            compressor.compress(&pairs[..n_pairs], &mut block);
+            block.len() <= BLOCK_CAPACITY
+        };
+
+        // Binary search for the most pairs that fit,
+        // relying on more pairs never compressing to fewer bytes
+        let (mut fitting, mut too_many) = (0, pairs.len() + 1);
+        if compress(pairs.len()) {
+            fitting = pairs.len();
+        }
+        while too_many - fitting > 1 {
+            let middle = (fitting + too_many) / 2;
+            if compress(middle) {
+                fitting = middle;
+            } else {
+                too_many = middle;
+            }
+        }
+        compress(fitting);
+        debug_assert!(fitting > 0 || pairs.is_empty());
+
+        self.length.set(fitting as u64);
+        self.block_size.set(block.len() as u64);
+        self.block[..block.len()].copy_from_slice(&block);
+        fitting
+    }
+
+    /// Decompresses the pairs stored in this page.
+    fn decompress(&self, compressor: &dyn Compressor) -> Result<Vec<[u64; 2]>, DbError> {
+        let length = self.length.get();
+        let block_size = self.block_size.get();
+        if length == 0 || length > MAX_BLOCK_PAIRS as u64 || block_size > BLOCK_CAPACITY as u64 {
+            return Err(DbError::CorruptSst);
+        }
+
+        let mut pairs = Vec::new();
+        compressor.decompress(
+            &self.block[..block_size as usize],
+            length as usize,
+            &mut pairs,
+        )?;
+        Ok(pairs)
+    }
+}
+
+/// The pairs of a leaf page, decompressed if the SST has compressed leaves.
+enum LeafPairs {
+    Page(Arc<Leaf>),
+    Delta(DeltaLeaf),
+    Decompressed(Vec<[u64; 2]>),
+}
+
+impl LeafPairs {
+    /// Reads the pairs out of a leaf page of an SST with the given compression.
+    fn decode(page: Arc<Aligned>, compression: Compression) -> Result<Self, DbError> {
+        match (compression, compression.compressor()) {
+            (Compression::DeltaKeys, _) => Ok(Self::Delta(DeltaLeaf::new(page, MAX_BLOCK_PAIRS)?)),
+            (_, None) => Ok(Self::Page(bytemuck::cast_arc(page))),
+            (_, Some(compressor)) => {
+                let leaf: &CompressedLeaf = bytemuck::cast_ref(page.as_ref());
+                Ok(Self::Decompressed(leaf.decompress(compressor)?))
+            }
+        }
+    }
+
+    /// Number of pairs in the leaf
+    fn len(&self) -> usize {
+        match self {
+            Self::Page(leaf) => leaf.len(),
+            Self::Delta(leaf) => leaf.len(),
+            Self::Decompressed(pairs) => pairs.len(),
+        }
+    }
+
+    /// The key of the pair at the given index
+    fn key(&self, index: usize) -> u64 {
+        match self {
+            Self::Page(leaf) => leaf.key(index),
+            Self::Delta(leaf) => leaf.pair(index)[0],
+            Self::Decompressed(pairs) => pairs[index][0],
+        }
+    }
+
+    /// The value of the pair at the given index
+    fn value(&self, index: usize) -> u64 {
+        match self {
+            Self::Page(leaf) => leaf.value(index),
+            Self::Delta(leaf) => leaf.pair(index)[1],
+            Self::Decompressed(pairs) => pairs[index][1],
+        }
+    }
+
+    /// Binary searches the leaf for the given key, like `slice::binary_search`
+    fn binary_search(&self, key: u64) -> Result<usize, usize> {
+        match self {
+            Self::Page(leaf) => leaf
+                .stored_pairs()
+                .binary_search_by_key(&key, |x| x[0].get()),
+            Self::Delta(leaf) => leaf.binary_search(key),
+            Self::Decompressed(pairs) => pairs.binary_search_by_key(&key, |x| x[0]),
+        }
+    }
+}
 
 /// A magic number that is used to check the validity of an SST
 pub const BEAR_MAGIC: u64 = 0xBEA22;
 
 /// Metadata struct for each sst
-#[repr(C)]
-#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct BTreeMetadata {
     pub magic: u64,        // This is used to check the validity of the metadata
     pub leafs_offset: u64, // Where the leafs start from
@@ -52,31 +226,297 @@ pub struct BTreeMetadata {
     pub bloom_size: u64,   // Bloom filter(including hash functions and bitmap) size in bytes
     pub num_hashes: u64,   // Number of hash functions for the bloom filter
     pub n_entries: u64,    // Number of entries in the SST
+    pub version: u64, // Format version the SST was written with, 0 for SSTs from before versioning
+    pub compression: u64, // Tag of the compression of the leaf pages, 0 for uncompressed
+    pub properties: Option<SstProperties>, // None for SSTs from before format version 4
+    pub filter_type: u64, // Tag of the type of the filter, 0 for a standard bloom filter
+    pub filter_hash: u64, // Tag of the hash algorithm of the filter, the filter type's default before version 9
+    pub range_filter: Option<RangeFilterMetadata>, // None for SSTs without a range filter
+}
+
+/// Metadata for the range filter of an SST, which starts on the page after the bloom filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeFilterMetadata {
+    pub size: u64,        // Range filter size in bytes
+    pub num_hashes: u64,  // Number of hash functions for the bloom filter over the buckets
+    pub bucket_bits: u64, // Base-2 logarithm of the number of keys in each bucket
+}
+
+impl BTreeMetadata {
+    /// The number of bytes of each bloom filter page that hold data.
+    /// Before format version 1, pages had no checksums, so the whole page was used.
+    fn bloom_page_size(&self) -> usize {
+        if self.version == 0 {
+            PAGE_SIZE
+        } else {
+            PAGE_DATA_SIZE
+        }
+    }
+
+    /// The compression of the leaf pages.
+    /// The tag is checked when the SST is opened, so this is only ever wrong for unchecked metadata.
+    pub fn compression(&self) -> Compression {
+        Compression::from_tag(self.compression).unwrap_or_default()
+    }
+
+    /// The type of the bloom filter.
+    /// The tag is checked when the SST is opened, so this is only ever wrong for unchecked metadata.
+    pub fn filter_type(&self) -> FilterType {
+        FilterType::from_tag(self.filter_type).unwrap_or_default()
+    }
+
+    /// The hash algorithm of the bloom filter.
+    /// The tag is checked when the SST is opened, so this is only ever wrong for unchecked metadata.
+    pub fn filter_hash(&self) -> HashType {
+        HashType::from_tag(self.filter_hash).unwrap_or(self.filter_type().default_hash())
+    }
+
+    /// Returns whether the filter type and hash algorithm are known and the bloom filter size fits the type.
+    fn has_valid_filter(&self) -> bool {
+        HashType::from_tag(self.filter_hash).is_some()
+            && FilterType::from_tag(self.filter_type).is_some_and(|filter_type| {
+                filter_type.is_valid_size(self.num_hashes, self.bloom_size)
+            })
+    }
+
+    /// The page that the range filter starts on, right after the bloom filter.
+    pub fn range_filter_offset(&self) -> u64 {
+        let bloom_pages = self.bloom_size.div_ceil(self.bloom_page_size() as u64);
+        self.bloom_offset.saturating_add(bloom_pages)
+    }
+
+    /// Returns whether the range filter, if there is one, is a valid bloom filter
+    /// that fits between the bloom filter and the end of the file.
+    fn has_valid_range_filter(&self) -> bool {
+        let Some(range_filter) = &self.range_filter else {
+            return true;
+        };
+        let pages = range_filter.size.div_ceil(self.bloom_page_size() as u64);
+        range_filter.bucket_bits < u64::BITS as u64
+            && FilterType::Bloom.is_valid_size(range_filter.num_hashes, range_filter.size)
+            && self
+                .range_filter_offset()
+                .checked_add(pages)
+                .is_some_and(|end| end <= self.size)
+    }
+
+    /// The most pairs a leaf page can hold.
+    fn max_leaf_length(&self) -> usize {
+        match self.compression() {
+            Compression::None => PAIRS_PER_CHUNK,
+            _ => MAX_BLOCK_PAIRS,
+        }
+    }
+
+    /// Returns whether the properties are present if and only if the format version has them.
+    fn has_valid_properties(&self) -> bool {
+        self.properties.is_some() == (self.version >= PROPERTIES_VERSION)
+    }
+}
+
+/// The format version that added the properties section to the metadata page.
+const PROPERTIES_VERSION: u64 = 4;
+/// The format version that added the filter type to the properties section.
+const FILTER_TYPE_VERSION: u64 = 5;
+/// The format version that added the hash algorithm of the filter to the properties section.
+const FILTER_HASH_VERSION: u64 = 9;
+
+/// Statistics about the pairs of an SST, recorded when it is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SstProperties {
+    /// The smallest key, or `u64::MAX` if the SST is empty
+    pub min_key: u64,
+    /// The largest key, or 0 if the SST is empty
+    pub max_key: u64,
+    /// The number of pairs whose value is a tombstone
+    pub n_tombstones: u64,
+    /// When the SST was written, in microseconds since the Unix epoch
+    pub created_at: u64,
+    /// The LSM level the SST was written for. It may have been moved down since.
+    pub origin_level: u64,
 }
 
+/// The tags identifying each property in the properties section of the metadata page.
+const MIN_KEY_TAG: u64 = 1;
+const MAX_KEY_TAG: u64 = 2;
+const N_TOMBSTONES_TAG: u64 = 3;
+const CREATED_AT_TAG: u64 = 4;
+const ORIGIN_LEVEL_TAG: u64 = 5;
+/// Not properties of the pairs, but stored alongside them.
+const FILTER_TYPE_TAG: u64 = 6;
+const RANGE_FILTER_SIZE_TAG: u64 = 7;
+const RANGE_FILTER_HASHES_TAG: u64 = 8;
+const RANGE_FILTER_BUCKET_BITS_TAG: u64 = 9;
+const FILTER_HASH_TAG: u64 = 10;
+
+impl SstProperties {
+    /// Returns the properties as (tag, value) pairs.
+    fn tagged(&self) -> [[u64; 2]; 5] {
+        [
+            [MIN_KEY_TAG, self.min_key],
+            [MAX_KEY_TAG, self.max_key],
+            [N_TOMBSTONES_TAG, self.n_tombstones],
+            [CREATED_AT_TAG, self.created_at],
+            [ORIGIN_LEVEL_TAG, self.origin_level],
+        ]
+    }
+
+    /// Reads the properties from (tag, value) pairs, skipping unknown tags.
+    /// Returns None if a property is missing.
+    fn from_tagged(pairs: &[[LeU64; 2]]) -> Option<Self> {
+        let find = |tag: u64| {
+            pairs
+                .iter()
+                .find(|pair| pair[0].get() == tag)
+                .map(|pair| pair[1].get())
+        };
+        Some(Self {
+            min_key: find(MIN_KEY_TAG)?,
+            max_key: find(MAX_KEY_TAG)?,
+            n_tombstones: find(N_TOMBSTONES_TAG)?,
+            created_at: find(CREATED_AT_TAG)?,
+            origin_level: find(ORIGIN_LEVEL_TAG)?,
+        })
+    }
+}
+
+/// Returns whether the given page is the metadata page of an SST from before format version 1,
+/// whose pages have no checksums. The version field of these was always part of the zeroed padding.
+fn is_unchecksummed_metadata(page: &Aligned) -> bool {
+    let metadata = bytemuck::cast_ref::<_, MetadataPage>(page).metadata();
+    metadata.magic == BEAR_MAGIC && metadata.version == 0 && !page.verify_checksum(0)
+}
+
+/// The number of fixed fields in the metadata page.
+const METADATA_FIELDS: usize = 11;
+/// The most properties that fit in the metadata page.
+const MAX_PROPERTIES: usize = (PAGE_DATA_SIZE - (METADATA_FIELDS + 1) * 8) / 16;
+
 /// The struct that points to the actuall metadata
+///
+/// The fields of `BTreeMetadata` but the properties and filter type are stored as little-endian `u64`s
+/// in the order they are declared, so field `i` takes bytes `8 * i..8 * i + 8`.
+/// They are followed by the number of properties and (tag, value) pairs for the properties, the filter type,
+/// and the range filter if there is one,
+/// so that new properties can be added without breaking readers that don't know them.
 #[repr(C, align(4096))]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
 struct MetadataPage {
-    metadata: BTreeMetadata,
-    padding: [u8; PAGE_SIZE - size_of::<BTreeMetadata>()],
+    fields: [LeU64; METADATA_FIELDS],
+    n_properties: LeU64,
+    properties: [[LeU64; 2]; MAX_PROPERTIES],
+    padding: [u8; PAGE_SIZE - (METADATA_FIELDS + 1) * 8 - MAX_PROPERTIES * 16],
 }
 
-impl Default for MetadataPage {
-    fn default() -> Self {
-        Self {
-            metadata: BTreeMetadata {
-                magic: BEAR_MAGIC,
-                leafs_offset: LEAF_OFFSET,
-                nodes_offset: 0x1000,
-                bloom_offset: 0x1000,
-                tree_depth: 0,
-                size: 0,
-                bloom_size: 0,
-                num_hashes: 0,
-                n_entries: 0,
-            },
+impl MetadataPage {
+    /// Encodes the given metadata.
+    fn new(metadata: &BTreeMetadata) -> Self {
+        let fields = [
+            metadata.magic,
+            metadata.leafs_offset,
+            metadata.nodes_offset,
+            metadata.bloom_offset,
+            metadata.tree_depth,
+            metadata.size,
+            metadata.bloom_size,
+            metadata.num_hashes,
+            metadata.n_entries,
+            metadata.version,
+            metadata.compression,
+        ];
+        let mut page = Self {
+            fields: fields.map(LeU64::new),
+            n_properties: LeU64::default(),
+            properties: [Default::default(); _],
             padding: [Default::default(); _],
+        };
+        if let Some(properties) = &metadata.properties {
+            let mut tagged = properties.tagged().to_vec();
+            if metadata.version >= FILTER_TYPE_VERSION {
+                tagged.push([FILTER_TYPE_TAG, metadata.filter_type]);
+            }
+            if metadata.version >= FILTER_HASH_VERSION {
+                tagged.push([FILTER_HASH_TAG, metadata.filter_hash]);
+            }
+            if let Some(range_filter) = &metadata.range_filter {
+                tagged.extend([
+                    [RANGE_FILTER_SIZE_TAG, range_filter.size],
+                    [RANGE_FILTER_HASHES_TAG, range_filter.num_hashes],
+                    [RANGE_FILTER_BUCKET_BITS_TAG, range_filter.bucket_bits],
+                ]);
+            }
+            page.n_properties.set(tagged.len() as u64);
+            for (stored, pair) in page.properties.iter_mut().zip(tagged) {
+                *stored = pair.map(LeU64::new);
+            }
+        }
+        page
+    }
+
+    /// Decodes the metadata stored in the page.
+    fn metadata(&self) -> BTreeMetadata {
+        let [
+            magic,
+            leafs_offset,
+            nodes_offset,
+            bloom_offset,
+            tree_depth,
+            size,
+            bloom_size,
+            num_hashes,
+            n_entries,
+            version,
+            compression,
+        ] = self.fields.map(LeU64::get);
+        let n_properties = self.n_properties.get();
+        let tagged = if version < PROPERTIES_VERSION || n_properties > MAX_PROPERTIES as u64 {
+            None
+        } else {
+            Some(&self.properties[..n_properties as usize])
+        };
+        let properties = tagged.and_then(SstProperties::from_tagged);
+        let find = |tag: u64| {
+            tagged
+                .and_then(|tagged| tagged.iter().find(|pair| pair[0].get() == tag))
+                .map(|pair| pair[1].get())
+        };
+        // Absent before the filter type was recorded, when every filter was a standard bloom filter
+        let filter_type = find(FILTER_TYPE_TAG).unwrap_or(FilterType::Bloom.tag());
+        // Absent before the hash algorithm was recorded, when each filter type had a single one
+        let filter_hash = find(FILTER_HASH_TAG).unwrap_or_else(|| {
+            FilterType::from_tag(filter_type)
+                .unwrap_or_default()
+                .default_hash()
+                .tag()
+        });
+        let range_filter = match (
+            find(RANGE_FILTER_SIZE_TAG),
+            find(RANGE_FILTER_HASHES_TAG),
+            find(RANGE_FILTER_BUCKET_BITS_TAG),
+        ) {
+            (Some(size), Some(num_hashes), Some(bucket_bits)) => Some(RangeFilterMetadata {
+                size,
+                num_hashes,
+                bucket_bits,
+            }),
+            _ => None,
+        };
+        BTreeMetadata {
+            magic,
+            leafs_offset,
+            nodes_offset,
+            bloom_offset,
+            tree_depth,
+            size,
+            bloom_size,
+            num_hashes,
+            n_entries,
+            version,
+            compression,
+            properties,
+            filter_type,
+            filter_hash,
+            range_filter,
         }
     }
 }
@@ -103,7 +543,7 @@ type Leaf = Page;
 pub struct BTreeIter<'a, 'b> {
     sst: &'a Sst,
     file_system: &'b FileSystem,
-    buffered_page: Option<Arc<Page>>,
+    buffered_page: Option<LeafPairs>,
     pub page_number: usize,
     pub item_number: usize,
     range: RangeInclusive<u64>,
@@ -134,12 +574,13 @@ impl<'a, 'b> BTreeIter<'a, 'b> {
 
         let res = BTree::search(sst, *range.start(), file_system)?;
 
-        if let Some(Ok((page_number, item_number)) | Err((page_number, item_number))) = res {
+        if let Some(position) = res {
+            let (Ok(item_number) | Err(item_number)) = position.index;
             Ok(Self {
                 sst,
                 file_system,
-                buffered_page: None,
-                page_number,
+                buffered_page: Some(position.leaf),
+                page_number: position.page_number,
                 item_number,
                 range,
                 ended: false,
@@ -169,17 +610,19 @@ impl<'a, 'b> BTreeIter<'a, 'b> {
                 self.sst.btree_metadata.size as usize,
             );
 
-            let buffered_page: Arc<Page> = match page_bytes {
-                Ok(bytes) => bytemuck::cast_arc(bytes),
-                Err(e) => return Some(Err(e)),
-            };
+            let compression = self.sst.btree_metadata.compression();
+            let buffered_page =
+                match page_bytes.and_then(|bytes| LeafPairs::decode(bytes, compression)) {
+                    Ok(leaf) => leaf,
+                    Err(e) => return Some(Err(e)),
+                };
             self.buffered_page = Some(buffered_page)
         }
 
         let buffered_page = self.buffered_page.as_ref().unwrap();
 
-        let [key, value] = buffered_page.pairs[self.item_number];
-        let item = (key, value);
+        let key = buffered_page.key(self.item_number);
+        let item = (key, buffered_page.value(self.item_number));
 
         if &key > self.range.end() {
             self.ended = true;
@@ -188,7 +631,7 @@ impl<'a, 'b> BTreeIter<'a, 'b> {
 
         self.item_number += 1;
 
-        if self.item_number < buffered_page.length as usize {
+        if self.item_number < buffered_page.len() {
             return Some(Ok(item));
         }
 
@@ -206,10 +649,32 @@ impl<'a, 'b> BTreeIter<'a, 'b> {
     }
 }
 
-type SearchResult = Result<(usize, usize), (usize, usize)>;
+/// How a point lookup finds the leaf page that a key is in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SearchStrategy {
+    /// Traverse the internal nodes from the root down to the leaf.
+    #[default]
+    BTree,
+    /// Binary search over the leaf pages, without reading internal nodes.
+    Binary,
+    /// Interpolation search over the leaf pages, guessing the page from the key.
+    /// Works best when keys are evenly spread.
+    Interpolation,
+}
+
+/// The leaf that a key is in, or would be in, as found by `BTree::search`.
+struct LeafPosition {
+    page_number: usize,
+    leaf: LeafPairs,
+    /// The index of the key in the leaf if it's there, or else the index where it would be inserted
+    index: Result<usize, usize>,
+}
 
 pub struct BTree {}
 
+/// The metadata, filter, and range filter of an SST, as written or opened.
+pub type BTreeParts = (BTreeMetadata, SstFilter, Option<RangeFilter>);
+
 impl BTree {
     /// Creates a static B-tree SST (Sorted String Table) file with the following layout:
     ///
@@ -229,64 +694,127 @@ impl BTree {
     /// │  - Bottom internal nodes point to leaf pages                │
     /// │  - Upper nodes point to lower internal nodes                │
     /// ├─────────────────────────────────────────────────────────────┤
-    /// │ Pages bloom_offset..end: BLOOM FILTER                       │
+    /// │ Pages bloom_offset..: BLOOM FILTER                          │
     /// │  - Probabilistic membership test for keys                   │
     /// │  - Multiple hash functions for low false positive rate      │
+    /// ├─────────────────────────────────────────────────────────────┤
+    /// │ Pages range_filter_offset..end: RANGE FILTER (optional)     │
+    /// │  - Bloom filter over buckets of keys, for ruling out scans  │
     /// └─────────────────────────────────────────────────────────────┘
     /// ```
     ///
     /// # Arguments
     /// * `file_id` - Identifier for the SST file
     /// * `pairs` - Iterator of (key, value) pairs (must be sorted by key)
-    /// * `n_entries_hint` - Estimated number of entries for bloom filter sizing
     /// * `bits_per_entry` - Bloom filter bits per entry (affects false positive rate)
+    /// * `filter_type` - Type of the filter
+    /// * `filter_hash` - Hash algorithm of the filter
+    /// * `range_filter` - Configuration of the range filter, or None for no range filter
+    /// * `compression` - Compression for the leaf pages
+    /// * `lsm_level` - LSM level the SST is written for, recorded as its origin level
     /// * `file_system` - File system to write pages to
     ///
     /// # Returns
     /// * `BTreeMetadata` - Metadata describing the tree structure and offsets
-    /// * `BloomFilter` - The constructed bloom filter for quick negative lookups
+    /// * `SstFilter` - The constructed filter for quick negative lookups,
+    ///   of which only the index is kept if it's partitioned
+    /// * `Option<RangeFilter>` - The constructed range filter for quick empty scans, if configured
+    #[allow(clippy::too_many_arguments)]
     pub fn write(
         file_id: FileId,
-        mut pairs: impl Iterator<Item = Result<(u64, u64), DbError>>,
-        n_entries_hint: usize,
+        pairs: impl Iterator<Item = Result<(u64, u64), DbError>>,
         bits_per_entry: usize,
+        filter_type: FilterType,
+        filter_hash: HashType,
+        range_filter: Option<RangeFilterConfiguration>,
+        compression: Compression,
+        lsm_level: usize,
         file_system: &FileSystem,
-    ) -> Result<(BTreeMetadata, BloomFilter), DbError> {
+    ) -> Result<BTreeParts, DbError> {
+        let mut pairs = pairs.fuse();
         let mut nodes_offset: u64;
         let mut largest_keys: Vec<u64> = Vec::new();
         let mut largest_pages: Vec<u64> = Vec::new();
 
         let mut leaf_count: u64 = 0;
 
-        let mut filter = BloomFilter::empty(n_entries_hint, bits_per_entry);
+        let mut filter = FilterBuilder::new(
+            filter_type,
+            filter_hash,
+            bits_per_entry,
+            file_system.next_seed(),
+        );
+        let mut range_filter = range_filter
+            .map(|configuration| RangeFilterBuilder::new(configuration, file_system.next_seed()));
         let mut n_entries = 0;
+        let mut min_key = None;
+        let mut n_tombstones = 0;
+
+        // Pairs taken from the iterator that haven't been written yet, if compressing
+        let mut pending: Vec<[u64; 2]> = Vec::new();
 
         // Closure to write leaf pages containing actual key-value pairs.
         // Each leaf is filled with pairs from the iterator until full.
         // Tracks the largest key in each leaf for building the index structure.
         let write_next_leaf = |page_bytes: &mut Aligned| {
-            let leaf: &mut Leaf = bytemuck::cast_mut(page_bytes);
-            leaf.length = 0;
-            for (pair, k_v) in leaf.pairs.iter_mut().zip(&mut pairs) {
-                match k_v {
-                    Ok((k, v)) => {
-                        filter.insert(k);
-                        n_entries += 1;
-
-                        *pair = [k, v];
+            let largest_key;
+            if compression != Compression::None {
+                while pending.len() < MAX_BLOCK_PAIRS
+                    && let Some(k_v) = pairs.next()
+                {
+                    let (k, v) = k_v?;
+                    pending.push([k, v]);
+                }
+
+                let length = match compression.compressor() {
+                    Some(compressor) => {
+                        let leaf: &mut CompressedLeaf = bytemuck::cast_mut(page_bytes);
+                        leaf.fill(&pending, compressor)
+                    }
+                    None => delta_leaf::fill(page_bytes, &pending),
+                };
+                for &[k, v] in &pending[..length] {
+                    filter.insert(k);
+                    if let Some(range_filter) = &mut range_filter {
+                        range_filter.insert(k);
+                    }
+                    min_key.get_or_insert(k);
+                    n_tombstones += (v == TOMBSTONE) as u64;
+                }
+                n_entries += length as u64;
+                largest_key = length.checked_sub(1).map(|last| pending[last][0]);
+                pending.drain(..length);
+            } else {
+                let leaf: &mut Leaf = bytemuck::cast_mut(page_bytes);
+                let mut length = 0;
+                for (pair, k_v) in leaf.pairs.iter_mut().zip(&mut pairs) {
+                    match k_v {
+                        Ok((k, v)) => {
+                            filter.insert(k);
+                            if let Some(range_filter) = &mut range_filter {
+                                range_filter.insert(k);
+                            }
+                            min_key.get_or_insert(k);
+                            n_tombstones += (v == TOMBSTONE) as u64;
+                            n_entries += 1;
+
+                            *pair = [k.into(), v.into()];
+                        }
+                        Err(e) => return Err(e),
                     }
-                    Err(e) => return Err(e),
+                    length += 1;
                 }
-                leaf.length += 1;
+                leaf.length.set(length);
+                largest_key = (length as usize).checked_sub(1).map(|last| leaf.key(last));
             }
 
             // Push the largest key in a page to the largest keys vector
-            if leaf.length > 0 {
-                largest_keys.push(leaf.pairs[(leaf.length - 1) as usize][0]);
+            if let Some(largest_key) = largest_key {
+                largest_keys.push(largest_key);
                 largest_pages.push(leaf_count);
                 leaf_count += 1;
             }
-            Ok(leaf.length > 0)
+            Ok(largest_key.is_some())
         };
 
         // Write all leaf pages starting at LEAF_OFFSET
@@ -296,6 +824,7 @@ impl BTree {
 
         // Construct the B-tree index structure in memory from the largest keys.
         // This creates a hierarchical index where each level helps navigate to the correct page.
+        let max_key = largest_keys.last().copied().unwrap_or(0);
         let btree = create_tree(largest_keys, largest_pages, KEYS_PER_NODE);
         let tree_depth = btree.len() as u64;
 
@@ -305,16 +834,18 @@ impl BTree {
         // Each node contains (key, page_number) pairs for navigation.
         let write_next_btree_page = |page_bytes: &mut Aligned| {
             let node: &mut Node = bytemuck::cast_mut(page_bytes);
-            node.length = 0;
+            node.length.set(0);
             let Some(page_iter) = btree_itter.next() else {
                 return Ok(false);
             };
-            for (pair, k_v) in node.pairs.iter_mut().zip(page_iter.into_iter()) {
-                *pair = k_v.into();
-                node.length += 1;
+            let mut length = 0;
+            for (pair, (key, page_number)) in node.pairs.iter_mut().zip(page_iter) {
+                *pair = [key.into(), page_number.into()];
+                length += 1;
             }
+            node.length.set(length);
 
-            Ok(node.length > 0)
+            Ok(length > 0)
         };
 
         // Write internal nodes after the leaf pages
@@ -322,27 +853,68 @@ impl BTree {
             .write_file(file_id.page(nodes_offset as usize), write_next_btree_page)?
             as u64;
 
-        let num_hashes = filter.hash_functions.len() as u64;
-        let mut bloom_bytes_iter = filter.turn_to_bytes().into_iter();
+        // The bloom filter allows quick negative lookups (if a key is definitely not present).
+        // Now that the number of entries is known, bloom filters are sized by it and filled from the leaves.
+        let filter = filter.build(
+            n_entries as usize,
+            read_leaf_keys(
+                file_id,
+                LEAF_OFFSET as usize..nodes_offset as usize,
+                compression,
+                file_system,
+            ),
+        )?;
+        let num_hashes = filter.num_hashes() as u64;
+        let bloom_bytes = filter.turn_to_bytes();
+        let bloom_size = bloom_bytes.len() as u64;
+        // A partitioned filter is read through the buffer pool, so only its index stays in memory
+        let filter = match filter_type {
+            FilterType::Partitioned => SstFilter::Partitioned {
+                index: PartitionIndex::from_bytes(
+                    &bloom_bytes,
+                    num_hashes as usize,
+                    bloom_size,
+                    filter_hash,
+                )
+                .ok_or(DbError::CorruptSst)?,
+                pinned: None,
+            },
+            _ => SstFilter::InMemory(filter),
+        };
 
         let bloom_offset = nodes_written + nodes_offset;
-        let mut bloom_size = 0;
-
-        // Closure to write bloom filter pages.
-        // The bloom filter allows quick negative lookups (if a key is definitely not present).
-        let write_next_bloom_page = |page_bytes: &mut Aligned| {
-            let mut page_length: u64 = 0;
-            for (dest, src) in page_bytes.0.iter_mut().zip(&mut bloom_bytes_iter) {
-                *dest = src;
-                page_length += 1;
+        let mut file_size = bloom_offset
+            + write_bytes(
+                file_id.page(bloom_offset as usize),
+                bloom_bytes,
+                file_system,
+            )?;
+
+        // The range filter allows quick empty scans, and comes right after the bloom filter
+        let range_filter = range_filter.map(RangeFilterBuilder::build);
+        let range_filter_metadata = match &range_filter {
+            Some(range_filter) => {
+                let bytes = range_filter.turn_to_bytes();
+                let metadata = RangeFilterMetadata {
+                    size: bytes.len() as u64,
+                    num_hashes: range_filter.num_hashes() as u64,
+                    bucket_bits: range_filter.bucket_bits() as u64,
+                };
+                file_size += write_bytes(file_id.page(file_size as usize), bytes, file_system)?;
+                Some(metadata)
             }
-            bloom_size += page_length;
-            Ok(page_length > 0)
+            None => None,
         };
 
-        let file_size = bloom_offset
-            + file_system.write_file(file_id.page(bloom_offset as usize), write_next_bloom_page)?
-                as u64;
+        let properties = SstProperties {
+            min_key: min_key.unwrap_or(u64::MAX),
+            max_key,
+            n_tombstones,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_micros() as u64),
+            origin_level: lsm_level as u64,
+        };
 
         // Create metadata structure with all offsets and sizes
         let btree_metadata = BTreeMetadata {
@@ -355,13 +927,19 @@ impl BTree {
             bloom_size,
             num_hashes,
             n_entries,
+            version: FORMAT_VERSION,
+            compression: compression.tag(),
+            properties: Some(properties),
+            filter_type: filter_type.tag(),
+            filter_hash: filter_hash.tag(),
+            range_filter: range_filter_metadata,
         };
 
         let mut write_metadata = 0;
         let write_metadata = |page_bytes: &mut Aligned| {
             write_metadata += 1;
             let metadata_page: &mut MetadataPage = bytemuck::cast_mut(page_bytes);
-            metadata_page.metadata = btree_metadata;
+            *metadata_page = MetadataPage::new(&btree_metadata);
 
             Ok(write_metadata == 1)
         };
@@ -369,16 +947,18 @@ impl BTree {
         // Write metadata at page 0 (METADATA_OFFSET)
         file_system.write_file(file_id.page(METADATA_OFFSET as usize), write_metadata)?;
 
-        Ok((btree_metadata, filter))
+        Ok((btree_metadata, filter, range_filter))
     }
 
-    /// Opens an existing SST file and loads its metadata and bloom filter.
+    /// Opens an existing SST file and loads its metadata, bloom filter, and range filter.
     ///
     /// # Process
     /// 1. Reads and validates metadata from page 0
     /// 2. Validates magic number and basic sanity checks
-    /// 3. Loads bloom filter from the pages specified in metadata
-    /// 4. Reconstructs the BloomFilter object from raw bytes
+    /// 3. Loads bloom filter from the pages specified in metadata,
+    ///    or only the index of a partitioned filter
+    /// 4. Reconstructs the filter from raw bytes
+    /// 5. Does the same for the range filter, if the SST has one
     ///
     /// # Arguments
     /// * `file_id` - Identifier for the SST file to open
@@ -386,59 +966,145 @@ impl BTree {
     ///
     /// # Returns
     /// * `BTreeMetadata` - The tree's metadata (offsets, sizes, depth)
-    /// * `BloomFilter` - The reconstructed bloom filter
+    /// * `SstFilter` - The reconstructed filter, or the index of a partitioned filter
+    /// * `Option<RangeFilter>` - The reconstructed range filter, if the SST has one
     ///
     /// # Errors
     /// * `DbError::CorruptSst` - If magic number is wrong or offsets are invalid
-    pub fn open(
-        file_id: FileId,
-        file_system: &FileSystem,
-    ) -> Result<(BTreeMetadata, BloomFilter), DbError> {
+    /// * `DbError::UnsupportedFormatVersion` - If the SST was written by a newer version of BEARR
+    pub fn open(file_id: FileId, file_system: &FileSystem) -> Result<BTreeParts, DbError> {
         // Read metadata from page 0
-        let metadata_page = file_system.get(file_id.page(METADATA_OFFSET as usize))?;
+        let metadata_page_id = file_id.page(METADATA_OFFSET as usize);
+        let metadata_page = match file_system.get(metadata_page_id) {
+            // SSTs from before format version 1 have no checksums
+            Err(DbError::Corruption { .. })
+                if is_unchecksummed_metadata(&file_system.read_unbuffered(file_id, 0..1)?[0]) =>
+            {
+                file_system.mark_unchecksummed(file_id);
+                file_system.get(metadata_page_id)?
+            }
+            result => result?,
+        };
         let metadata_page: Arc<MetadataPage> = bytemuck::cast_arc(metadata_page);
-        let metadata = metadata_page.metadata;
+        let metadata = metadata_page.metadata();
 
         // Validate magic number
         if metadata.magic != BEAR_MAGIC {
             return Err(DbError::CorruptSst);
         }
+        if metadata.version > FORMAT_VERSION {
+            return Err(DbError::UnsupportedFormatVersion(metadata.version));
+        }
+        if Compression::from_tag(metadata.compression).is_none()
+            || !metadata.has_valid_properties()
+            || !metadata.has_valid_filter()
+            || !metadata.has_valid_range_filter()
+        {
+            return Err(DbError::CorruptSst);
+        }
         // Sanity check: nodes must come after leafs
         if metadata.nodes_offset <= metadata.leafs_offset {
             return Err(DbError::CorruptSst);
         }
 
-        let bloom_offset = metadata.bloom_offset;
-        let bloom_size = metadata.bloom_size;
-        let num_hashes = metadata.num_hashes;
-
-        let bloom_pages_num = bloom_size.div_ceil(PAGE_SIZE as u64);
+        let num_hashes = metadata.num_hashes as usize;
+        let filter = if metadata.filter_type() == FilterType::Partitioned {
+            // Only the index, which records its own size on its first page
+            let first_page = read_bytes(
+                file_id,
+                &metadata,
+                metadata.bloom_offset,
+                PARTITION_SIZE as u64,
+                file_system,
+            )?;
+            let index_size = PartitionIndex::size(&first_page)
+                .filter(|&size| size <= metadata.bloom_size)
+                .ok_or(DbError::CorruptSst)?;
+            let index_bytes = read_bytes(
+                file_id,
+                &metadata,
+                metadata.bloom_offset,
+                index_size,
+                file_system,
+            )?;
+            SstFilter::Partitioned {
+                index: PartitionIndex::from_bytes(
+                    &index_bytes,
+                    num_hashes,
+                    metadata.bloom_size,
+                    metadata.filter_hash(),
+                )
+                .ok_or(DbError::CorruptSst)?,
+                pinned: None,
+            }
+        } else {
+            let bloom_vec = read_bytes(
+                file_id,
+                &metadata,
+                metadata.bloom_offset,
+                metadata.bloom_size,
+                file_system,
+            )?;
+
+            // Reconstruct bloom filter from bytes
+            SstFilter::InMemory(filter::from_bytes(
+                &bloom_vec,
+                num_hashes,
+                metadata.filter_type(),
+                metadata.filter_hash(),
+            ))
+        };
 
-        let mut bloom_vec: aligned_vec::AVec<u8, aligned_vec::ConstAlign<4>> =
-            aligned_vec::AVec::new(4);
-        for page in 0..bloom_pages_num {
-            let bloom_page = file_system.get(file_id.page((bloom_offset + page) as usize))?;
-            // Handle partial last page
-            let end = if page == bloom_pages_num - 1 {
-                (bloom_size % (PAGE_SIZE as u64)) as usize
-            } else {
-                PAGE_SIZE
-            };
+        let range_filter = match &metadata.range_filter {
+            Some(range_filter_metadata) => {
+                let bytes = read_bytes(
+                    file_id,
+                    &metadata,
+                    metadata.range_filter_offset(),
+                    range_filter_metadata.size,
+                    file_system,
+                )?;
+                Some(RangeFilter::from_bytes(
+                    &bytes,
+                    range_filter_metadata.num_hashes as usize,
+                    range_filter_metadata.bucket_bits as u32,
+                ))
+            }
+            None => None,
+        };
 
-            bloom_vec.extend_from_slice(&bloom_page.0[0..end]);
-        }
+        Ok((metadata, filter, range_filter))
+    }
 
-        // Reconstruct bloom filter from bytes
-        let filter = BloomFilter::from_bytes(&bloom_vec, num_hashes as usize);
+    /// Reads the smallest and largest keys of an SST from its first and last leaves.
+    /// Only needed for SSTs from before format version 4, whose properties don't record them.
+    ///
+    /// # Errors
+    /// * `DbError::CorruptSst` - If a leaf is empty or can't be decoded
+    pub fn read_key_range(
+        file_id: FileId,
+        metadata: &BTreeMetadata,
+        file_system: &FileSystem,
+    ) -> Result<RangeInclusive<u64>, DbError> {
+        let read_leaf = |page_number: u64| {
+            let page = file_system.get(file_id.page(page_number as usize))?;
+            let leaf = LeafPairs::decode(page, metadata.compression())?;
+            if leaf.len() == 0 || leaf.len() > metadata.max_leaf_length() {
+                return Err(DbError::CorruptSst);
+            }
+            Ok(leaf)
+        };
 
-        Ok((metadata, filter))
+        let first = read_leaf(metadata.leafs_offset)?;
+        let last = read_leaf(metadata.nodes_offset - 1)?;
+        Ok(first.key(0)..=last.key(last.len() - 1))
     }
 
     /// Retrieves the value associated with a key from the SST.
     ///
     /// # Process
     /// 1. Uses `search()` to locate the key in the B-tree structure
-    /// 2. If found, reads the leaf page and extracts the value
+    /// 2. If found, extracts the value from the leaf page that was read
     /// 3. Returns None if key doesn't exist
     ///
     /// # Arguments
@@ -451,20 +1117,35 @@ impl BTree {
     /// * `None` if the key doesn't exist
     pub fn get(sst: &Sst, key: u64, file_system: &FileSystem) -> Result<Option<u64>, DbError> {
         let res = BTree::search(sst, key, file_system)?;
-        let Some(res) = res else { return Ok(None) };
+        let Some(position) = res else { return Ok(None) };
 
-        let Ok((page_number, item_number)) = res else {
+        let Ok(item_number) = position.index else {
             return Ok(None);
         };
 
-        // Read the leaf page and extract the value
-        let leaf_page = file_system.get(sst.file_id.page(page_number))?;
-        let leaf_node: Arc<Leaf> = bytemuck::cast_arc(leaf_page);
+        // Extract the value from the leaf that search read
+        Ok(Some(position.leaf.value(item_number)))
+    }
 
-        Ok(Some(leaf_node.pairs[item_number][1]))
+    /// Searches for a key in the SST with its search strategy.
+    ///
+    /// # Returns
+    /// * `None` - Key is outside the range of this SST
+    /// * `Some(position)` - The leaf that was read, and where the key is in it,
+    ///   or where it would be inserted if it's not found
+    fn search(
+        sst: &Sst,
+        key: u64,
+        file_system: &FileSystem,
+    ) -> Result<Option<LeafPosition>, DbError> {
+        match sst.search_strategy {
+            SearchStrategy::BTree => Self::tree_search(sst, key, file_system),
+            SearchStrategy::Binary => Self::leaf_search(sst, key, false, file_system),
+            SearchStrategy::Interpolation => Self::leaf_search(sst, key, true, file_system),
+        }
     }
 
-    /// Searches for a key in the B-tree using tree navigation (non-binary search version).
+    /// Searches for a key in the B-tree using tree navigation.
     ///
     /// # Algorithm
     /// 1. Start at root node
@@ -479,23 +1160,22 @@ impl BTree {
     ///
     /// # Returns
     /// * `None` - Key is outside the range of this SST
-    /// * `Some(Ok((page, index)))` - Exact match found at this position
-    /// * `Some(Err((page, index)))` - Key not found, but this is where it would be inserted
-    #[cfg(not(feature = "binary_search"))]
-    fn search(
+    /// * `Some(position)` - The leaf that was read, and where the key is in it,
+    ///   or where it would be inserted if it's not found
+    fn tree_search(
         sst: &Sst,
         key: u64,
         file_system: &FileSystem,
-    ) -> Result<Option<SearchResult>, DbError> {
+    ) -> Result<Option<LeafPosition>, DbError> {
         let nodes_offset = sst.btree_metadata.nodes_offset;
         let leafs_offset = sst.btree_metadata.leafs_offset;
         let tree_depth = sst.btree_metadata.tree_depth;
 
         // Check if key is beyond the maximum key in the tree
-        let root_page = file_system.get(sst.file_id.page(nodes_offset as usize))?;
+        let root_page = sst.node_page(nodes_offset, file_system)?;
         let root_node: Arc<Node> = bytemuck::cast_arc(root_page);
-        assert_ne!(root_node.length, 0);
-        if root_node.pairs[(root_node.length - 1) as usize][0] < key {
+        assert_ne!(root_node.len(), 0);
+        if root_node.key(root_node.len() - 1) < key {
             return Ok(None);
         }
 
@@ -507,13 +1187,12 @@ impl BTree {
 
         // Navigate through internal nodes to find the correct leaf
         for level in 0..tree_depth {
-            let sub_vec: &[[u64; 2]] =
-                &current_node.as_ref().pairs[0..current_node.length as usize];
+            let sub_vec: &[[LeU64; 2]] = current_node.as_ref().stored_pairs();
 
             // Binary search finds the first key >= search key
-            let (Ok(i) | Err(i)) = sub_vec.binary_search_by_key(&key, |x| x[0]);
+            let (Ok(i) | Err(i)) = sub_vec.binary_search_by_key(&key, |x| x[0].get());
             idx = i;
-            node_number = current_node.pairs[idx][1];
+            node_number = current_node.value(idx);
 
             if level == tree_depth - 1 {
                 break;
@@ -521,125 +1200,510 @@ impl BTree {
 
             // Load next level node
             page_number = node_number + nodes_offset;
-            let current_page = file_system.get(sst.file_id.page(page_number as usize))?;
+            let current_page = sst.node_page(page_number, file_system)?;
             current_node = bytemuck::cast_arc(current_page);
         }
 
         // Search within the target leaf page
         page_number = leafs_offset + node_number;
         let leaf_page = file_system.get(sst.file_id.page(page_number as usize))?;
-        let leaf: Arc<Leaf> = bytemuck::cast_arc(leaf_page);
-        let sub_vec: &[[u64; 2]] = &leaf.as_ref().pairs[0..leaf.length as usize];
-
-        let found_exact;
+        let leaf = LeafPairs::decode(leaf_page, sst.btree_metadata.compression())?;
 
-        idx = match sub_vec.binary_search_by_key(&key, |x| x[0]) {
-            Ok(i) => {
-                found_exact = true;
-                i
-            }
-            Err(i) => {
-                found_exact = false;
-                i
-            }
-        };
-
-        let page_number = page_number as usize;
-
-        if found_exact {
-            Ok(Some(Ok((page_number, idx))))
-        } else {
-            Ok(Some(Err((page_number, idx))))
-        }
+        Ok(Some(LeafPosition {
+            page_number: page_number as usize,
+            index: leaf.binary_search(key),
+            leaf,
+        }))
     }
 
-    /// Searches for a key using binary search over leaf pages (alternative implementation).
+    /// Searches for a key by searching over the leaf pages directly, without the internal nodes.
     ///
     /// # Algorithm
-    /// Instead of traversing the tree structure, this performs a binary search
-    /// directly over the leaf pages, which can be more efficient when the tree
-    /// is shallow or when doing random access patterns.
+    /// Instead of traversing the tree structure, this repeatedly probes a leaf page
+    /// between the bounds of where the key can be, and narrows the bounds around it:
+    /// - `SearchStrategy::Binary` probes the middle page
+    /// - `SearchStrategy::Interpolation` guesses the page from where the key falls between
+    ///   the smallest and largest keys that can be in the bounds, assuming evenly spread keys
     ///
-    /// 1. Binary search over leaf page numbers to find the page that could contain the key
-    /// 2. Binary search within that leaf page to find the exact key
+    /// The leaf found is the first one whose largest key is at least the key.
     ///
     /// # Arguments
     /// * `sst` - The SST metadata and identifiers
     /// * `key` - The key to search for
+    /// * `interpolate` - Whether to interpolate rather than bisect
     /// * `file_system` - File system to read pages from
     ///
     /// # Returns
     /// * `None` - Key is outside the range of this SST
-    /// * `Some(Ok((page, index)))` - Exact match found at this position
-    /// * `Some(Err((page, index)))` - Key not found, but this is where it would be inserted
-    #[cfg(feature = "binary_search")]
-    fn search(
+    /// * `Some(position)` - The leaf that was read, and where the key is in it,
+    ///   or where it would be inserted if it's not found
+    fn leaf_search(
         sst: &Sst,
         key: u64,
+        interpolate: bool,
         file_system: &FileSystem,
-    ) -> Result<Option<SearchResult>, DbError> {
-        let nodes_offset = sst.btree_metadata.nodes_offset;
-        let leafs_offset = sst.btree_metadata.leafs_offset;
-
+    ) -> Result<Option<LeafPosition>, DbError> {
         // Check if key is beyond the maximum key in the tree
-        let root_page = file_system.get(sst.file_id.page(nodes_offset as usize))?;
-        let root_node: Arc<Node> = bytemuck::cast_arc(root_page);
-        if root_node.pairs[(root_node.length - 1) as usize][0] < key {
+        if sst.key_range.is_empty() || key > *sst.key_range.end() {
             return Ok(None);
         }
 
-        let mut start_page_num = leafs_offset;
-        let mut end_page_num = nodes_offset - 1;
-        let mut page_number: usize;
+        let compression = sst.btree_metadata.compression();
+        let read_leaf = |page_number: u64| {
+            let page = file_system.get(sst.file_id.page(page_number as usize))?;
+            let leaf = LeafPairs::decode(page, compression)?;
+            if leaf.len() == 0 {
+                return Err(DbError::CorruptSst);
+            }
+            Ok(leaf)
+        };
+
+        // The leaf is within start..=end, and its keys are within start_key..=end_key
+        let mut start = sst.btree_metadata.leafs_offset;
+        let mut end = sst.btree_metadata.nodes_offset - 1;
+        let (mut start_key, mut end_key) = (*sst.key_range.start(), *sst.key_range.end());
 
-        // Binary search over leaf pages to find the right page
-        loop {
-            page_number = (start_page_num + end_page_num) as usize / 2;
-            let middle_page = file_system.get(sst.file_id.page(page_number))?;
-            let leaf: Arc<Leaf> = bytemuck::cast_arc(middle_page);
+        let (page_number, leaf) = loop {
+            if start == end {
+                break (start, read_leaf(start)?);
+            }
+
+            // Always probe before the end, so that the bounds shrink
+            let probe = if interpolate && start_key < end_key {
+                let offset = key.saturating_sub(start_key) as u128 * (end - start) as u128
+                    / (end_key - start_key) as u128;
+                start + (offset as u64).min(end - start - 1)
+            } else {
+                (start + end) / 2
+            };
 
-            // Check if key is before this page's range
-            if key < leaf.pairs[0][0] {
-                if page_number == start_page_num as usize {
-                    page_number = start_page_num as usize;
-                    break;
+            let leaf = read_leaf(probe)?;
+            if key > leaf.key(leaf.len() - 1) {
+                start = probe + 1;
+                start_key = leaf.key(leaf.len() - 1);
+            } else if key < leaf.key(0) && probe > start {
+                end = probe;
+                end_key = leaf.key(0);
+            } else {
+                break (probe, leaf);
+            }
+        };
+
+        Ok(Some(LeafPosition {
+            page_number: page_number as usize,
+            index: leaf.binary_search(key),
+            leaf,
+        }))
+    }
+
+    /// Checks the integrity of the SST file with the given ID, reading it directly from disk,
+    /// and adds what it finds to the given report.
+    ///
+    /// # Checks
+    /// 1. The checksum of every page
+    /// 2. The metadata: magic number, offsets, and file size
+    /// 3. Keys are strictly increasing within and across leaves
+    /// 4. Every key is in the bloom filter, and in the range filter if there is one
+    /// 5. The internal nodes match the tree built from the largest key of each leaf
+    /// 6. The number of entries matches the metadata
+    ///
+    /// Pages with bad checksums are reported and left out of the other checks.
+    ///
+    /// # Arguments
+    /// * `file_id` - Identifier for the SST file to verify
+    /// * `file_system` - File system to read pages from, bypassing its buffer pool
+    /// * `report` - Report to add counts and problems to
+    /// * `pace` - Called after each chunk of pages is read; verification stops early if it returns false
+    ///
+    /// # Errors
+    /// * `DbError::IoError` - If the file can't be read
+    pub fn verify(
+        file_id: FileId,
+        file_system: &FileSystem,
+        report: &mut VerifyReport,
+        pace: &dyn Fn() -> bool,
+    ) -> Result<(), DbError> {
+        let file_len = std::fs::metadata(file_system.path(file_id))?.len();
+        let mut verifier = Verifier {
+            file_id,
+            file_system,
+            file_pages: (file_len / PAGE_SIZE as u64) as usize,
+            report,
+            pace,
+            stopped: false,
+            checksummed: true,
+        };
+        verifier.report.ssts_checked += 1;
+
+        if verifier.file_pages > 0
+            && is_unchecksummed_metadata(&file_system.read_unbuffered(file_id, 0..1)?[0])
+        {
+            verifier.checksummed = false;
+        }
+
+        // Read metadata from page 0
+        let mut metadata = None;
+        verifier.for_each_page(0..1, |_, _, page| {
+            metadata = page.map(|page| bytemuck::cast_ref::<_, MetadataPage>(page).metadata());
+        })?;
+        let Some(metadata) = metadata else {
+            if verifier.file_pages == 0 {
+                verifier.problem(None, ProblemKind::InvalidMetadata);
+            }
+            return Ok(());
+        };
+
+        let bloom_page_size = metadata.bloom_page_size();
+        let bloom_capacity = metadata
+            .size
+            .saturating_sub(metadata.bloom_offset)
+            .saturating_mul(bloom_page_size as u64);
+        if metadata.magic != BEAR_MAGIC
+            || metadata.version > FORMAT_VERSION
+            || metadata.leafs_offset != LEAF_OFFSET
+            || metadata.nodes_offset <= metadata.leafs_offset
+            || metadata.bloom_offset < metadata.nodes_offset
+            || metadata.size < metadata.bloom_offset
+            || metadata.bloom_size > bloom_capacity
+            || !metadata.has_valid_filter()
+            || !metadata.has_valid_range_filter()
+            || Compression::from_tag(metadata.compression).is_none()
+            || !metadata.has_valid_properties()
+        {
+            verifier.problem(Some(METADATA_OFFSET as usize), ProblemKind::InvalidMetadata);
+            return Ok(());
+        }
+
+        if file_len != metadata.size * PAGE_SIZE as u64 {
+            verifier.problem(
+                None,
+                ProblemKind::SizeMismatch {
+                    expected_pages: metadata.size,
+                    actual_pages: file_len.div_ceil(PAGE_SIZE as u64),
+                },
+            );
+        }
+
+        // Load the bloom filter and range filter, which come after the keys they're checked against.
+        // Each starts on a new page, so the data part of every page is kept, and each filter is sliced out.
+        let mut filter_vec: Option<aligned_vec::AVec<u8, aligned_vec::ConstAlign<4>>> =
+            Some(aligned_vec::AVec::new(4));
+        let filter_pages = metadata.bloom_offset as usize..metadata.size as usize;
+        verifier.for_each_page(filter_pages, |_, _, page| {
+            let (Some(page), Some(bytes)) = (page, &mut filter_vec) else {
+                filter_vec = None;
+                return;
+            };
+            bytes.extend_from_slice(&page.0[..bloom_page_size]);
+        })?;
+        let filter_bytes = |offset: u64, size: u64| {
+            let start = ((offset - metadata.bloom_offset) as usize).saturating_mul(bloom_page_size);
+            filter_vec
+                .as_ref()
+                .and_then(|bytes| bytes.get(start..start.checked_add(size as usize)?))
+        };
+        let filter = filter_bytes(metadata.bloom_offset, metadata.bloom_size).map(|bytes| {
+            filter::from_bytes(
+                bytes,
+                metadata.num_hashes as usize,
+                metadata.filter_type(),
+                metadata.filter_hash(),
+            )
+        });
+        let range_filter = metadata.range_filter.and_then(|range_filter| {
+            let bytes = filter_bytes(metadata.range_filter_offset(), range_filter.size)?;
+            Some(RangeFilter::from_bytes(
+                bytes,
+                range_filter.num_hashes as usize,
+                range_filter.bucket_bits as u32,
+            ))
+        });
+
+        // Check leaves, remembering the largest key of each (None if unreadable)
+        let mut largest_keys: Vec<Option<u64>> = Vec::new();
+        let mut previous_key: Option<u64> = None;
+        let mut n_entries: u64 = 0;
+        let mut min_key: Option<u64> = None;
+        let mut n_tombstones: u64 = 0;
+        let leaf_pages = metadata.leafs_offset as usize..metadata.nodes_offset as usize;
+        verifier.for_each_page(leaf_pages, |verifier, page_number, page| {
+            let Some(page) = page else {
+                largest_keys.push(None);
+                return;
+            };
+            let leaf: &Leaf = bytemuck::cast_ref(page);
+            let length = leaf.length.get();
+            if length == 0 || length > metadata.max_leaf_length() as u64 {
+                verifier.problem(Some(page_number), ProblemKind::InvalidLeafLength(length));
+                largest_keys.push(None);
+                return;
+            }
+
+            let compression = metadata.compression();
+            let pairs: Vec<[u64; 2]> = match compression.compressor() {
+                None if compression == Compression::DeltaKeys => {
+                    let Ok(pairs) = DeltaLeaf::decode_all(page, MAX_BLOCK_PAIRS) else {
+                        verifier.problem(Some(page_number), ProblemKind::UndecodableLeaf);
+                        largest_keys.push(None);
+                        return;
+                    };
+                    pairs
                 }
-                end_page_num = page_number as u64;
-            } else if key > leaf.pairs[(leaf.length - 1) as usize][0] {
-                if page_number == start_page_num as usize {
-                    page_number = end_page_num as usize;
-                    break;
+                None => leaf
+                    .stored_pairs()
+                    .iter()
+                    .map(|pair| pair.map(LeU64::get))
+                    .collect(),
+                Some(compressor) => {
+                    let leaf: &CompressedLeaf = bytemuck::cast_ref(page);
+                    let Ok(pairs) = leaf.decompress(compressor) else {
+                        verifier.problem(Some(page_number), ProblemKind::UndecodableLeaf);
+                        largest_keys.push(None);
+                        return;
+                    };
+                    pairs
                 }
-                start_page_num = page_number as u64;
+            };
+            if !pairs.is_sorted_by(|a, b| a[0] < b[0]) {
+                verifier.problem(Some(page_number), ProblemKind::UnsortedKeys);
             }
-            // Key is within this page's range
-            else {
-                break;
+            if previous_key.is_some_and(|previous| previous >= pairs[0][0]) {
+                verifier.problem(Some(page_number), ProblemKind::OverlappingLeaves);
+            }
+            if let Some(filter) = &filter {
+                for &[key, _] in &pairs {
+                    if !filter.query(key) {
+                        verifier.problem(Some(page_number), ProblemKind::MissingFromFilter { key });
+                    }
+                }
+            }
+            if let Some(range_filter) = &range_filter {
+                for &[key, _] in &pairs {
+                    if !range_filter.may_overlap(&(key..=key)) {
+                        verifier.problem(
+                            Some(page_number),
+                            ProblemKind::MissingFromRangeFilter { key },
+                        );
+                    }
+                }
+            }
+
+            min_key.get_or_insert(pairs[0][0]);
+            n_tombstones += pairs.iter().filter(|pair| pair[1] == TOMBSTONE).count() as u64;
+            let largest_key = pairs[pairs.len() - 1][0];
+            previous_key = Some(largest_key);
+            largest_keys.push(Some(largest_key));
+            n_entries += length;
+            verifier.report.entries_checked += length as usize;
+        })?;
+
+        // The internal nodes can only be rebuilt if every leaf was readable
+        let largest_keys: Option<Vec<u64>> = largest_keys.into_iter().collect();
+        let expected_nodes = largest_keys.map(|largest_keys| {
+            let leaf_numbers = (0..largest_keys.len() as u64).collect();
+            create_tree(largest_keys, leaf_numbers, KEYS_PER_NODE)
+        });
+
+        if let Some(expected_nodes) = &expected_nodes {
+            if n_entries != metadata.n_entries {
+                verifier.problem(
+                    None,
+                    ProblemKind::EntryCountMismatch {
+                        expected: metadata.n_entries,
+                        actual: n_entries,
+                    },
+                );
+            }
+            if expected_nodes.len() as u64 != metadata.tree_depth {
+                verifier.problem(None, ProblemKind::SeparatorMismatch);
+            }
+            if let Some(properties) = &metadata.properties
+                && (properties.min_key != min_key.unwrap_or(u64::MAX)
+                    || properties.max_key != previous_key.unwrap_or(0)
+                    || properties.n_tombstones != n_tombstones)
+            {
+                verifier.problem(
+                    Some(METADATA_OFFSET as usize),
+                    ProblemKind::PropertiesMismatch,
+                );
             }
         }
 
-        // Search within the target leaf page
-        let leaf_page = file_system.get(sst.file_id.page(page_number))?;
-        let leaf: Arc<Leaf> = bytemuck::cast_arc(leaf_page);
-        let found_exact;
-
-        let sub_vec: &[[u64; 2]] = &leaf.as_ref().pairs[0..leaf.length as usize];
-        let idx = match sub_vec.binary_search_by_key(&key, |x| x[0]) {
-            Ok(i) => {
-                found_exact = true;
-                i
+        let mut expected_nodes = expected_nodes.map(|nodes| nodes.into_iter().flatten());
+        let node_pages = metadata.nodes_offset as usize..metadata.bloom_offset as usize;
+        verifier.for_each_page(node_pages, |verifier, page_number, page| {
+            let Some(expected_nodes) = &mut expected_nodes else {
+                return;
+            };
+            let expected = expected_nodes.next().unwrap_or_default();
+            let Some(page) = page else {
+                return;
+            };
+            let node: &Node = bytemuck::cast_ref(page);
+            let matches = node.len() == expected.len()
+                && node
+                    .pairs
+                    .iter()
+                    .zip(&expected)
+                    .all(|(&actual, &(key, child))| actual == [key.into(), child.into()]);
+            if !matches {
+                verifier.problem(Some(page_number), ProblemKind::SeparatorMismatch);
+            }
+        })?;
+        if let Some(mut expected_nodes) = expected_nodes
+            && !verifier.stopped
+            && expected_nodes.next().is_some()
+        {
+            verifier.problem(None, ProblemKind::SeparatorMismatch);
+        }
+
+        // Pages past the end recorded in the metadata still get their checksums checked
+        verifier.for_each_page(metadata.size as usize..verifier.file_pages, |_, _, _| {})?;
+
+        Ok(())
+    }
+}
+
+/// Writes the given bytes to pages starting at the given page, filling the data part of each page,
+/// and returns the number of pages written.
+fn write_bytes(
+    starting_page_id: PageId,
+    bytes: Vec<u8>,
+    file_system: &FileSystem,
+) -> Result<u64, DbError> {
+    let mut bytes_iter = bytes.into_iter();
+    let write_next_page = |page_bytes: &mut Aligned| {
+        let mut page_length: u64 = 0;
+        for (dest, src) in page_bytes.0[..PAGE_DATA_SIZE]
+            .iter_mut()
+            .zip(&mut bytes_iter)
+        {
+            *dest = src;
+            page_length += 1;
+        }
+        Ok(page_length > 0)
+    };
+    Ok(file_system.write_file(starting_page_id, write_next_page)? as u64)
+}
+
+/// Reads the given number of bytes from the data part of consecutive pages of an SST,
+/// starting at the given page, through the buffer pool.
+fn read_bytes(
+    file_id: FileId,
+    metadata: &BTreeMetadata,
+    offset: u64,
+    size: u64,
+    file_system: &FileSystem,
+) -> Result<aligned_vec::AVec<u8, aligned_vec::ConstAlign<4>>, DbError> {
+    let page_size = metadata.bloom_page_size() as u64;
+    let mut bytes = aligned_vec::AVec::new(4);
+    for page in 0..size.div_ceil(page_size) {
+        let page_bytes = file_system.get(file_id.page((offset + page) as usize))?;
+        // Handle partial last page
+        let end = (size - page * page_size).min(page_size) as usize;
+        bytes.extend_from_slice(&page_bytes.0[0..end]);
+    }
+    Ok(bytes)
+}
+
+/// Reads the keys of the given leaf pages of an SST with the given compression directly from disk,
+/// a chunk of pages at a time, so that the pages don't displace others in the buffer pool.
+fn read_leaf_keys(
+    file_id: FileId,
+    leaf_pages: Range<usize>,
+    compression: Compression,
+    file_system: &FileSystem,
+) -> impl Iterator<Item = Result<u64, DbError>> {
+    let end = leaf_pages.end;
+    leaf_pages
+        .step_by(LEAF_KEYS_CHUNK_PAGES)
+        .flat_map(move |chunk_start| {
+            let chunk_end = (chunk_start + LEAF_KEYS_CHUNK_PAGES).min(end);
+            let keys = file_system
+                .read_unbuffered(file_id, chunk_start..chunk_end)
+                .and_then(|chunk| {
+                    let mut keys = Vec::new();
+                    for page in chunk {
+                        let leaf = LeafPairs::decode(Arc::new(page), compression)?;
+                        keys.extend((0..leaf.len()).map(|index| leaf.key(index)));
+                    }
+                    Ok(keys)
+                });
+            match keys {
+                Ok(keys) => keys.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
             }
-            Err(i) => {
-                found_exact = false;
-                i
+        })
+}
+
+/// The number of leaf pages to read at once when building the filter of an SST from them.
+const LEAF_KEYS_CHUNK_PAGES: usize = 64;
+/// The number of pages to read at once when verifying an SST.
+const VERIFY_CHUNK_PAGES: usize = 64;
+
+/// State for verifying a single SST file.
+struct Verifier<'a> {
+    file_id: FileId,
+    file_system: &'a FileSystem,
+    /// The number of whole pages in the file.
+    file_pages: usize,
+    report: &'a mut VerifyReport,
+    pace: &'a dyn Fn() -> bool,
+    /// Whether `pace` has asked for verification to stop.
+    stopped: bool,
+    /// Whether the file has page checksums, which it doesn't if it's from before format version 1.
+    checksummed: bool,
+}
+
+impl Verifier<'_> {
+    /// Adds a problem with the file to the report.
+    fn problem(&mut self, page: Option<usize>, kind: ProblemKind) {
+        self.report.problems.push(VerifyProblem {
+            file: self.file_id.name(),
+            page,
+            kind,
+        });
+    }
+
+    /// Reads the given range of pages in chunks, checking their checksums if the file has them,
+    /// and calls `f` with each page, or None if the page has a bad checksum or is past the end of the file.
+    fn for_each_page(
+        &mut self,
+        page_range: Range<usize>,
+        mut f: impl FnMut(&mut Self, usize, Option<&Aligned>),
+    ) -> Result<(), DbError> {
+        let readable_end = page_range.end.min(self.file_pages);
+        for chunk_start in (page_range.start..readable_end).step_by(VERIFY_CHUNK_PAGES) {
+            if self.stopped {
+                return Ok(());
             }
-        };
 
-        if found_exact {
-            Ok(Some(Ok((page_number, idx))))
-        } else {
-            Ok(Some(Err((page_number, idx))))
+            let chunk_end = (chunk_start + VERIFY_CHUNK_PAGES).min(readable_end);
+            let chunk = self
+                .file_system
+                .read_unbuffered(self.file_id, chunk_start..chunk_end)?;
+            for (page_number, page) in (chunk_start..).zip(&chunk) {
+                if !self.checksummed {
+                    f(self, page_number, Some(page));
+                    continue;
+                }
+
+                self.report.pages_checked += 1;
+                if page.verify_checksum(page_number) {
+                    f(self, page_number, Some(page));
+                } else {
+                    self.problem(Some(page_number), ProblemKind::ChecksumMismatch);
+                    f(self, page_number, None);
+                }
+            }
+
+            self.stopped = !(self.pace)();
+        }
+
+        for page_number in readable_end.max(page_range.start)..page_range.end {
+            f(self, page_number, None);
         }
+        Ok(())
     }
 }
 
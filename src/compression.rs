@@ -0,0 +1,388 @@
+use serde::{Deserialize, Serialize};
+
+use crate::DbError;
+
+/// A codec for the key-value pairs of a leaf page.
+pub trait Compressor {
+    /// Appends the compressed form of the given pairs, which are sorted by key, to `out`.
+    fn compress(&self, pairs: &[[u64; 2]], out: &mut Vec<u8>);
+
+    /// Decompresses `n_pairs` pairs from the given bytes and appends them to `out`.
+    ///
+    /// Returns `DbError::CorruptSst` if the bytes don't hold exactly that many pairs.
+    fn decompress(
+        &self,
+        bytes: &[u8],
+        n_pairs: usize,
+        out: &mut Vec<[u64; 2]>,
+    ) -> Result<(), DbError>;
+}
+
+/// The compression used for the leaf pages of an SST.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Leaf pages hold uncompressed pairs.
+    #[default]
+    None,
+    /// Keys are stored as varint-encoded differences from the previous key,
+    /// and values as bit-packed offsets from the smallest value in the page (frame of reference).
+    /// Works best for dense keys such as timestamps.
+    DeltaFor,
+    /// Pairs are compressed with the general-purpose LZ4 block format.
+    Lz4,
+    /// Keys are stored as varint-encoded differences from the previous key, with restart points
+    /// holding full keys for binary search, and values as varints.
+    /// Unlike the other compressions, leaves are searched in place without being decompressed.
+    DeltaKeys,
+}
+
+impl Compression {
+    /// The number identifying the compression in SST metadata.
+    pub fn tag(self) -> u64 {
+        match self {
+            Compression::None => 0,
+            Compression::DeltaFor => 1,
+            Compression::Lz4 => 2,
+            Compression::DeltaKeys => 3,
+        }
+    }
+
+    /// Returns the compression with the given tag, or None if there is no such compression.
+    pub fn from_tag(tag: u64) -> Option<Self> {
+        match tag {
+            0 => Some(Compression::None),
+            1 => Some(Compression::DeltaFor),
+            2 => Some(Compression::Lz4),
+            3 => Some(Compression::DeltaKeys),
+            _ => None,
+        }
+    }
+
+    /// Returns the codec for the compression, or None if leaves aren't compressed as a block.
+    pub fn compressor(self) -> Option<&'static dyn Compressor> {
+        match self {
+            Compression::None | Compression::DeltaKeys => None,
+            Compression::DeltaFor => Some(&DeltaForCompressor),
+            Compression::Lz4 => Some(&Lz4Compressor),
+        }
+    }
+}
+
+/// The compression for the leaf pages of SSTs at each LSM level, starting from the top.
+/// Levels past the last one given use its compression, and if none are given, nothing is compressed.
+///
+/// Holds at most `LevelCompression::MAX_LEVELS` levels inline, so that configurations stay `Copy`.
+/// It is stored in configuration files as a list.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(try_from = "Vec<Compression>", into = "Vec<Compression>")]
+pub struct LevelCompression {
+    levels: [Compression; LevelCompression::MAX_LEVELS],
+    len: usize,
+}
+
+impl LevelCompression {
+    /// The most levels whose compression can be given separately.
+    pub const MAX_LEVELS: usize = 16;
+
+    /// Returns the compression with the given compressions for the top levels.
+    ///
+    /// Panics if more than `LevelCompression::MAX_LEVELS` are given.
+    pub fn new(levels: &[Compression]) -> Self {
+        Self::try_from(levels.to_vec()).unwrap()
+    }
+
+    /// Returns the compressions given for the top levels.
+    pub fn levels(&self) -> &[Compression] {
+        &self.levels[..self.len]
+    }
+
+    /// Returns the compression for the leaf pages of SSTs at the given level.
+    pub fn level(&self, level: usize) -> Compression {
+        let levels = self.levels();
+        levels
+            .get(level)
+            .or(levels.last())
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl TryFrom<Vec<Compression>> for LevelCompression {
+    type Error = String;
+
+    fn try_from(levels: Vec<Compression>) -> Result<Self, Self::Error> {
+        if levels.len() > Self::MAX_LEVELS {
+            return Err(format!(
+                "compression given for {} levels, at most {} are supported",
+                levels.len(),
+                Self::MAX_LEVELS
+            ));
+        }
+        let mut result = Self::default();
+        result.levels[..levels.len()].copy_from_slice(&levels);
+        result.len = levels.len();
+        Ok(result)
+    }
+}
+
+impl From<LevelCompression> for Vec<Compression> {
+    fn from(compression: LevelCompression) -> Self {
+        compression.levels().to_vec()
+    }
+}
+
+/// Delta and varint encoding for keys, with frame-of-reference bit packing for values.
+///
+/// Layout: the first key and then the difference between each key and the previous one as varints,
+/// followed by the smallest value as a varint, the number of bits per value as a byte,
+/// and each value minus the smallest value in that many bits, starting from the least significant bit.
+pub struct DeltaForCompressor;
+
+impl Compressor for DeltaForCompressor {
+    fn compress(&self, pairs: &[[u64; 2]], out: &mut Vec<u8>) {
+        let mut previous_key = 0;
+        for &[key, _] in pairs {
+            write_varint(key - previous_key, out);
+            previous_key = key;
+        }
+
+        let min_value = pairs.iter().map(|&[_, value]| value).min().unwrap_or(0);
+        let max_offset = pairs.iter().map(|&[_, value]| value - min_value).max();
+        let width = u64::BITS - max_offset.unwrap_or(0).leading_zeros();
+        write_varint(min_value, out);
+        out.push(width as u8);
+
+        let mut buffer: u128 = 0;
+        let mut buffered_bits = 0;
+        for &[_, value] in pairs {
+            buffer |= ((value - min_value) as u128) << buffered_bits;
+            buffered_bits += width;
+            while buffered_bits >= 8 {
+                out.push(buffer as u8);
+                buffer >>= 8;
+                buffered_bits -= 8;
+            }
+        }
+        if buffered_bits > 0 {
+            out.push(buffer as u8);
+        }
+    }
+
+    fn decompress(
+        &self,
+        bytes: &[u8],
+        n_pairs: usize,
+        out: &mut Vec<[u64; 2]>,
+    ) -> Result<(), DbError> {
+        let mut bytes = bytes.iter().copied();
+        let start = out.len();
+        out.try_reserve(n_pairs)?;
+
+        let mut key: u64 = 0;
+        for _ in 0..n_pairs {
+            key = key
+                .checked_add(read_varint(&mut bytes)?)
+                .ok_or(DbError::CorruptSst)?;
+            out.push([key, 0]);
+        }
+
+        let min_value = read_varint(&mut bytes)?;
+        let width = bytes.next().ok_or(DbError::CorruptSst)? as u32;
+        if width > u64::BITS {
+            return Err(DbError::CorruptSst);
+        }
+        let mask = match width {
+            0 => 0,
+            width => u64::MAX >> (u64::BITS - width),
+        };
+
+        let mut buffer: u128 = 0;
+        let mut buffered_bits = 0;
+        for pair in &mut out[start..] {
+            while buffered_bits < width {
+                buffer |= (bytes.next().ok_or(DbError::CorruptSst)? as u128) << buffered_bits;
+                buffered_bits += 8;
+            }
+            let offset = buffer as u64 & mask;
+            buffer >>= width;
+            buffered_bits -= width;
+            pair[1] = min_value.checked_add(offset).ok_or(DbError::CorruptSst)?;
+        }
+
+        if bytes.next().is_some() {
+            return Err(DbError::CorruptSst);
+        }
+        Ok(())
+    }
+}
+
+/// Writes the value as a little-endian base-128 varint: 7 bits per byte,
+/// with the most significant bit set on every byte but the last.
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a varint written by `write_varint`.
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Result<u64, DbError> {
+    let mut value: u64 = 0;
+    for shift in (0..u64::BITS).step_by(7) {
+        let byte = bytes.next().ok_or(DbError::CorruptSst)?;
+        let bits = (byte & 0x7f) as u64;
+        if bits << shift >> shift != bits {
+            return Err(DbError::CorruptSst);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DbError::CorruptSst)
+}
+
+/// LZ4 block compression of the pairs, stored as little-endian keys and values.
+pub struct Lz4Compressor;
+
+impl Compressor for Lz4Compressor {
+    fn compress(&self, pairs: &[[u64; 2]], out: &mut Vec<u8>) {
+        let bytes: Vec<u8> = pairs
+            .iter()
+            .flatten()
+            .flat_map(|number| number.to_le_bytes())
+            .collect();
+        out.extend_from_slice(&lz4_flex::block::compress(&bytes));
+    }
+
+    fn decompress(
+        &self,
+        bytes: &[u8],
+        n_pairs: usize,
+        out: &mut Vec<[u64; 2]>,
+    ) -> Result<(), DbError> {
+        let pair_size = 2 * size_of::<u64>();
+        let bytes = lz4_flex::block::decompress(bytes, n_pairs * pair_size)
+            .map_err(|_| DbError::CorruptSst)?;
+        if bytes.len() != n_pairs * pair_size {
+            return Err(DbError::CorruptSst);
+        }
+
+        out.try_reserve(n_pairs)?;
+        let (numbers, _) = bytes.as_chunks::<8>();
+        for pair in numbers.chunks_exact(2) {
+            out.push([u64::from_le_bytes(pair[0]), u64::from_le_bytes(pair[1])]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPRESSIONS: [Compression; 2] = [Compression::DeltaFor, Compression::Lz4];
+
+    fn round_trip(compression: Compression, pairs: &[[u64; 2]]) -> Vec<u8> {
+        let compressor = compression.compressor().unwrap();
+        let mut bytes = Vec::new();
+        compressor.compress(pairs, &mut bytes);
+
+        let mut decompressed = vec![[7, 7]];
+        compressor
+            .decompress(&bytes, pairs.len(), &mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed[0], [7, 7]);
+        assert_eq!(&decompressed[1..], pairs, "{compression:?}");
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = fastrand::Rng::with_seed(0xBEA22);
+        let cases: Vec<Vec<[u64; 2]>> = vec![
+            vec![],
+            vec![[0, 0]],
+            vec![[u64::MAX, u64::MAX]],
+            vec![[0, u64::MAX], [1, 0], [u64::MAX, 12345]],
+            (0..1000).map(|i| [i * 3, i % 5]).collect(),
+            (0..1000).map(|i| [i << 40, u64::MAX - i]).collect(),
+            (0..1000).map(|_| rng.u64(..)).map(|k| [k, k]).collect(),
+        ];
+        for mut pairs in cases {
+            pairs.sort();
+            pairs.dedup_by_key(|pair| pair[0]);
+            for compression in COMPRESSIONS {
+                round_trip(compression, &pairs);
+            }
+        }
+    }
+
+    #[test]
+    fn test_level_compression() {
+        let compression = LevelCompression::new(&[Compression::None, Compression::Lz4]);
+        assert_eq!(compression.level(0), Compression::None);
+        assert_eq!(compression.level(1), Compression::Lz4);
+        assert_eq!(compression.level(5), Compression::Lz4);
+        assert_eq!(LevelCompression::default().level(3), Compression::None);
+
+        let json = serde_json::to_string(&compression).unwrap();
+        assert_eq!(json, r#"["None","Lz4"]"#);
+        assert_eq!(
+            serde_json::from_str::<LevelCompression>(&json).unwrap(),
+            compression
+        );
+        let too_many = vec![Compression::Lz4; LevelCompression::MAX_LEVELS + 1];
+        assert!(
+            serde_json::to_string(&too_many)
+                .map(|json| serde_json::from_str::<LevelCompression>(&json))
+                .unwrap()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_tags() {
+        let others = [Compression::None, Compression::DeltaKeys];
+        for compression in others.into_iter().chain(COMPRESSIONS) {
+            assert_eq!(Compression::from_tag(compression.tag()), Some(compression));
+        }
+        assert_eq!(Compression::from_tag(4), None);
+        assert!(Compression::None.compressor().is_none());
+        assert!(Compression::DeltaKeys.compressor().is_none());
+    }
+
+    /// Tests that dense timestamp keys with small values compress about 4x with delta/FOR.
+    #[test]
+    fn test_dense_keys() {
+        let mut rng = fastrand::Rng::with_seed(0xBEA22);
+        let start = 1_700_000_000_000;
+        let pairs: Vec<[u64; 2]> = (0..1000)
+            .map(|i| [start + i * 10 + rng.u64(..10), rng.u64(..1 << 20)])
+            .collect();
+        let bytes = round_trip(Compression::DeltaFor, &pairs);
+        assert!(bytes.len() * 4 <= pairs.len() * 16, "{}", bytes.len());
+    }
+
+    #[test]
+    fn test_corrupt() {
+        let pairs: Vec<[u64; 2]> = (0..100).map(|i| [i * 1000, i]).collect();
+        for compression in COMPRESSIONS {
+            let compressor = compression.compressor().unwrap();
+            let mut bytes = Vec::new();
+            compressor.compress(&pairs, &mut bytes);
+
+            let mut out = Vec::new();
+            let truncated = &bytes[..bytes.len() - 1];
+            assert_eq!(
+                compressor.decompress(truncated, pairs.len(), &mut out),
+                Err(DbError::CorruptSst)
+            );
+            assert_eq!(
+                compressor.decompress(&bytes, pairs.len() + 1, &mut out),
+                Err(DbError::CorruptSst)
+            );
+        }
+    }
+}
@@ -2,14 +2,19 @@ use std::{
     fs::{self, File},
     ops::RangeInclusive,
     path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    DbError,
-    file_system::FileSystem,
-    lsm::{LsmConfiguration, LsmMetadata, LsmTree, TOMBSTONE},
+    DbError, FORMAT_VERSION, SstProperties,
+    eviction::EvictionType,
+    file_system::{FileId, FileSystem},
+    filter::FilterCounts,
+    lsm::{FilterReport, LsmConfiguration, LsmMetadata, LsmTree, TOMBSTONE},
+    sst::Sst,
+    verify::{self, Scrubber, VerifyReport},
 };
 
 /// An open connection to a database.
@@ -17,9 +22,12 @@ pub struct Database {
     name: PathBuf,
     lsm: LsmTree,
     file_system: FileSystem,
-    wal_buffer: Vec<(u64, u64)>,
+    wal_buffer: Vec<WalEntry>,
     wal_file: File,
     wal_enabled: bool,
+    wal_archiving: bool,
+    /// The sequence number of the most recent write. Sequence numbers start at 1.
+    last_sequence: u64,
 }
 
 /// Configuration options for a database.
@@ -30,6 +38,14 @@ pub struct DbConfiguration {
     /// Number of pages that the buffer pool can hold.
     /// Must be at least 16.
     pub buffer_pool_capacity: usize,
+    /// The number of shards that the buffer pool is split into, each with its own lock
+    /// and an even part of the capacity, so that concurrent reads rarely wait on each other.
+    /// None keeps it in a single shard. Each shard must hold at least 16 pages.
+    #[serde(default)]
+    pub buffer_pool_shards: Option<usize>,
+    /// The policy that each shard of the buffer pool evicts pages with.
+    #[serde(default)]
+    pub eviction: EvictionType,
     /// When writing multiple pages to a file,
     /// the number of pages to buffer before issuing an I/O call.
     /// Must be nonzero.
@@ -39,15 +55,29 @@ pub struct DbConfiguration {
     pub readahead_buffering: usize,
     /// Number of operations to buffer before flushing to WAL
     pub wal_buffer_size: Option<usize>,
+    /// Whether to move the WAL into an archive folder when the memtable is flushed,
+    /// instead of truncating it, so that it can be used for point-in-time recovery.
+    /// Requires the WAL to be enabled.
+    #[serde(default)]
+    pub wal_archiving: bool,
+    /// The seed that every hash table and filter derives its seeds from,
+    /// so that runs can be reproduced exactly, or None to choose seeds at random.
+    #[serde(default)]
+    pub seed: Option<u64>,
 }
 
 impl DbConfiguration {
     fn validate(&self) -> Result<(), DbError> {
         self.lsm_configuration.validate()?;
         if self.buffer_pool_capacity >= 16
+            && self
+                .buffer_pool_shards
+                .is_none_or(|x| x > 0 && self.buffer_pool_capacity / x >= 16)
+            && self.eviction.is_valid()
             && self.write_buffering > 0
             && self.readahead_buffering > 0
             && self.wal_buffer_size.is_none_or(|x| x > 0)
+            && (!self.wal_archiving || self.wal_buffer_size.is_some())
         {
             Ok(())
         } else {
@@ -56,15 +86,95 @@ impl DbConfiguration {
     }
 }
 
+/// The contents of the configuration file.
+#[derive(Serialize, Deserialize, Debug)]
+struct ConfigFile {
+    /// The format version that every file in the database has been written with or upgraded to.
+    /// Databases from before format versions were recorded don't have this, and are version 0.
+    #[serde(default)]
+    format_version: u64,
+    #[serde(flatten)]
+    configuration: DbConfiguration,
+}
+
+impl ConfigFile {
+    /// Reads the configuration file of the database located at the given path.
+    ///
+    /// Returns `DbError::UnsupportedFormatVersion` if the database was written by a newer version of BEARR.
+    fn read(name: &Path) -> Result<Self, DbError> {
+        let config_file = File::open(name.join(CONFIG_FILENAME))?;
+        let config: Self = serde_json::from_reader(config_file)?;
+        if config.format_version > FORMAT_VERSION {
+            return Err(DbError::UnsupportedFormatVersion(config.format_version));
+        }
+        Ok(config)
+    }
+
+    /// Writes the configuration file of the database located at the given path,
+    /// replacing it if it exists.
+    fn write(&self, name: &Path) -> Result<(), DbError> {
+        let config_file = File::create(name.join(CONFIG_FILENAME))?;
+        serde_json::to_writer_pretty(&config_file, self)?;
+        config_file.sync_all()?;
+        Ok(())
+    }
+}
+
 /// Metadata for a database
 #[derive(Serialize, Deserialize, Debug)]
 struct DbMetadata {
     lsm_metadata: LsmMetadata,
+    /// The sequence number of the most recent write stored in the LSM tree's SSTs.
+    #[serde(default)]
+    last_sequence: u64,
+}
+
+/// A write recorded in the WAL.
+#[derive(Clone, Copy, Debug)]
+struct WalEntry {
+    sequence: u64,
+    /// Microseconds since the Unix epoch.
+    timestamp: u64,
+    key: u64,
+    value: u64,
+}
+
+/// What `Database::repair` found and recovered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepairReport {
+    /// The number of SSTs recovered in each level of the LSM tree, from the top level down.
+    pub ssts_per_level: Vec<usize>,
+    /// The total number of entries in the recovered SSTs, including overwritten entries and tombstones.
+    pub sst_entries: usize,
+    /// The names of SST files that failed validation.
+    /// Each was renamed with a `.corrupt` extension and left in the database folder.
+    pub corrupt_files: Vec<String>,
+    /// The names of SST files left over from a merge or upgrade that was interrupted
+    /// before they replaced the SSTs they were written from, which are still there.
+    /// Each was renamed with a `.leftover` extension and left in the database folder.
+    pub leftover_files: Vec<String>,
+    /// The number of writes replayed from the WAL.
+    pub wal_entries: usize,
+    /// The sequence number of the most recent recovered write.
+    pub last_sequence: u64,
+}
+
+/// The point up to which `Database::recover` replays archived writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecoveryTarget {
+    /// Replay every write with a sequence number up to and including this one.
+    Sequence(u64),
+    /// Replay every write made at or before this time.
+    Time(SystemTime),
 }
 
 const CONFIG_FILENAME: &str = "config.json";
 const METADATA_FILENAME: &str = "metadata.json";
 const LOG_FILENAME: &str = "WAL.log";
+const WAL_ARCHIVE_DIRNAME: &str = "wal_archive";
+
+/// The files in a database folder other than SSTs, which change over the lifetime of the database.
+pub(crate) const MUTABLE_FILENAMES: [&str; 3] = [CONFIG_FILENAME, METADATA_FILENAME, LOG_FILENAME];
 
 impl Database {
     /// Creates and returns an empty database with the given configuration,
@@ -84,10 +194,15 @@ impl Database {
         fs::create_dir(name)?;
 
         let config_file = File::create_new(name.join(CONFIG_FILENAME))?;
-        serde_json::to_writer_pretty(config_file, &configuration)?;
+        let config = ConfigFile {
+            format_version: FORMAT_VERSION,
+            configuration,
+        };
+        serde_json::to_writer_pretty(config_file, &config)?;
 
         let metadata = DbMetadata {
             lsm_metadata: LsmMetadata::empty(),
+            last_sequence: 0,
         };
         let metadata_file = File::create_new(name.join(METADATA_FILENAME))?;
         serde_json::to_writer_pretty(metadata_file, &metadata)?;
@@ -99,16 +214,21 @@ impl Database {
 
     /// Opens the database located at the given path.
     ///
+    /// Databases written in older format versions can be opened,
+    /// and `Database::upgrade` can be used to rewrite them in the current one.
+    ///
     /// Returns `DbError::IoError` if:
     /// - The configuration and/or metadata files do not exist at the path.
     /// - There are problems with reading files.
     ///
+    /// Returns `DbError::UnsupportedFormatVersion` if the database or one of its SSTs
+    /// was written by a newer version of BEARR.
+    ///
     /// Also returns errors if creation of the file system struct or LSM tree fails.
     pub fn open(name: impl AsRef<Path>) -> Result<Self, DbError> {
         let name = name.as_ref();
 
-        let config_file = File::open(name.join(CONFIG_FILENAME))?;
-        let configuration: DbConfiguration = serde_json::from_reader(config_file)?;
+        let configuration = ConfigFile::read(name)?.configuration;
 
         let metadata_file = File::open(name.join(METADATA_FILENAME))?;
         let metadata: DbMetadata = serde_json::from_reader(metadata_file)?;
@@ -132,11 +252,14 @@ impl Database {
     ) -> Result<Self, DbError> {
         configuration.validate()?;
 
-        let file_system = FileSystem::new(
+        let file_system = FileSystem::sharded(
             name,
             configuration.buffer_pool_capacity,
+            configuration.buffer_pool_shards.unwrap_or(1),
+            configuration.eviction,
             configuration.write_buffering,
             configuration.readahead_buffering,
+            configuration.seed,
         )?;
 
         let lsm = LsmTree::open(
@@ -150,6 +273,10 @@ impl Database {
             .create(true)
             .open(name.join(LOG_FILENAME))?;
 
+        if configuration.wal_archiving {
+            fs::create_dir_all(name.join(WAL_ARCHIVE_DIRNAME))?;
+        }
+
         Ok(Self {
             name: name.to_path_buf(),
             lsm,
@@ -157,6 +284,8 @@ impl Database {
             wal_buffer: Vec::with_capacity(configuration.wal_buffer_size.unwrap_or(0)),
             wal_file,
             wal_enabled: configuration.wal_buffer_size.is_some(),
+            wal_archiving: configuration.wal_archiving,
+            last_sequence: metadata.last_sequence,
         })
     }
 
@@ -179,15 +308,7 @@ impl Database {
             return Err(DbError::InvalidValue);
         }
 
-        if self.wal_enabled {
-            // Add to WAL buffer
-            self.wal_buffer.push((key, value));
-
-            // Flush WAL buffer if it's full
-            if self.wal_buffer.len() >= self.wal_buffer.capacity() {
-                self.flush_wal_buffer()?;
-            }
-        }
+        self.log_write(key, value)?;
 
         let sst_flushed = self.lsm.put(key, value, &self.file_system)?;
 
@@ -206,14 +327,8 @@ impl Database {
     ///
     /// Returns an error if deletion fails.
     pub fn delete(&mut self, key: u64) -> Result<(), DbError> {
-        if self.wal_enabled {
-            // Add to WAL buffer with TOMBSTONE
-            self.wal_buffer.push((key, TOMBSTONE));
-            // Flush WAL buffer if it's full
-            if self.wal_buffer.len() >= self.wal_buffer.capacity() {
-                self.flush_wal_buffer()?;
-            }
-        }
+        self.log_write(key, TOMBSTONE)?;
+
         let sst_flushed = self.lsm.delete(key, &self.file_system)?;
 
         if sst_flushed {
@@ -253,7 +368,10 @@ impl Database {
         self.lsm.flush_memtable(&self.file_system)?;
 
         let lsm_metadata = self.lsm.metadata();
-        let metadata = DbMetadata { lsm_metadata };
+        let metadata = DbMetadata {
+            lsm_metadata,
+            last_sequence: self.last_sequence,
+        };
         let metadata_file = fs::OpenOptions::new()
             .create(true)
             .truncate(true)
@@ -268,6 +386,399 @@ impl Database {
         Ok(())
     }
 
+    /// Creates an openable, point-in-time copy of the database in a new folder at the given path.
+    ///
+    /// The memtable is flushed first, so the copy contains every write made before this call.
+    /// SST files are immutable, so they are hard-linked into the new folder rather than copied.
+    /// The configuration, metadata, and write-ahead log files are copied.
+    /// The database remains usable afterwards, and later writes do not affect the copy.
+    ///
+    /// Returns `DbError::IoError` if:
+    /// - The path already exists.
+    /// - A parent of the path does not exist.
+    /// - The path is on a different file system, so hard links cannot be created.
+    /// - There are problems with creating/writing to files.
+    ///
+    /// Also returns errors if flushing fails.
+    pub fn checkpoint(&mut self, target: impl AsRef<Path>) -> Result<(), DbError> {
+        let target = target.as_ref();
+
+        self.flush()?;
+
+        fs::create_dir(target)?;
+
+        for filename in self.sst_filenames() {
+            fs::hard_link(self.name.join(&filename), target.join(&filename))?;
+        }
+
+        for filename in MUTABLE_FILENAMES {
+            fs::copy(self.name.join(filename), target.join(filename))?;
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds the metadata file of the database located at the given path
+    /// from the SST files in its folder, for when the metadata file is lost or corrupted,
+    /// then replays the WAL and flushes it into the LSM tree.
+    ///
+    /// Every SST is validated by opening it. Those that fail are renamed with a `.corrupt` extension
+    /// so they don't get in the way, and so are SSTs left over from an interrupted merge or upgrade,
+    /// with a `.leftover` extension, since their entries are still in the SSTs they were written from.
+    /// The remaining SSTs of each level are renamed if needed
+    /// so that they are numbered consecutively, keeping their order.
+    /// The bottom leveling of the LSM tree isn't stored in the SSTs, so it is estimated.
+    ///
+    /// The sequence numbers of writes that were already flushed to SSTs are only known
+    /// if there are archived WAL segments, so `last_sequence` may start over otherwise.
+    ///
+    /// Returns `DbError::IoError` if the configuration file does not exist at the path,
+    /// or if there are problems with reading, writing, or renaming files.
+    ///
+    /// Also returns errors if opening or flushing the repaired database fails.
+    pub fn repair(name: impl AsRef<Path>) -> Result<RepairReport, DbError> {
+        let name = name.as_ref();
+
+        let configuration = ConfigFile::read(name)?.configuration;
+        configuration.validate()?;
+
+        let mut file_ids = Vec::new();
+        for entry in fs::read_dir(name)? {
+            let filename = entry?.file_name();
+            if let Some(file_id) = filename.to_str().and_then(FileId::parse) {
+                file_ids.push(file_id);
+            }
+        }
+        file_ids.sort_by_key(|file_id| (file_id.lsm_level, file_id.sst_number));
+
+        // Validate SSTs with a file system that is dropped before anything is renamed,
+        // so no stale pages stay cached
+        let mut valid_ssts = Vec::new();
+        let mut corrupt_files = Vec::new();
+        {
+            let file_system = FileSystem::new(
+                name,
+                configuration.buffer_pool_capacity,
+                configuration.write_buffering,
+                configuration.readahead_buffering,
+            )?;
+            for file_id in file_ids {
+                match Sst::open(file_id, &file_system) {
+                    Ok(sst) => {
+                        valid_ssts.push((file_id, sst.num_entries(), sst.properties().copied()))
+                    }
+                    Err(_) => corrupt_files.push(file_id.name()),
+                }
+            }
+        }
+
+        let (leftover_ssts, valid_ssts): (Vec<_>, Vec<_>) =
+            valid_ssts
+                .iter()
+                .copied()
+                .partition(|&(file_id, _, properties)| {
+                    is_leftover_sst(file_id, properties, &valid_ssts)
+                });
+        let leftover_files: Vec<String> = leftover_ssts
+            .into_iter()
+            .map(|(file_id, _, _)| file_id.name())
+            .collect();
+
+        for filename in &corrupt_files {
+            fs::rename(
+                name.join(filename),
+                name.join(format!("{filename}.corrupt")),
+            )?;
+        }
+        for filename in &leftover_files {
+            fs::rename(
+                name.join(filename),
+                name.join(format!("{filename}.leftover")),
+            )?;
+        }
+
+        let num_levels = valid_ssts
+            .last()
+            .map_or(0, |(file_id, _, _)| file_id.lsm_level + 1);
+        let mut entries_per_sst = vec![Vec::new(); num_levels];
+        for (file_id, n_entries, _) in valid_ssts {
+            let level = &mut entries_per_sst[file_id.lsm_level];
+            let new_file_id = FileId {
+                lsm_level: file_id.lsm_level,
+                sst_number: level.len(),
+            };
+            // SSTs are visited in increasing order, so the new name is either the same or already freed
+            if new_file_id != file_id {
+                fs::rename(name.join(file_id.name()), name.join(new_file_id.name()))?;
+            }
+            level.push(n_entries);
+        }
+
+        // Archived segments are named after the sequence number of their last write
+        let mut last_sequence = 0;
+        if let Ok(segments) = fs::read_dir(name.join(WAL_ARCHIVE_DIRNAME)) {
+            for entry in segments {
+                let filename = entry?.file_name();
+                let sequence = filename
+                    .to_str()
+                    .and_then(|f| f.strip_prefix("WAL-")?.strip_suffix(".log")?.parse().ok());
+                last_sequence = u64::max(last_sequence, sequence.unwrap_or(0));
+            }
+        }
+
+        let metadata = DbMetadata {
+            lsm_metadata: LsmMetadata::reconstruct(
+                &entries_per_sst,
+                configuration.lsm_configuration,
+            ),
+            last_sequence,
+        };
+        let metadata_file = File::create(name.join(METADATA_FILENAME))?;
+        serde_json::to_writer_pretty(&metadata_file, &metadata)?;
+        metadata_file.sync_all()?;
+
+        let log_path = name.join(LOG_FILENAME);
+        let wal_entries = if configuration.wal_buffer_size.is_some() && log_path.exists() {
+            read_wal(&log_path)?.count()
+        } else {
+            0
+        };
+
+        let mut db = Self::open(name)?;
+        db.flush()?;
+
+        Ok(RepairReport {
+            ssts_per_level: metadata.lsm_metadata.ssts_per_level,
+            sst_entries: entries_per_sst.iter().flatten().sum(),
+            corrupt_files,
+            leftover_files,
+            wal_entries,
+            last_sequence: db.last_sequence,
+        })
+    }
+
+    /// Rewrites every SST written in an older format version in the current one,
+    /// then records the current format version in the configuration file.
+    /// This lets databases created by older versions of BEARR take on format changes
+    /// without dumping and reloading them.
+    ///
+    /// Each outdated SST is rewritten by compacting it into a new SST at the same position in the LSM tree.
+    ///
+    /// Returns the number of SSTs rewritten.
+    ///
+    /// Returns an error if reading or writing files fails.
+    pub fn upgrade(&mut self) -> Result<usize, DbError> {
+        let rewritten = self.lsm.rewrite_outdated_ssts(&self.file_system)?;
+
+        let mut config = ConfigFile::read(&self.name)?;
+        config.format_version = FORMAT_VERSION;
+        config.write(&self.name)?;
+
+        Ok(rewritten)
+    }
+
+    /// Returns the format version of the database: the oldest version that any of its files may be in.
+    ///
+    /// Returns an error if the configuration file can't be read.
+    pub fn format_version(&self) -> Result<u64, DbError> {
+        Ok(ConfigFile::read(&self.name)?.format_version)
+    }
+
+    /// Checks the integrity of every SST in the database, reading each file directly from disk,
+    /// and returns a report of everything that was checked and every problem found.
+    ///
+    /// For each SST, this checks the checksum of every page, the metadata, the ordering of keys
+    /// within and across leaves, that the B-tree separator keys match the largest key of each leaf,
+    /// that every key is in the bloom filter, the number of entries, and the properties.
+    ///
+    /// Writes in the memtable are not checked. Files that can't be read are reported as problems.
+    pub fn verify(&self) -> VerifyReport {
+        verify::verify_ssts(self.lsm.file_ids(), &self.file_system)
+    }
+
+    /// Starts a background thread that repeatedly performs the same checks as `Database::verify`
+    /// at a low priority, pausing for the given duration after each chunk of pages and between passes.
+    ///
+    /// The scrubber reads the database folder independently, so it keeps running alongside
+    /// writes and merges. It stops when the returned handle is dropped.
+    ///
+    /// Returns `DbError::IoError` if the thread can't be spawned.
+    pub fn start_scrubber(&self, pause: Duration) -> Result<Scrubber, DbError> {
+        Scrubber::start(&self.name, pause)
+    }
+
+    /// Returns the path of the folder containing the database.
+    pub(crate) fn path(&self) -> &Path {
+        &self.name
+    }
+
+    /// Returns the name of each SST file with its properties, from the top LSM level down:
+    /// its key range, tombstone count, creation time, and the LSM level it was written for.
+    ///
+    /// SSTs written before format version 4 have no properties until `Database::upgrade` rewrites them.
+    pub fn sst_properties(&self) -> Vec<(String, Option<SstProperties>)> {
+        self.lsm
+            .ssts()
+            .map(|sst| (sst.file_id.name(), sst.properties().copied()))
+            .collect()
+    }
+
+    /// Returns the number of bytes of internal B-tree nodes pinned in memory,
+    /// which is 0 unless `LsmConfiguration::pin_internal_nodes` is set.
+    ///
+    /// This memory is in addition to the buffer pool, and grows with the number of SSTs.
+    pub fn pinned_memory(&self) -> usize {
+        self.lsm.pinned_bytes()
+    }
+
+    /// Returns the number of bytes that SST filters hold in memory: whole bloom and xor filters,
+    /// and the indexes and pinned partitions of partitioned filters.
+    ///
+    /// The unpinned partitions of partitioned filters are read through the buffer pool instead,
+    /// so they count towards its capacity rather than this.
+    pub fn filter_memory(&self) -> usize {
+        self.lsm.filter_bytes()
+    }
+
+    /// Returns the number of bloom filter bits per entry that new SSTs at each LSM level get,
+    /// the number of point lookups that the filters of each level answered and how,
+    /// and the expected number of false positives for a key that isn't in the database.
+    ///
+    /// The lookups are counted in memory, starting from when the database was opened.
+    pub fn filter_report(&self) -> FilterReport {
+        self.lsm.filter_report()
+    }
+
+    /// Returns the name of each SST file, from the top LSM level down,
+    /// with how its filter answered point lookups since the database was opened
+    /// and the rate of false positives that the filter should give.
+    ///
+    /// `FilterCounts::false_positive_rate` gives the observed rate to compare against.
+    pub fn filter_stats(&self) -> Vec<(String, FilterCounts, f64)> {
+        self.lsm
+            .ssts()
+            .map(|sst| {
+                (
+                    sst.file_id.name(),
+                    sst.filter_stats.counts(),
+                    sst.expected_false_positive_rate(),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the names of the SST files in the database folder, from the top LSM level down.
+    pub(crate) fn sst_filenames(&self) -> Vec<String> {
+        self.lsm.file_ids().map(FileId::name).collect()
+    }
+
+    /// Returns the sequence number of the most recent write (put or delete), or 0 if there are none.
+    ///
+    /// Every write is assigned the next sequence number, starting from 1.
+    /// These are recorded in the WAL and can be used as targets for `Database::recover`.
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    /// Returns the path of the folder that WAL segments are moved into when WAL archiving is enabled.
+    pub fn wal_archive_path(&self) -> PathBuf {
+        self.name.join(WAL_ARCHIVE_DIRNAME)
+    }
+
+    /// Restores the database located at the given path to a later point in time
+    /// by replaying writes from archived WAL segments, and returns the opened database.
+    ///
+    /// The database at the path should be a checkpoint or a restored backup of the database
+    /// that produced the archive. Archived writes that it already contains are skipped,
+    /// and replaying stops at the first write past the given target.
+    /// Only archived segments are replayed, so flush the source database first
+    /// if its most recent writes are needed.
+    ///
+    /// Returns `DbError::InvalidConfiguration` if the archive folder is the database's own archive folder,
+    /// since replayed writes keep their sequence numbers and would be archived on top of the segments being read.
+    ///
+    /// Returns `DbError::IoError` if the archive folder can't be read or contains malformed entries.
+    ///
+    /// Also returns errors if opening, writing to, or flushing the database fails.
+    pub fn recover(
+        name: impl AsRef<Path>,
+        wal_archive: impl AsRef<Path>,
+        target: RecoveryTarget,
+    ) -> Result<Self, DbError> {
+        let own_archive = fs::canonicalize(name.as_ref().join(WAL_ARCHIVE_DIRNAME)).ok();
+        if own_archive.is_some_and(|own_archive| {
+            fs::canonicalize(&wal_archive).is_ok_and(|wal_archive| wal_archive == own_archive)
+        }) {
+            return Err(DbError::InvalidConfiguration);
+        }
+
+        let mut db = Self::open(name)?;
+
+        // Segments are named after the sequence number of their last write, so sorting by name orders them
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(wal_archive)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "log") {
+                segments.push(path);
+            }
+        }
+        segments.sort();
+
+        'replay: for segment in segments {
+            for entry in read_wal(&segment)? {
+                let WalEntry {
+                    sequence,
+                    timestamp,
+                    key,
+                    value,
+                } = entry?;
+
+                if sequence <= db.last_sequence {
+                    continue;
+                }
+                let past_target = match target {
+                    RecoveryTarget::Sequence(target) => sequence > target,
+                    RecoveryTarget::Time(target) => timestamp > unix_micros(target),
+                };
+                if past_target {
+                    break 'replay;
+                }
+
+                // Make the write get logged under its original sequence number
+                db.last_sequence = sequence - 1;
+                if value == TOMBSTONE {
+                    db.delete(key)?;
+                } else {
+                    db.put(key, value)?;
+                }
+            }
+        }
+
+        db.flush()?;
+        Ok(db)
+    }
+
+    /// Assigns the next sequence number to a write,
+    /// and adds the write to the WAL buffer if the WAL is enabled, flushing the buffer if it's full.
+    fn log_write(&mut self, key: u64, value: u64) -> Result<(), DbError> {
+        self.last_sequence += 1;
+
+        if self.wal_enabled {
+            self.wal_buffer.push(WalEntry {
+                sequence: self.last_sequence,
+                timestamp: unix_micros(SystemTime::now()),
+                key,
+                value,
+            });
+
+            if self.wal_buffer.len() >= self.wal_buffer.capacity() {
+                self.flush_wal_buffer()?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn flush_wal_buffer(&mut self) -> Result<(), DbError> {
         assert!(self.wal_enabled);
         use std::io::Write;
@@ -276,8 +787,14 @@ impl Database {
             return Ok(());
         }
 
-        for &(key, value) in &self.wal_buffer {
-            writeln!(&mut self.wal_file, "{},{}", key, value)?;
+        for entry in &self.wal_buffer {
+            let WalEntry {
+                sequence,
+                timestamp,
+                key,
+                value,
+            } = entry;
+            writeln!(&mut self.wal_file, "{sequence},{timestamp},{key},{value}")?;
         }
         self.wal_file.flush()?;
         self.wal_file.sync_all()?;
@@ -289,48 +806,125 @@ impl Database {
     /// Replays WAL entries into memtable
     fn replay_wal(&mut self) -> Result<(), DbError> {
         assert!(self.wal_enabled);
-        use std::io::{BufRead, BufReader};
-
-        let wal_path = self.name.join(LOG_FILENAME);
-        let file = File::open(&wal_path)?;
-        let reader = BufReader::new(file);
 
-        for line in reader.lines() {
-            let line = line?;
-            if line.is_empty() {
-                continue;
-            }
-
-            let parts: Vec<&str> = line.split(',').collect();
-            if parts.len() == 2 {
-                let key: u64 = parts[0]
-                    .parse()
-                    .map_err(|_| DbError::IoError("wrong Key".to_string()))?;
-                let value: u64 = parts[1]
-                    .parse()
-                    .map_err(|_| DbError::IoError("wrong Val".to_string()))?;
-
-                // Replay without WAL buffering to avoid infinite recursion
-                self.lsm.put(key, value, &self.file_system)?;
-            }
+        for entry in read_wal(&self.name.join(LOG_FILENAME))? {
+            let WalEntry {
+                sequence,
+                key,
+                value,
+                ..
+            } = entry?;
+
+            // Entries from before sequence numbers were logged are treated as the next write
+            self.last_sequence = if sequence == 0 {
+                self.last_sequence + 1
+            } else {
+                u64::max(self.last_sequence, sequence)
+            };
+
+            // Replay without WAL buffering to avoid infinite recursion
+            self.lsm.put(key, value, &self.file_system)?;
         }
 
         Ok(())
     }
 
-    /// Checkpoints WAL by truncating it
+    /// Checkpoints WAL by truncating it,
+    /// or by moving it into the archive folder if WAL archiving is enabled
     fn checkpoint_wal(&mut self) -> Result<(), DbError> {
         assert!(self.wal_enabled);
         // Flush any pending entries first
         self.flush_wal_buffer()?;
 
+        let wal_path = self.name.join(LOG_FILENAME);
+        if self.wal_archiving && self.wal_file.metadata()?.len() > 0 {
+            // Link rather than rename, which fails instead of replacing a segment with the same name
+            let segment_name = format!("WAL-{:020}.log", self.last_sequence);
+            fs::hard_link(&wal_path, self.wal_archive_path().join(segment_name))?;
+            fs::remove_file(&wal_path)?;
+        }
+
         // Truncate WAL file
-        self.wal_file = File::create(self.name.join(LOG_FILENAME))?;
+        self.wal_file = File::create(wal_path)?;
 
         Ok(())
     }
 }
 
+/// Returns an iterator over the entries of the WAL file at the given path.
+fn read_wal(path: &Path) -> Result<impl Iterator<Item = Result<WalEntry, DbError>>, DbError> {
+    use std::io::{BufRead, BufReader};
+
+    let reader = BufReader::new(File::open(path)?);
+    let entries = reader.lines().filter_map(|line| match line {
+        Ok(line) => parse_wal_line(&line),
+        Err(e) => Some(Err(e.into())),
+    });
+
+    Ok(entries)
+}
+
+/// Parses a line of the WAL, of the form `sequence,timestamp,key,value`.
+///
+/// Lines of the form `key,value`, written before sequence numbers were logged,
+/// are read with a sequence number and timestamp of 0.
+///
+/// Returns None for lines of any other form, which are skipped.
+fn parse_wal_line(line: &str) -> Option<Result<WalEntry, DbError>> {
+    let parse = |part: &str| {
+        part.parse()
+            .map_err(|_| DbError::IoError(format!("malformed WAL entry: {line}")))
+    };
+    let parse_all = |sequence, timestamp, key, value| {
+        Ok(WalEntry {
+            sequence: parse(sequence)?,
+            timestamp: parse(timestamp)?,
+            key: parse(key)?,
+            value: parse(value)?,
+        })
+    };
+
+    let parts: Vec<&str> = line.split(',').collect();
+    match parts[..] {
+        [key, value] => Some(parse_all("0", "0", key, value)),
+        [sequence, timestamp, key, value] => Some(parse_all(sequence, timestamp, key, value)),
+        _ => None,
+    }
+}
+
+/// Returns the number of microseconds between the Unix epoch and the given time.
+fn unix_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_micros() as u64)
+}
+
+/// Returns whether the SST with the given file ID and properties, among the given SSTs of a database folder,
+/// is left over from a merge into the bottom level or an upgrade that was interrupted.
+///
+/// Those write their output below the level it's for, and only move it there once the SSTs it was written from
+/// are destroyed. So an SST below the level it was written for is left over if that level still has an SST
+/// written no later than it, whose key range it covers. An SST that was moved down into a new bottom level
+/// is the only one left at its level when it moves, so every SST written there since is newer.
+fn is_leftover_sst(
+    file_id: FileId,
+    properties: Option<SstProperties>,
+    ssts: &[(FileId, usize, Option<SstProperties>)],
+) -> bool {
+    let Some(properties) = properties else {
+        return false;
+    };
+    let origin_level = properties.origin_level as usize;
+    origin_level < file_id.lsm_level
+        && ssts.iter().any(|&(other_file_id, _, other_properties)| {
+            other_file_id.lsm_level == origin_level
+                && other_properties.is_some_and(|other| {
+                    other.created_at <= properties.created_at
+                        && properties.min_key <= other.min_key
+                        && other.max_key <= properties.max_key
+                })
+        })
+}
+
 /// The database is flushed upon dropping.
 ///
 /// Errors are ignored. To handle them, call `Database::flush` manually.
@@ -349,7 +943,11 @@ mod tests {
 
     use anyhow::Result;
 
-    use crate::test_util::{TestPath, get_path};
+    use crate::{
+        FilterType, SearchStrategy,
+        compression::{Compression, LevelCompression},
+        test_util::{TestPath, get_path, test_seed},
+    };
 
     use super::*;
 
@@ -388,10 +986,23 @@ mod tests {
                 write_buffering: 1,
                 readahead_buffering: 1,
                 wal_buffer_size: Some(10),
+                wal_archiving: false,
+                seed: Some(test_seed()),
+                buffer_pool_shards: None,
+                eviction: EvictionType::default(),
                 lsm_configuration: LsmConfiguration {
                     size_ratio: 2,
                     memtable_capacity: 3,
                     bloom_filter_bits: 1,
+                    compression: LevelCompression::default(),
+                    pin_internal_nodes: false,
+                    search_strategy: SearchStrategy::BTree,
+                    uniform_bits: false,
+                    workload_aware_bits: false,
+                    filter_type: FilterType::Bloom,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
                 },
             },
         )?;
@@ -451,10 +1062,23 @@ mod tests {
                     write_buffering: 1,
                     readahead_buffering: 1,
                     wal_buffer_size: Some(10),
+                    wal_archiving: false,
+                    seed: Some(test_seed()),
+                    buffer_pool_shards: None,
+                    eviction: EvictionType::default(),
                     lsm_configuration: LsmConfiguration {
                         size_ratio: 2,
                         memtable_capacity: 10,
                         bloom_filter_bits: 2,
+                        compression: LevelCompression::default(),
+                        pin_internal_nodes: false,
+                        search_strategy: SearchStrategy::BTree,
+                        uniform_bits: false,
+                        workload_aware_bits: false,
+                        filter_type: FilterType::Bloom,
+                        filter_hash: None,
+                        pinned_filter_levels: 0,
+                        range_filter: None,
                     },
                 },
             )?;
@@ -529,11 +1153,24 @@ mod tests {
                     size_ratio,
                     memtable_capacity,
                     bloom_filter_bits,
+                    compression: LevelCompression::default(),
+                    pin_internal_nodes: false,
+                    search_strategy: SearchStrategy::BTree,
+                    uniform_bits: false,
+                    workload_aware_bits: false,
+                    filter_type: FilterType::Bloom,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
                 },
                 buffer_pool_capacity,
                 write_buffering,
                 readahead_buffering,
                 wal_buffer_size: Some(wal_buffer_size),
+                wal_archiving: false,
+                seed: Some(test_seed()),
+                buffer_pool_shards: None,
+                eviction: EvictionType::default(),
             },
         )
     }
@@ -545,11 +1182,24 @@ mod tests {
                 size_ratio: 2,
                 memtable_capacity: 1,
                 bloom_filter_bits: 0,
+                compression: LevelCompression::default(),
+                pin_internal_nodes: false,
+                search_strategy: SearchStrategy::BTree,
+                uniform_bits: false,
+                workload_aware_bits: false,
+                filter_type: FilterType::Bloom,
+                filter_hash: None,
+                pinned_filter_levels: 0,
+                range_filter: None,
             },
             buffer_pool_capacity: 16,
             write_buffering: 1,
             readahead_buffering: 1,
             wal_buffer_size: Some(10),
+            wal_archiving: false,
+            seed: Some(test_seed()),
+            buffer_pool_shards: None,
+            eviction: EvictionType::default(),
         };
 
         let path = &test_path("errors");
@@ -595,6 +1245,58 @@ mod tests {
             Some(DbError::InvalidConfiguration)
         );
 
+        for (name, eviction) in [
+            ("errors_lru_0", EvictionType::LruK { k: 0 }),
+            (
+                "errors_two_q_k_in",
+                EvictionType::TwoQ {
+                    k_in: 1.5,
+                    k_out: 0.5,
+                },
+            ),
+        ] {
+            assert_eq!(
+                Database::create(
+                    test_path(name),
+                    DbConfiguration {
+                        eviction,
+                        ..ok_config
+                    }
+                )
+                .err(),
+                Some(DbError::InvalidConfiguration)
+            );
+        }
+
+        for (name, shards) in [("errors_zero_shards", 0), ("errors_small_shards", 2)] {
+            assert_eq!(
+                Database::create(
+                    test_path(name),
+                    DbConfiguration {
+                        buffer_pool_capacity: 31,
+                        buffer_pool_shards: Some(shards),
+                        ..ok_config
+                    }
+                )
+                .err(),
+                Some(DbError::InvalidConfiguration)
+            );
+        }
+
+        assert_eq!(
+            Database::create(
+                test_path("errors_archiving_without_wal"),
+                DbConfiguration {
+                    wal_buffer_size: None,
+                    wal_archiving: true,
+                    seed: Some(test_seed()),
+                    ..ok_config
+                }
+            )
+            .err(),
+            Some(DbError::InvalidConfiguration)
+        );
+
         assert_eq!(db.put(0, TOMBSTONE), Err(DbError::InvalidValue));
 
         #[allow(clippy::reversed_empty_ranges)]
@@ -604,6 +1306,218 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_checkpoint() -> Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let name = &test_path("checkpoint");
+        let checkpoint_name = &test_path("checkpoint_copy");
+        let mut db = Database::create(
+            name,
+            DbConfiguration {
+                buffer_pool_capacity: 16,
+                write_buffering: 1,
+                readahead_buffering: 1,
+                wal_buffer_size: Some(4),
+                wal_archiving: false,
+                seed: Some(test_seed()),
+                buffer_pool_shards: None,
+                eviction: EvictionType::default(),
+                lsm_configuration: LsmConfiguration {
+                    size_ratio: 2,
+                    memtable_capacity: 4,
+                    bloom_filter_bits: 2,
+                    compression: LevelCompression::default(),
+                    pin_internal_nodes: false,
+                    search_strategy: SearchStrategy::BTree,
+                    uniform_bits: false,
+                    workload_aware_bits: false,
+                    filter_type: FilterType::Bloom,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
+                },
+            },
+        )?;
+
+        put_many(
+            &mut db,
+            &[(1, 10), (2, 20), (3, 30), (4, 40), (5, 50), (6, 60)],
+        )?;
+        delete_many(&mut db, &[2])?;
+
+        db.checkpoint(checkpoint_name)?;
+
+        // SSTs are shared with the live database
+        for file_id in db.lsm.file_ids() {
+            let filename = file_id.name();
+            let live = fs::metadata(name.as_ref().join(&filename))?;
+            let copy = fs::metadata(checkpoint_name.as_ref().join(&filename))?;
+            assert_eq!(live.ino(), copy.ino());
+        }
+
+        // The live database stays usable, and changes don't leak into the checkpoint
+        put_many(&mut db, &[(7, 70), (1, 11), (8, 80), (9, 90), (10, 100)])?;
+        delete_many(&mut db, &[3, 4, 5])?;
+        db.flush()?;
+        assert_pairs(&db, &[(1, Some(11)), (3, None), (7, Some(70))])?;
+
+        // Checkpoint can't overwrite an existing path
+        assert!(matches!(
+            db.checkpoint(checkpoint_name),
+            Err(DbError::IoError(_))
+        ));
+
+        let copy = Database::open(checkpoint_name)?;
+        assert_eq!(
+            copy.scan(u64::MIN..=u64::MAX)?
+                .collect::<Result<Vec<_>, _>>()?,
+            vec![(1, 10), (3, 30), (4, 40), (5, 50), (6, 60)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_point_in_time_recovery() -> Result<()> {
+        let name = &test_path("pitr");
+        let by_sequence = &test_path("pitr_by_sequence");
+        let by_time = &test_path("pitr_by_time");
+        let mut db = Database::create(
+            name,
+            DbConfiguration {
+                buffer_pool_capacity: 16,
+                write_buffering: 1,
+                readahead_buffering: 1,
+                wal_buffer_size: Some(2),
+                wal_archiving: true,
+                seed: Some(test_seed()),
+                buffer_pool_shards: None,
+                eviction: EvictionType::default(),
+                lsm_configuration: LsmConfiguration {
+                    size_ratio: 2,
+                    memtable_capacity: 4,
+                    bloom_filter_bits: 2,
+                    compression: LevelCompression::default(),
+                    pin_internal_nodes: false,
+                    search_strategy: SearchStrategy::BTree,
+                    uniform_bits: false,
+                    workload_aware_bits: false,
+                    filter_type: FilterType::Bloom,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
+                },
+            },
+        )?;
+
+        put_many(&mut db, &[(1, 10), (2, 20), (3, 30), (4, 40), (5, 50)])?;
+        db.checkpoint(by_sequence)?;
+        db.checkpoint(by_time)?;
+
+        put_many(&mut db, &[(6, 60), (7, 70), (1, 11)])?;
+        delete_many(&mut db, &[2])?;
+        let good_sequence = db.last_sequence();
+        let good_state = db
+            .scan(u64::MIN..=u64::MAX)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let good_time = SystemTime::now();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        // Bad application writes
+        delete_many(&mut db, &[1, 3, 5, 6])?;
+        put_many(&mut db, &[(7, 0), (8, 0), (9, 0)])?;
+        db.flush()?;
+
+        let archive_path = db.wal_archive_path();
+        assert!(fs::read_dir(&archive_path)?.count() > 1);
+
+        // Recovering into the database that owns the archive would archive replayed writes on top of it
+        let segments = fs::read_dir(&archive_path)?.count();
+        drop(db);
+        assert_eq!(
+            Database::recover(name, &archive_path, RecoveryTarget::Sequence(good_sequence)).err(),
+            Some(DbError::InvalidConfiguration)
+        );
+        assert_eq!(fs::read_dir(&archive_path)?.count(), segments);
+
+        let recovered = Database::recover(
+            by_sequence,
+            &archive_path,
+            RecoveryTarget::Sequence(good_sequence),
+        )?;
+        assert_eq!(recovered.last_sequence(), good_sequence);
+        assert_eq!(
+            recovered
+                .scan(u64::MIN..=u64::MAX)?
+                .collect::<Result<Vec<_>, _>>()?,
+            good_state
+        );
+        drop(recovered);
+
+        let recovered = Database::recover(by_time, &archive_path, RecoveryTarget::Time(good_time))?;
+        assert_eq!(
+            recovered
+                .scan(u64::MIN..=u64::MAX)?
+                .collect::<Result<Vec<_>, _>>()?,
+            good_state
+        );
+        drop(recovered);
+
+        // Recovered writes are durable
+        let reopened = Database::open(by_time)?;
+        assert_eq!(reopened.last_sequence(), good_sequence);
+        assert_pairs(&reopened, &[(1, Some(11)), (2, None), (7, Some(70))])?;
+
+        Ok(())
+    }
+
+    /// Tests that archiving the WAL fails instead of replacing a segment with the same name.
+    #[test]
+    fn test_wal_archive_collision() -> Result<()> {
+        let name = &test_path("wal_archive_collision");
+        let mut db = Database::create(
+            name,
+            DbConfiguration {
+                buffer_pool_capacity: 16,
+                write_buffering: 1,
+                readahead_buffering: 1,
+                wal_buffer_size: Some(2),
+                wal_archiving: true,
+                seed: Some(test_seed()),
+                buffer_pool_shards: None,
+                eviction: EvictionType::default(),
+                lsm_configuration: LsmConfiguration {
+                    size_ratio: 2,
+                    memtable_capacity: 4,
+                    bloom_filter_bits: 2,
+                    compression: LevelCompression::default(),
+                    pin_internal_nodes: false,
+                    search_strategy: SearchStrategy::BTree,
+                    uniform_bits: false,
+                    workload_aware_bits: false,
+                    filter_type: FilterType::Bloom,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
+                },
+            },
+        )?;
+
+        put_many(&mut db, &[(1, 10), (2, 20), (3, 30)])?;
+        let segment = db
+            .wal_archive_path()
+            .join(format!("WAL-{:020}.log", db.last_sequence()));
+        fs::write(&segment, "existing segment")?;
+
+        assert!(matches!(db.flush(), Err(DbError::IoError(_))));
+        assert_eq!(fs::read_to_string(&segment)?, "existing segment");
+
+        Ok(())
+    }
+
     #[derive(Debug)]
     enum Command {
         Get,
@@ -617,6 +1531,551 @@ mod tests {
     const KEY_RANGE: Range<u64> = 0..65536;
     const VALUE_RANGE: Range<u64> = 0..65536;
 
+    #[test]
+    fn test_repair() -> Result<()> {
+        let name = &test_path("repair");
+        let configuration = DbConfiguration {
+            buffer_pool_capacity: 16,
+            write_buffering: 1,
+            readahead_buffering: 1,
+            wal_buffer_size: Some(1),
+            wal_archiving: false,
+            seed: Some(test_seed()),
+            buffer_pool_shards: None,
+            eviction: EvictionType::default(),
+            lsm_configuration: LsmConfiguration {
+                size_ratio: 3,
+                memtable_capacity: 4,
+                bloom_filter_bits: 2,
+                compression: LevelCompression::default(),
+                pin_internal_nodes: false,
+                search_strategy: SearchStrategy::BTree,
+                uniform_bits: false,
+                workload_aware_bits: false,
+                filter_type: FilterType::Bloom,
+                filter_hash: None,
+                pinned_filter_levels: 0,
+                range_filter: None,
+            },
+        };
+
+        let mut db = Database::create(name, configuration)?;
+        for key in 0..56 {
+            db.put(key, key * 10)?;
+        }
+        delete_many(&mut db, &[5, 40])?;
+        let expected = db
+            .scan(u64::MIN..=u64::MAX)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let expected_ssts_per_level = db.lsm.metadata().ssts_per_level;
+        assert_eq!(expected_ssts_per_level, [2, 1, 1]);
+        let last_sequence = db.last_sequence();
+        // Crash, with writes that are only in the WAL
+        std::mem::forget(db);
+
+        let name = name.as_ref();
+        fs::write(name.join(METADATA_FILENAME), "garbage")?;
+        assert!(Database::open(name).is_err());
+
+        let report = Database::repair(name)?;
+        assert_eq!(report.ssts_per_level, expected_ssts_per_level);
+        assert_eq!(report.corrupt_files, Vec::<String>::new());
+        assert_eq!(report.leftover_files, Vec::<String>::new());
+        assert_eq!(report.wal_entries, 2);
+        assert_eq!(report.last_sequence, last_sequence);
+
+        let db = Database::open(name)?;
+        assert_eq!(
+            db.scan(u64::MIN..=u64::MAX)?
+                .collect::<Result<Vec<_>, _>>()?,
+            expected
+        );
+        // Repairing flushed the replayed writes, causing a merge
+        let ssts_per_level = db.lsm.metadata().ssts_per_level;
+        assert_eq!(ssts_per_level, [0, 2, 1]);
+        drop(db);
+
+        // Corrupt the older SST of the middle level, which makes the newer one need renaming
+        let corrupt_name = FileId {
+            lsm_level: 1,
+            sst_number: 0,
+        }
+        .name();
+        fs::write(name.join(&corrupt_name), [0; 4096])?;
+        fs::remove_file(name.join(METADATA_FILENAME))?;
+
+        let report = Database::repair(name)?;
+        assert_eq!(report.corrupt_files, std::slice::from_ref(&corrupt_name));
+        assert!(name.join(format!("{corrupt_name}.corrupt")).exists());
+        assert_eq!(report.ssts_per_level, [0, 1, 1]);
+        assert_eq!(report.wal_entries, 0);
+
+        // Everything outside of the corrupt SST is still there
+        let mut db = Database::open(name)?;
+        let remaining = db
+            .scan(u64::MIN..=u64::MAX)?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(!remaining.is_empty() && remaining.len() < expected.len());
+        assert!(remaining.iter().all(|pair| expected.contains(pair)));
+        db.put(100, 1000)?;
+        db.flush()?;
+        assert_eq!(db.get(100)?, Some(1000));
+
+        // A copy of an SST below every level, as an interrupted upgrade leaves behind,
+        // is set aside instead of becoming a new level that duplicates its entries
+        let expected = db
+            .scan(u64::MIN..=u64::MAX)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let expected_ssts_per_level = db.lsm.metadata().ssts_per_level;
+        let (source_name, _) = db
+            .sst_properties()
+            .into_iter()
+            .find(|(filename, properties)| {
+                properties.is_some_and(|properties| {
+                    FileId::parse(filename).unwrap().lsm_level == properties.origin_level as usize
+                })
+            })
+            .unwrap();
+        drop(db);
+        let leftover_name = FileId {
+            lsm_level: expected_ssts_per_level.len(),
+            sst_number: 0,
+        }
+        .name();
+        fs::copy(name.join(source_name), name.join(&leftover_name))?;
+        fs::remove_file(name.join(METADATA_FILENAME))?;
+
+        let report = Database::repair(name)?;
+        assert_eq!(report.leftover_files, std::slice::from_ref(&leftover_name));
+        assert!(name.join(format!("{leftover_name}.leftover")).exists());
+        assert_eq!(report.ssts_per_level, expected_ssts_per_level);
+        let db = Database::open(name)?;
+        assert_eq!(
+            db.scan(u64::MIN..=u64::MAX)?
+                .collect::<Result<Vec<_>, _>>()?,
+            expected
+        );
+
+        Ok(())
+    }
+
+    /// Opens and upgrades a database written by BEARR before format versions were recorded.
+    /// The files in `test_data/format_v0` were generated by that version with the following writes.
+    #[test]
+    fn test_format_v0() -> Result<()> {
+        let mut expected = HashMap::new();
+        for i in 0..2000 {
+            expected.insert(i * 3, i);
+        }
+        for i in 0..100 {
+            expected.remove(&(i * 30));
+        }
+        for i in 0..50 {
+            expected.insert(i * 7 + 1, 1_000_000 + i);
+        }
+        // These were only written to the WAL
+        for i in 0..10 {
+            expected.insert(100_000 + i, i);
+        }
+        let mut expected: Vec<(u64, u64)> = expected.into_iter().collect();
+        expected.sort();
+
+        let name = &test_path("format_v0");
+        fs::create_dir(name)?;
+        for entry in fs::read_dir("test_data/format_v0")? {
+            let entry = entry?;
+            fs::copy(entry.path(), name.as_ref().join(entry.file_name()))?;
+        }
+
+        let assert_contents = |db: &Database| -> Result<()> {
+            assert_eq!(
+                db.scan(u64::MIN..=u64::MAX)?
+                    .collect::<Result<Vec<_>, _>>()?,
+                expected
+            );
+            assert_eq!(db.get(3)?, Some(1));
+            assert_eq!(db.get(30)?, None);
+            assert_eq!(db.get(8)?, Some(1_000_001));
+            Ok(())
+        };
+        let total_pages = |db: &Database| -> Result<usize> {
+            let mut total = 0;
+            for filename in db.sst_filenames() {
+                total += fs::metadata(name.as_ref().join(filename))?.len() as usize / 4096;
+            }
+            Ok(total)
+        };
+
+        let mut db = Database::open(name)?;
+        assert_eq!(db.format_version()?, 0);
+        assert_contents(&db)?;
+
+        // Old SSTs have no checksums to check
+        let report = db.verify();
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert_eq!(report.ssts_checked, 3);
+        assert_eq!(report.pages_checked, 0);
+        assert!(report.entries_checked >= 2000);
+        assert!(db.sst_properties().iter().all(|(_, p)| p.is_none()));
+        // Key ranges of old SSTs are read from their leaves instead
+        let key_ranges: Vec<_> = db.lsm.ssts().map(|sst| sst.key_range.clone()).collect();
+        assert!(key_ranges.iter().all(|range| !range.is_empty()));
+
+        assert_eq!(db.upgrade()?, 3);
+        assert_eq!(db.format_version()?, FORMAT_VERSION);
+        assert_contents(&db)?;
+        let properties = db.sst_properties();
+        assert_eq!(properties.len(), 3);
+        for ((_, properties), key_range) in properties.into_iter().zip(key_ranges) {
+            let properties = properties.unwrap();
+            assert_eq!(properties.min_key..=properties.max_key, key_range);
+        }
+        let report = db.verify();
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert_eq!(report.pages_checked, total_pages(&db)?);
+        assert_eq!(db.upgrade()?, 0);
+        drop(db);
+
+        let db = Database::open(name)?;
+        assert_eq!(db.format_version()?, FORMAT_VERSION);
+        assert_contents(&db)?;
+        drop(db);
+
+        // Databases from newer versions are rejected
+        let config_path = name.as_ref().join(CONFIG_FILENAME);
+        let config = fs::read_to_string(&config_path)?.replacen(
+            &format!("\"format_version\": {FORMAT_VERSION}"),
+            &format!("\"format_version\": {}", FORMAT_VERSION + 1),
+            1,
+        );
+        fs::write(&config_path, config)?;
+        assert_eq!(
+            Database::open(name).err(),
+            Some(DbError::UnsupportedFormatVersion(FORMAT_VERSION + 1))
+        );
+
+        Ok(())
+    }
+
+    /// Tests a database whose levels use different compressions for their leaf pages.
+    #[test]
+    fn test_compression() -> Result<()> {
+        let name = &test_path("compression");
+        let configuration = DbConfiguration {
+            buffer_pool_capacity: 64,
+            write_buffering: 4,
+            readahead_buffering: 4,
+            wal_buffer_size: None,
+            wal_archiving: false,
+            seed: Some(test_seed()),
+            buffer_pool_shards: None,
+            eviction: EvictionType::default(),
+            lsm_configuration: LsmConfiguration {
+                size_ratio: 3,
+                memtable_capacity: 1000,
+                bloom_filter_bits: 4,
+                compression: LevelCompression::new(&[
+                    Compression::None,
+                    Compression::DeltaFor,
+                    Compression::Lz4,
+                ]),
+                pin_internal_nodes: false,
+                search_strategy: SearchStrategy::BTree,
+                uniform_bits: false,
+                workload_aware_bits: false,
+                filter_type: FilterType::Bloom,
+                filter_hash: None,
+                pinned_filter_levels: 0,
+                range_filter: None,
+            },
+        };
+
+        let start = 1_700_000_000_000;
+        let mut expected = HashMap::new();
+        let mut db = Database::create(name, configuration)?;
+        for i in 0..30_000 {
+            let key = start + i * 10 + fastrand::u64(..10);
+            let value = fastrand::u64(..1 << 16);
+            db.put(key, value)?;
+            expected.insert(key, value);
+            if i % 10 == 0 {
+                let key = start + fastrand::u64(..i * 10 + 1);
+                db.delete(key)?;
+                expected.remove(&key);
+            }
+        }
+        db.flush()?;
+
+        let mut compressions = Vec::new();
+        for file_id in db.lsm.file_ids() {
+            let sst = Sst::open(file_id, &db.file_system)?;
+            let compression = sst.btree_metadata.compression();
+            if file_id.lsm_level == 0 {
+                assert_eq!(compression, Compression::None);
+            }
+            compressions.push(compression);
+        }
+        assert!(compressions.contains(&Compression::DeltaFor));
+        assert!(compressions.contains(&Compression::Lz4));
+        assert!(db.verify().is_ok());
+        drop(db);
+
+        let db = Database::open(name)?;
+        let mut expected: Vec<(u64, u64)> = expected.into_iter().collect();
+        expected.sort();
+        for &(key, value) in expected.iter().step_by(7) {
+            assert_eq!(db.get(key)?, Some(value));
+        }
+        let scanned = db
+            .scan(u64::MIN..=u64::MAX)?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(scanned, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pinned_nodes() -> Result<()> {
+        let name = &test_path("pinned_nodes");
+        let mut configuration = DbConfiguration {
+            buffer_pool_capacity: 64,
+            write_buffering: 4,
+            readahead_buffering: 4,
+            wal_buffer_size: None,
+            wal_archiving: false,
+            seed: Some(test_seed()),
+            buffer_pool_shards: None,
+            eviction: EvictionType::default(),
+            lsm_configuration: LsmConfiguration {
+                size_ratio: 3,
+                memtable_capacity: 1000,
+                bloom_filter_bits: 4,
+                compression: LevelCompression::default(),
+                pin_internal_nodes: true,
+                search_strategy: SearchStrategy::BTree,
+                uniform_bits: false,
+                workload_aware_bits: false,
+                filter_type: FilterType::Bloom,
+                filter_hash: None,
+                pinned_filter_levels: 0,
+                range_filter: None,
+            },
+        };
+
+        let mut db = Database::create(name, configuration)?;
+        for i in 0..20_000 {
+            db.put(i * 7 % 20_000, i)?;
+        }
+        db.flush()?;
+        let node_pages: u64 = db
+            .lsm
+            .ssts()
+            .map(|sst| sst.btree_metadata.bloom_offset - sst.btree_metadata.nodes_offset)
+            .sum();
+        assert_eq!(db.pinned_memory(), node_pages as usize * 4096);
+        drop(db);
+
+        let db = Database::open(name)?;
+        assert_eq!(db.pinned_memory(), node_pages as usize * 4096);
+        for i in (0..20_000).step_by(13) {
+            assert_eq!(db.get(i * 7 % 20_000)?, Some(i));
+        }
+        drop(db);
+
+        let unpinned_name = &test_path("unpinned_nodes");
+        configuration.lsm_configuration.pin_internal_nodes = false;
+        let mut db = Database::create(unpinned_name, configuration)?;
+        db.put(1, 1)?;
+        db.flush()?;
+        assert_eq!(db.pinned_memory(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_types() -> Result<()> {
+        for (name, filter_type) in [
+            ("blocked_filter", FilterType::Blocked),
+            ("xor_filter", FilterType::Xor),
+        ] {
+            let name = &test_path(name);
+            let configuration = DbConfiguration {
+                buffer_pool_capacity: 64,
+                write_buffering: 4,
+                readahead_buffering: 4,
+                wal_buffer_size: None,
+                wal_archiving: false,
+                seed: Some(test_seed()),
+                buffer_pool_shards: None,
+                eviction: EvictionType::default(),
+                lsm_configuration: LsmConfiguration {
+                    size_ratio: 3,
+                    memtable_capacity: 1000,
+                    bloom_filter_bits: 8,
+                    compression: LevelCompression::default(),
+                    pin_internal_nodes: false,
+                    search_strategy: SearchStrategy::BTree,
+                    uniform_bits: false,
+                    workload_aware_bits: false,
+                    filter_type,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
+                },
+            };
+
+            let mut db = Database::create(name, configuration)?;
+            for i in 0..20_000 {
+                db.put(i * 7 % 20_000 * 2, i)?;
+            }
+            db.flush()?;
+            drop(db);
+
+            let db = Database::open(name)?;
+            assert!(
+                db.lsm
+                    .ssts()
+                    .all(|sst| sst.btree_metadata.filter_type() == filter_type)
+            );
+            // Monkey gives lower levels fewer bits: fewer probes, or narrower fingerprints
+            let num_hashes: Vec<u64> = db
+                .lsm
+                .ssts()
+                .map(|sst| sst.btree_metadata.num_hashes)
+                .collect();
+            assert!(num_hashes.iter().all(|&n| n <= 6), "{num_hashes:?}");
+            assert!(num_hashes.iter().any(|&n| n < 6), "{num_hashes:?}");
+            assert!(db.verify().is_ok());
+            for i in (0..20_000).step_by(13) {
+                assert_eq!(db.get(i * 7 % 20_000 * 2)?, Some(i));
+                assert_eq!(db.get(i * 2 + 1)?, None);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tests that the observed false positive rate of the filters is close to what they expect.
+    #[test]
+    fn test_filter_stats() -> Result<()> {
+        let name = &test_path("filter_stats");
+        let configuration = DbConfiguration {
+            buffer_pool_capacity: 64,
+            write_buffering: 4,
+            readahead_buffering: 4,
+            wal_buffer_size: None,
+            wal_archiving: false,
+            seed: Some(test_seed()),
+            buffer_pool_shards: None,
+            eviction: EvictionType::default(),
+            lsm_configuration: LsmConfiguration {
+                size_ratio: 3,
+                memtable_capacity: 1000,
+                bloom_filter_bits: 8,
+                compression: LevelCompression::default(),
+                pin_internal_nodes: false,
+                search_strategy: SearchStrategy::BTree,
+                uniform_bits: true,
+                workload_aware_bits: false,
+                filter_type: FilterType::Bloom,
+                filter_hash: None,
+                pinned_filter_levels: 0,
+                range_filter: None,
+            },
+        };
+
+        let mut db = Database::create(name, configuration)?;
+        for i in 0..20_000 {
+            db.put(i * 7 % 20_000 * 2, i)?;
+        }
+        db.flush()?;
+        for i in 0..20_000 {
+            assert_eq!(db.get(i * 2 + 1)?, None);
+        }
+
+        let mut observed = FilterCounts::default();
+        let mut expected = 0.0;
+        for (_, counts, rate) in db.filter_stats() {
+            assert_eq!(counts.positives, counts.false_positives);
+            observed += counts;
+            expected += rate * counts.negatives() as f64;
+        }
+        assert!(observed.probes > 0);
+        assert!(
+            (expected / 2.0..expected * 2.0).contains(&(observed.false_positives as f64)),
+            "{observed:?}, expected {expected}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_strategy() -> Result<()> {
+        let strategies = [
+            ("search_btree", SearchStrategy::BTree, false),
+            ("search_binary", SearchStrategy::Binary, true),
+            ("search_interpolation", SearchStrategy::Interpolation, false),
+        ];
+        for (name, search_strategy, uniform_bits) in strategies {
+            let name = &test_path(name);
+            let configuration = DbConfiguration {
+                buffer_pool_capacity: 64,
+                write_buffering: 4,
+                readahead_buffering: 4,
+                wal_buffer_size: None,
+                wal_archiving: false,
+                seed: Some(test_seed()),
+                buffer_pool_shards: None,
+                eviction: EvictionType::default(),
+                lsm_configuration: LsmConfiguration {
+                    size_ratio: 3,
+                    memtable_capacity: 1000,
+                    bloom_filter_bits: 8,
+                    compression: LevelCompression::default(),
+                    pin_internal_nodes: false,
+                    search_strategy,
+                    uniform_bits,
+                    workload_aware_bits: false,
+                    filter_type: FilterType::Bloom,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
+                },
+            };
+
+            let mut db = Database::create(name, configuration)?;
+            for i in 0..20_000 {
+                db.put(i * 7 % 20_000 * 3, i)?;
+            }
+            db.flush()?;
+            drop(db);
+
+            // Both options are read back from config.json
+            let db = Database::open(name)?;
+            assert!(
+                db.lsm
+                    .ssts()
+                    .all(|sst| sst.search_strategy == search_strategy)
+            );
+            let num_hashes: Vec<u64> = db
+                .lsm
+                .ssts()
+                .map(|sst| sst.btree_metadata.num_hashes)
+                .collect();
+            let uniform = num_hashes.iter().all(|&n| n == num_hashes[0]);
+            assert_eq!(uniform, uniform_bits, "{num_hashes:?}");
+
+            for i in (0..20_000).step_by(13) {
+                assert_eq!(db.get(i * 7 % 20_000 * 3)?, Some(i));
+                assert_eq!(db.get(i * 3 + 1)?, None);
+            }
+            let scanned = db.scan(3000..=3030)?.collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(scanned.len(), 11);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_chaotic() -> Result<()> {
         let name = &test_path("chaotic");
@@ -627,10 +2086,23 @@ mod tests {
                 write_buffering: 8,
                 readahead_buffering: 8,
                 wal_buffer_size: Some(64),
+                wal_archiving: false,
+                seed: Some(test_seed()),
+                buffer_pool_shards: None,
+                eviction: EvictionType::default(),
                 lsm_configuration: LsmConfiguration {
                     size_ratio: 3,
                     memtable_capacity: 256,
                     bloom_filter_bits: 4,
+                    compression: LevelCompression::default(),
+                    pin_internal_nodes: false,
+                    search_strategy: SearchStrategy::BTree,
+                    uniform_bits: false,
+                    workload_aware_bits: false,
+                    filter_type: FilterType::Bloom,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
                 },
             },
         )?);
@@ -758,10 +2230,23 @@ mod tests {
                 write_buffering: 8,
                 readahead_buffering: 8,
                 wal_buffer_size: Some(16),
+                wal_archiving: false,
+                seed: Some(test_seed()),
+                buffer_pool_shards: Some(4),
+                eviction: EvictionType::default(),
                 lsm_configuration: LsmConfiguration {
                     size_ratio: 3,
                     memtable_capacity: 256,
                     bloom_filter_bits: 4,
+                    compression: LevelCompression::default(),
+                    pin_internal_nodes: false,
+                    search_strategy: SearchStrategy::BTree,
+                    uniform_bits: false,
+                    workload_aware_bits: false,
+                    filter_type: FilterType::Bloom,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
                 },
             },
         )?;
@@ -858,10 +2343,23 @@ mod tests {
                     write_buffering: 1,
                     readahead_buffering: 1,
                     wal_buffer_size: Some(5), // Buffer 5 entries before flushing
+                    wal_archiving: false,
+                    seed: Some(test_seed()),
+                    buffer_pool_shards: None,
+                    eviction: EvictionType::default(),
                     lsm_configuration: LsmConfiguration {
                         size_ratio: 2,
                         memtable_capacity: 100, // Large enough to not trigger memtable flush
                         bloom_filter_bits: 1,
+                        compression: LevelCompression::default(),
+                        pin_internal_nodes: false,
+                        search_strategy: SearchStrategy::BTree,
+                        uniform_bits: false,
+                        workload_aware_bits: false,
+                        filter_type: FilterType::Bloom,
+                        filter_hash: None,
+                        pinned_filter_levels: 0,
+                        range_filter: None,
                     },
                 },
             )?;
@@ -899,10 +2397,23 @@ mod tests {
                         write_buffering: 1,
                         readahead_buffering: 1,
                         wal_buffer_size: Some(3),
+                        wal_archiving: false,
+                        seed: Some(test_seed()),
+                        buffer_pool_shards: None,
+                        eviction: EvictionType::default(),
                         lsm_configuration: LsmConfiguration {
                             size_ratio: 2,
                             memtable_capacity: 100,
                             bloom_filter_bits: 1,
+                            compression: LevelCompression::default(),
+                            pin_internal_nodes: false,
+                            search_strategy: SearchStrategy::BTree,
+                            uniform_bits: false,
+                            workload_aware_bits: false,
+                            filter_type: FilterType::Bloom,
+                            filter_hash: None,
+                            pinned_filter_levels: 0,
+                            range_filter: None,
                         },
                     },
                 )?;
@@ -945,10 +2456,23 @@ mod tests {
                     write_buffering: 1,
                     readahead_buffering: 1,
                     wal_buffer_size: Some(2),
+                    wal_archiving: false,
+                    seed: Some(test_seed()),
+                    buffer_pool_shards: None,
+                    eviction: EvictionType::default(),
                     lsm_configuration: LsmConfiguration {
                         size_ratio: 2,
                         memtable_capacity: 5,
                         bloom_filter_bits: 1,
+                        compression: LevelCompression::default(),
+                        pin_internal_nodes: false,
+                        search_strategy: SearchStrategy::BTree,
+                        uniform_bits: false,
+                        workload_aware_bits: false,
+                        filter_type: FilterType::Bloom,
+                        filter_hash: None,
+                        pinned_filter_levels: 0,
+                        range_filter: None,
                     },
                 },
             )?;
@@ -992,10 +2516,23 @@ mod tests {
                         write_buffering: 1,
                         readahead_buffering: 1,
                         wal_buffer_size: Some(3),
+                        wal_archiving: false,
+                        seed: Some(test_seed()),
+                        buffer_pool_shards: None,
+                        eviction: EvictionType::default(),
                         lsm_configuration: LsmConfiguration {
                             size_ratio: 2,
                             memtable_capacity: 100,
                             bloom_filter_bits: 1,
+                            compression: LevelCompression::default(),
+                            pin_internal_nodes: false,
+                            search_strategy: SearchStrategy::BTree,
+                            uniform_bits: false,
+                            workload_aware_bits: false,
+                            filter_type: FilterType::Bloom,
+                            filter_hash: None,
+                            pinned_filter_levels: 0,
+                            range_filter: None,
                         },
                     },
                 )?;
@@ -1037,10 +2574,23 @@ mod tests {
                         write_buffering: 1,
                         readahead_buffering: 1,
                         wal_buffer_size: Some(2),
+                        wal_archiving: false,
+                        seed: Some(test_seed()),
+                        buffer_pool_shards: None,
+                        eviction: EvictionType::default(),
                         lsm_configuration: LsmConfiguration {
                             size_ratio: 2,
                             memtable_capacity: 3,
                             bloom_filter_bits: 1,
+                            compression: LevelCompression::default(),
+                            pin_internal_nodes: false,
+                            search_strategy: SearchStrategy::BTree,
+                            uniform_bits: false,
+                            workload_aware_bits: false,
+                            filter_type: FilterType::Bloom,
+                            filter_hash: None,
+                            pinned_filter_levels: 0,
+                            range_filter: None,
                         },
                     },
                 )?;
@@ -1101,10 +2651,23 @@ mod tests {
                         write_buffering: 1,
                         readahead_buffering: 1,
                         wal_buffer_size: Some(3),
+                        wal_archiving: false,
+                        seed: Some(test_seed()),
+                        buffer_pool_shards: None,
+                        eviction: EvictionType::default(),
                         lsm_configuration: LsmConfiguration {
                             size_ratio: 2,
                             memtable_capacity: 100,
                             bloom_filter_bits: 1,
+                            compression: LevelCompression::default(),
+                            pin_internal_nodes: false,
+                            search_strategy: SearchStrategy::BTree,
+                            uniform_bits: false,
+                            workload_aware_bits: false,
+                            filter_type: FilterType::Bloom,
+                            filter_hash: None,
+                            pinned_filter_levels: 0,
+                            range_filter: None,
                         },
                     },
                 )?;
@@ -1132,6 +2695,59 @@ mod tests {
             Ok(())
         }
 
+        #[test]
+        fn test_wal_legacy_entries() -> Result<()> {
+            let name = &test_path("wal_legacy_entries");
+
+            {
+                let mut db = Database::create(
+                    name,
+                    DbConfiguration {
+                        buffer_pool_capacity: 16,
+                        write_buffering: 1,
+                        readahead_buffering: 1,
+                        wal_buffer_size: Some(1),
+                        wal_archiving: false,
+                        seed: Some(test_seed()),
+                        buffer_pool_shards: None,
+                        eviction: EvictionType::default(),
+                        lsm_configuration: LsmConfiguration {
+                            size_ratio: 2,
+                            memtable_capacity: 100,
+                            bloom_filter_bits: 1,
+                            compression: LevelCompression::default(),
+                            pin_internal_nodes: false,
+                            search_strategy: SearchStrategy::BTree,
+                            uniform_bits: false,
+                            workload_aware_bits: false,
+                            filter_type: FilterType::Bloom,
+                            filter_hash: None,
+                            pinned_filter_levels: 0,
+                            range_filter: None,
+                        },
+                    },
+                )?;
+                db.put(1, 10)?;
+                std::mem::forget(db);
+            }
+
+            // Append an entry in the format used before sequence numbers were logged
+            {
+                use std::io::Write;
+                let mut wal = fs::OpenOptions::new()
+                    .append(true)
+                    .open(name.as_ref().join(LOG_FILENAME))?;
+                writeln!(wal, "2,20")?;
+            }
+
+            let db = Database::open(name)?;
+            assert_eq!(db.get(1)?, Some(10));
+            assert_eq!(db.get(2)?, Some(20));
+            assert_eq!(db.last_sequence(), 2);
+
+            Ok(())
+        }
+
         #[test]
         fn test_wal_buffer_size_one() -> Result<()> {
             let name = &test_path("wal_buffer_one");
@@ -1142,10 +2758,23 @@ mod tests {
                     write_buffering: 1,
                     readahead_buffering: 1,
                     wal_buffer_size: Some(1), // Flush every single operation
+                    wal_archiving: false,
+                    seed: Some(test_seed()),
+                    buffer_pool_shards: None,
+                    eviction: EvictionType::default(),
                     lsm_configuration: LsmConfiguration {
                         size_ratio: 2,
                         memtable_capacity: 100,
                         bloom_filter_bits: 1,
+                        compression: LevelCompression::default(),
+                        pin_internal_nodes: false,
+                        search_strategy: SearchStrategy::BTree,
+                        uniform_bits: false,
+                        workload_aware_bits: false,
+                        filter_type: FilterType::Bloom,
+                        filter_hash: None,
+                        pinned_filter_levels: 0,
+                        range_filter: None,
                     },
                 },
             )?;
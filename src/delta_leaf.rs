@@ -0,0 +1,416 @@
+use std::{cell::Cell, sync::Arc};
+
+use crate::{
+    DbError,
+    file_system::{Aligned, PAGE_DATA_SIZE},
+};
+
+/// The number of pairs from one restart point to the next.
+const RESTART_INTERVAL: usize = 16;
+/// The number of bytes before the first entry: the number of pairs and the number of restart points.
+const HEADER_SIZE: usize = 16;
+/// The number of bytes for the offset of a restart point.
+const RESTART_SIZE: usize = size_of::<u16>();
+/// The most bytes a varint can take.
+const MAX_VARINT_SIZE: usize = 10;
+
+/*
+ * Structure of a delta leaf (page aligned):
+ *      Number of pairs                      (little-endian u64)
+ *      Number of restart points             (little-endian u64)
+ *      | key0 (little-endian u64) : value0 (varint) |
+ *      | key1 - key0 (varint)     : value1 (varint) |
+ *             ...
+ *      | key16 (little-endian u64) : value16 (varint) |   <- restart point, every RESTART_INTERVAL pairs
+ *             ...
+ *      00000000000000000000...
+ *      | offset of restart 0 | offset of restart 1 | ... |  (little-endian u16s, ending at PAGE_DATA_SIZE)
+ *      checksum
+ * */
+
+/// Encodes as many of the given pairs as fit into the page as a delta leaf, and returns how many that is.
+/// The pairs must be sorted by key.
+pub fn fill(page: &mut Aligned, pairs: &[[u64; 2]]) -> usize {
+    page.0[..PAGE_DATA_SIZE].fill(0);
+    let mut offset = HEADER_SIZE;
+    let mut restarts: Vec<u16> = Vec::new();
+    let mut length = 0;
+    let mut previous_key = 0;
+
+    for (index, &[key, value]) in pairs.iter().enumerate() {
+        let is_restart = index.is_multiple_of(RESTART_INTERVAL);
+        let key_size = if is_restart {
+            size_of::<u64>()
+        } else {
+            varint_size(key - previous_key)
+        };
+        let restarts_size = (restarts.len() + is_restart as usize) * RESTART_SIZE;
+        if offset + key_size + varint_size(value) + restarts_size > PAGE_DATA_SIZE {
+            break;
+        }
+
+        if is_restart {
+            restarts.push(offset as u16);
+            page.0[offset..offset + size_of::<u64>()].copy_from_slice(&key.to_le_bytes());
+            offset += size_of::<u64>();
+        } else {
+            offset += write_varint(key - previous_key, &mut page.0[offset..]);
+        }
+        offset += write_varint(value, &mut page.0[offset..]);
+        previous_key = key;
+        length += 1;
+    }
+
+    page.0[0..8].copy_from_slice(&(length as u64).to_le_bytes());
+    page.0[8..16].copy_from_slice(&(restarts.len() as u64).to_le_bytes());
+    let restarts_start = PAGE_DATA_SIZE - restarts.len() * RESTART_SIZE;
+    for (restart, bytes) in restarts
+        .iter()
+        .zip(page.0[restarts_start..PAGE_DATA_SIZE].chunks_exact_mut(RESTART_SIZE))
+    {
+        bytes.copy_from_slice(&restart.to_le_bytes());
+    }
+    length
+}
+
+/// The position of the most recently decoded pair of a delta leaf.
+#[derive(Clone, Copy)]
+struct Cursor {
+    index: usize,
+    /// The offset of the next pair's entry
+    next_offset: usize,
+    key: u64,
+    value: u64,
+}
+
+/// A leaf page laid out as a delta leaf, which is searched in place without decoding all of it.
+///
+/// Keys are stored as differences from the previous key, except every `RESTART_INTERVAL` pairs,
+/// where a restart point stores the full key. A search binary searches the restart points,
+/// then decodes at most `RESTART_INTERVAL` pairs.
+///
+/// Reading never panics, but a corrupt page can give wrong pairs. `DeltaLeaf::decode_all` checks the page fully.
+pub struct DeltaLeaf {
+    page: Arc<Aligned>,
+    length: usize,
+    restarts: usize,
+    /// Remembered so that reading pairs in order decodes each one once
+    cursor: Cell<Option<Cursor>>,
+}
+
+impl DeltaLeaf {
+    /// Returns the delta leaf in the given page.
+    ///
+    /// Returns `DbError::CorruptSst` if the header is inconsistent.
+    pub fn new(page: Arc<Aligned>, max_length: usize) -> Result<Self, DbError> {
+        let length = u64::from_le_bytes(page.0[0..8].try_into().unwrap());
+        let restarts = u64::from_le_bytes(page.0[8..16].try_into().unwrap());
+        if length == 0
+            || length > max_length as u64
+            || restarts != length.div_ceil(RESTART_INTERVAL as u64)
+        {
+            return Err(DbError::CorruptSst);
+        }
+
+        Ok(Self {
+            page,
+            length: length as usize,
+            restarts: restarts as usize,
+            cursor: Cell::new(None),
+        })
+    }
+
+    /// Number of pairs in the leaf
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns the pair at the given index.
+    pub fn pair(&self, index: usize) -> [u64; 2] {
+        assert!(index < self.length);
+
+        let mut cursor = match self.cursor.get() {
+            Some(cursor) if cursor.index == index => return [cursor.key, cursor.value],
+            // Continue from the cursor if that doesn't mean decoding more pairs than from the restart point
+            Some(cursor)
+                if cursor.index < index
+                    && (cursor.index / RESTART_INTERVAL == index / RESTART_INTERVAL
+                        || index.is_multiple_of(RESTART_INTERVAL) && cursor.index + 1 == index) =>
+            {
+                cursor
+            }
+            _ => self.decode_restart(index / RESTART_INTERVAL),
+        };
+        while cursor.index < index {
+            cursor = self.decode_next(cursor);
+        }
+
+        self.cursor.set(Some(cursor));
+        [cursor.key, cursor.value]
+    }
+
+    /// Binary searches the leaf for the given key, like `slice::binary_search`.
+    pub fn binary_search(&self, key: u64) -> Result<usize, usize> {
+        // Find the last restart point whose key is at most the key
+        let (mut low, mut high) = (0, self.restarts);
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.restart_key(middle) <= key {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        let Some(restart) = low.checked_sub(1) else {
+            return Err(0);
+        };
+
+        let end = ((restart + 1) * RESTART_INTERVAL).min(self.length);
+        let mut cursor = self.decode_restart(restart);
+        loop {
+            if cursor.key >= key {
+                self.cursor.set(Some(cursor));
+                return if cursor.key == key {
+                    Ok(cursor.index)
+                } else {
+                    Err(cursor.index)
+                };
+            }
+            if cursor.index + 1 == end {
+                return Err(end);
+            }
+            cursor = self.decode_next(cursor);
+        }
+    }
+
+    /// Decodes every pair of the delta leaf in the given page, checking that the page is laid out correctly.
+    ///
+    /// Returns `DbError::CorruptSst` if it isn't.
+    pub fn decode_all(page: &Aligned, max_length: usize) -> Result<Vec<[u64; 2]>, DbError> {
+        let length = u64::from_le_bytes(page.0[0..8].try_into().unwrap());
+        let restarts = u64::from_le_bytes(page.0[8..16].try_into().unwrap());
+        if length == 0
+            || length > max_length as u64
+            || restarts != length.div_ceil(RESTART_INTERVAL as u64)
+        {
+            return Err(DbError::CorruptSst);
+        }
+        let restarts_start = PAGE_DATA_SIZE - restarts as usize * RESTART_SIZE;
+        let entries = &page.0[..restarts_start];
+
+        let mut pairs: Vec<[u64; 2]> = Vec::new();
+        pairs.try_reserve(length as usize)?;
+        let mut offset = HEADER_SIZE;
+        for index in 0..length as usize {
+            let key = if index.is_multiple_of(RESTART_INTERVAL) {
+                let restart = index / RESTART_INTERVAL * RESTART_SIZE + restarts_start;
+                if u16::from_le_bytes([page.0[restart], page.0[restart + 1]]) as usize != offset {
+                    return Err(DbError::CorruptSst);
+                }
+                let bytes = entries
+                    .get(offset..offset + size_of::<u64>())
+                    .ok_or(DbError::CorruptSst)?;
+                offset += size_of::<u64>();
+                u64::from_le_bytes(bytes.try_into().unwrap())
+            } else {
+                let (delta, size) = read_varint(entries, offset).ok_or(DbError::CorruptSst)?;
+                offset += size;
+                pairs[index - 1][0]
+                    .checked_add(delta)
+                    .ok_or(DbError::CorruptSst)?
+            };
+            let (value, size) = read_varint(entries, offset).ok_or(DbError::CorruptSst)?;
+            offset += size;
+            pairs.push([key, value]);
+        }
+        Ok(pairs)
+    }
+
+    /// The full key stored at the given restart point.
+    fn restart_key(&self, restart: usize) -> u64 {
+        let offset = self.restart_offset(restart);
+        let bytes = std::array::from_fn(|i| self.page.0.get(offset + i).copied().unwrap_or(0));
+        u64::from_le_bytes(bytes)
+    }
+
+    /// The offset of the entry at the given restart point.
+    fn restart_offset(&self, restart: usize) -> usize {
+        let offset = PAGE_DATA_SIZE - (self.restarts - restart) * RESTART_SIZE;
+        u16::from_le_bytes([self.page.0[offset], self.page.0[offset + 1]]) as usize
+    }
+
+    /// Decodes the pair at the given restart point.
+    fn decode_restart(&self, restart: usize) -> Cursor {
+        let offset = self.restart_offset(restart) + size_of::<u64>();
+        let (value, size) = read_varint(&self.page.0, offset).unwrap_or_default();
+        Cursor {
+            index: restart * RESTART_INTERVAL,
+            next_offset: offset + size,
+            key: self.restart_key(restart),
+            value,
+        }
+    }
+
+    /// Decodes the pair after the one at the cursor.
+    fn decode_next(&self, cursor: Cursor) -> Cursor {
+        let index = cursor.index + 1;
+        if index.is_multiple_of(RESTART_INTERVAL) {
+            return self.decode_restart(index / RESTART_INTERVAL);
+        }
+
+        let offset = cursor.next_offset;
+        let (delta, key_size) = read_varint(&self.page.0, offset).unwrap_or_default();
+        let (value, value_size) = read_varint(&self.page.0, offset + key_size).unwrap_or_default();
+        Cursor {
+            index,
+            next_offset: offset + key_size + value_size,
+            key: cursor.key.wrapping_add(delta),
+            value,
+        }
+    }
+}
+
+/// Returns the number of bytes that `write_varint` takes for the value.
+fn varint_size(value: u64) -> usize {
+    (u64::BITS - value.leading_zeros()).max(1).div_ceil(7) as usize
+}
+
+/// Writes the value as a little-endian base-128 varint to the start of the given bytes,
+/// and returns the number of bytes written.
+fn write_varint(mut value: u64, bytes: &mut [u8]) -> usize {
+    let mut size = 0;
+    while value >= 0x80 {
+        bytes[size] = value as u8 | 0x80;
+        value >>= 7;
+        size += 1;
+    }
+    bytes[size] = value as u8;
+    size + 1
+}
+
+/// Reads a varint at the given offset of the bytes, returning it and its size,
+/// or None if it's invalid or runs past the end of the bytes.
+fn read_varint(bytes: &[u8], offset: usize) -> Option<(u64, usize)> {
+    let mut value: u64 = 0;
+    for size in 0..MAX_VARINT_SIZE {
+        let byte = *bytes.get(offset + size)?;
+        let bits = (byte & 0x7f) as u64;
+        let shift = 7 * size as u32;
+        if bits << shift >> shift != bits {
+            return None;
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Some((value, size + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PAGE_SIZE;
+
+    use super::*;
+
+    const MAX_LENGTH: usize = 4096;
+
+    fn write(pairs: &[[u64; 2]]) -> (Arc<Aligned>, usize) {
+        let mut page = Aligned([0; PAGE_SIZE]);
+        let length = fill(&mut page, pairs);
+        (Arc::new(page), length)
+    }
+
+    #[test]
+    fn test_read_write() {
+        let cases: Vec<Vec<[u64; 2]>> = vec![
+            vec![[0, 0]],
+            vec![[u64::MAX, u64::MAX]],
+            vec![[0, u64::MAX], [1, 0], [u64::MAX, 12345]],
+            (0..1000).map(|i| [i * 3, i % 5]).collect(),
+            (0..1000).map(|i| [i << 40, u64::MAX - i]).collect(),
+            (0..1000).map(|i| [i * 1000 + 17, i * i]).collect(),
+        ];
+        for pairs in cases {
+            let (page, length) = write(&pairs);
+            assert!(length > 0);
+            let pairs = &pairs[..length];
+            assert_eq!(DeltaLeaf::decode_all(&page, MAX_LENGTH).unwrap(), pairs);
+
+            let leaf = DeltaLeaf::new(page, MAX_LENGTH).unwrap();
+            assert_eq!(leaf.len(), length);
+            // In order, backwards, and jumping around
+            for (i, &pair) in pairs.iter().enumerate() {
+                assert_eq!(leaf.pair(i), pair);
+            }
+            for (i, &pair) in pairs.iter().enumerate().rev() {
+                assert_eq!(leaf.pair(i), pair);
+            }
+            for _ in 0..100 {
+                let i = fastrand::usize(..length);
+                assert_eq!(leaf.pair(i), pairs[i]);
+            }
+
+            for &[key, _] in pairs {
+                for key in [key.saturating_sub(1), key, key.saturating_add(1)] {
+                    assert_eq!(
+                        leaf.binary_search(key),
+                        pairs.binary_search_by_key(&key, |pair| pair[0]),
+                        "{key}"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Tests that clustered keys with small values fit far more pairs than an uncompressed page.
+    #[test]
+    fn test_clustered_keys() {
+        let pairs: Vec<[u64; 2]> = (0..10_000)
+            .map(|i| [1_700_000_000_000 + i * 10 + i % 3, i % 100])
+            .collect();
+        let (page, length) = write(&pairs);
+        assert!(length > 1000, "{length}");
+        assert_eq!(
+            DeltaLeaf::decode_all(&page, MAX_LENGTH).unwrap(),
+            pairs[..length]
+        );
+
+        // Random keys and values take more space than uncompressed pairs, but still fit
+        let mut pairs: Vec<[u64; 2]> = (0..1000).map(|_| [fastrand::u64(..), u64::MAX]).collect();
+        pairs.sort();
+        let (page, length) = write(&pairs);
+        assert!(length > 150, "{length}");
+        assert_eq!(
+            DeltaLeaf::decode_all(&page, MAX_LENGTH).unwrap(),
+            pairs[..length]
+        );
+    }
+
+    #[test]
+    fn test_corrupt() {
+        let pairs: Vec<[u64; 2]> = (0..100).map(|i| [i * 1000, i]).collect();
+        let (page, _) = write(&pairs);
+        assert!(DeltaLeaf::decode_all(&page, 50).is_err());
+        assert!(DeltaLeaf::new(page.clone(), 50).is_err());
+
+        let mut corrupt = *page;
+        corrupt.0[8] += 1;
+        assert!(DeltaLeaf::new(Arc::new(corrupt), MAX_LENGTH).is_err());
+
+        // An unterminated varint
+        let mut corrupt = *page;
+        corrupt.0[HEADER_SIZE + 8..HEADER_SIZE + 20].fill(0xff);
+        assert!(DeltaLeaf::decode_all(&corrupt, MAX_LENGTH).is_err());
+        let leaf = DeltaLeaf::new(Arc::new(corrupt), MAX_LENGTH).unwrap();
+        for i in 0..pairs.len() {
+            leaf.pair(i);
+        }
+        leaf.binary_search(50_000).unwrap();
+
+        // A restart point pointing at the wrong entry
+        let mut corrupt = *page;
+        corrupt.0[PAGE_DATA_SIZE - 2] += 1;
+        assert!(DeltaLeaf::decode_all(&corrupt, MAX_LENGTH).is_err());
+    }
+}
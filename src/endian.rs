@@ -0,0 +1,61 @@
+use std::fmt::Debug;
+
+/// A `u64` stored as 8 little-endian bytes, regardless of the byte order of the machine.
+///
+/// All integers in SST files are stored this way, so that databases can be moved between machines.
+/// Having no alignment requirement, it can be used in structs cast from raw page bytes.
+#[repr(transparent)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LeU64([u8; 8]);
+
+impl LeU64 {
+    /// Returns the little-endian representation of the given value.
+    pub const fn new(value: u64) -> Self {
+        Self(value.to_le_bytes())
+    }
+
+    /// Returns the value in the byte order of the machine.
+    pub const fn get(self) -> u64 {
+        u64::from_le_bytes(self.0)
+    }
+
+    /// Replaces the stored value.
+    pub fn set(&mut self, value: u64) {
+        *self = Self::new(value);
+    }
+}
+
+impl From<u64> for LeU64 {
+    fn from(value: u64) -> Self {
+        Self::new(value)
+    }
+}
+
+impl Debug for LeU64 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_order() {
+        let value = LeU64::new(0x0102_0304_0506_0708);
+        assert_eq!(
+            bytemuck::bytes_of(&value),
+            [0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]
+        );
+        assert_eq!(value.get(), 0x0102_0304_0506_0708);
+        assert_eq!(align_of::<LeU64>(), 1);
+
+        let mut value = LeU64::default();
+        value.set(u64::MAX - 1);
+        assert_eq!(
+            bytemuck::bytes_of(&value),
+            [0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+    }
+}
@@ -16,6 +16,17 @@ pub enum DbError {
     CorruptSst,
     /// Tried to insert a key-value pair where the value is `u64::MAX` (reserved for tombstones).
     InvalidValue,
+    /// The checksum of a page read from disk did not match its contents.
+    Corruption {
+        /// The name of the file containing the page.
+        file: String,
+        /// The page number of the page within the file.
+        page: usize,
+    },
+    /// A file was written by a newer version of BEARR, using a format version that isn't supported.
+    UnsupportedFormatVersion(u64),
+    /// A file in a backup is missing or does not match the size and checksum recorded for it.
+    CorruptBackup(String),
 }
 
 impl Display for DbError {
@@ -27,6 +38,13 @@ impl Display for DbError {
             DbError::InvalidConfiguration => write!(f, "invalid database configuration"),
             DbError::CorruptSst => write!(f, "Corrupt SST file"),
             DbError::InvalidValue => write!(f, "invalid value (cannot use u64::MAX)"),
+            DbError::Corruption { file, page } => {
+                write!(f, "checksum mismatch in page {page} of {file}")
+            }
+            DbError::UnsupportedFormatVersion(version) => {
+                write!(f, "unsupported format version {version}")
+            }
+            DbError::CorruptBackup(s) => write!(f, "corrupt backup: {s}"),
         }
     }
 }
@@ -1,3 +1,7 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
 use crate::{
     DbError,
     file_system::BufferPageId,
@@ -5,8 +9,440 @@ use crate::{
     list::{EntryId, List},
 };
 
+/// A policy for choosing which page of a full buffer pool (or shard of one) to evict.
+///
+/// The buffer pool tells the policy about every page it adds, accesses and evicts,
+/// and asks it for victims in the order that it would rather evict them.
+/// Victims that are still referenced elsewhere are skipped, so the policy must offer every page eventually.
+pub trait EvictionPolicy {
+    /// How the policy refers to a page that it tracks.
+    /// Stays the same for as long as the page is in the buffer pool.
+    type Id: Copy;
+
+    /// Starts tracking a page that was just added to the buffer pool.
+    /// Must be a page that isn't already tracked.
+    ///
+    /// Panics if inserting above capacity.
+    fn insert_new(&mut self, page_id: BufferPageId) -> Self::Id;
+
+    /// Marks a page as accessed.
+    fn touch(&mut self, id: Self::Id);
+
+    /// Returns every tracked page, in the order that the policy would evict them.
+    fn choose_victim(&self) -> impl Iterator<Item = (Self::Id, BufferPageId)> + '_;
+
+    /// Stops tracking a page that was chosen as a victim and evicted from the buffer pool.
+    fn evict(&mut self, victim: Self::Id);
+}
+
+/// The eviction policy of the buffer pool.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum EvictionType {
+    /// Evicts the least recently used page.
+    Lru,
+    /// Approximates LRU by sweeping a hand over the pages,
+    /// evicting the first one that hasn't been accessed since the hand last passed it.
+    Clock,
+    /// Evicts the page whose `k`th most recent access is the oldest,
+    /// preferring pages accessed fewer than `k` times, least recently used first.
+    /// From [this paper](https://dl.acm.org/doi/10.1145/170036.170081). Must have `k` at least 1.
+    LruK { k: usize },
+    /// Balances a list of pages accessed once recently against a list of pages accessed more than once,
+    /// adapting its target for each from accesses to pages it recently evicted.
+    /// From [this paper](https://www.usenix.org/conference/fast-03/arc-self-tuning-low-overhead-replacement-cache).
+    Arc,
+    /// The 2Q policy, keeping A_in to `k_in` and A_out to `k_out`, as fractions of the capacity.
+    /// Must have both between 0 and 1.
+    TwoQ { k_in: f64, k_out: f64 },
+}
+
+impl Default for EvictionType {
+    /// Returns 2Q with A_in at ~25% and A_out at ~50% of the capacity.
+    fn default() -> Self {
+        Self::TwoQ {
+            k_in: 0.25,
+            k_out: 0.5,
+        }
+    }
+}
+
+impl EvictionType {
+    /// Returns whether the parameters of the policy are in range.
+    pub fn is_valid(self) -> bool {
+        match self {
+            Self::Lru | Self::Clock | Self::Arc => true,
+            Self::LruK { k } => k > 0,
+            Self::TwoQ { k_in, k_out } => {
+                (0.0..=1.0).contains(&k_in) && (0.0..=1.0).contains(&k_out)
+            }
+        }
+    }
+}
+
+/// Returns the IDs of the entries of the list and the entries themselves, from front to back.
+fn iter_list<T: Clone>(list: &List<T>) -> impl Iterator<Item = (EntryId, &T)> {
+    std::iter::successors(list.front(), |&(id, _)| list.get_next(id))
+}
+
+/// Returns the slots `0..capacity`, to be popped in increasing order.
+fn free_slots(capacity: usize) -> Result<Vec<usize>, DbError> {
+    let mut free_slots = Vec::new();
+    free_slots.try_reserve_exact(capacity)?;
+    free_slots.extend((0..capacity).rev());
+    Ok(free_slots)
+}
+
+/// Returns `capacity` empty slots.
+fn empty_slots<T: Clone>(capacity: usize) -> Result<Vec<Option<T>>, DbError> {
+    let mut slots = Vec::new();
+    slots.try_reserve_exact(capacity)?;
+    slots.resize(capacity, None);
+    Ok(slots)
+}
+
+/// Implements the LRU eviction policy, keeping pages in a list from least to most recently used.
+pub struct Lru {
+    pages: List<BufferPageId>,
+}
+
+impl Lru {
+    /// Creates a new LRU eviction handler with a given max capacity.
+    /// Returns `DbError::Oom` if allocation fails
+    pub fn new(capacity: usize) -> Result<Self, DbError> {
+        Ok(Self {
+            pages: List::new(capacity)?,
+        })
+    }
+}
+
+impl EvictionPolicy for Lru {
+    type Id = EntryId;
+
+    fn insert_new(&mut self, page_id: BufferPageId) -> EntryId {
+        self.pages.push_back(page_id)
+    }
+
+    fn touch(&mut self, id: EntryId) {
+        self.pages.move_to_back(id);
+    }
+
+    fn choose_victim(&self) -> impl Iterator<Item = (EntryId, BufferPageId)> + '_ {
+        iter_list(&self.pages).map(|(id, &page_id)| (id, page_id))
+    }
+
+    fn evict(&mut self, victim: EntryId) {
+        self.pages.delete(victim);
+    }
+}
+
+/// Implements the CLOCK eviction policy.
+///
+/// Pages sit in a ring of slots, each with a reference bit that is set when the page is accessed.
+/// New pages start with the bit clear, so they only get a second chance once they're accessed again.
+/// To find a victim, the hand sweeps the ring from where it stopped last, clearing the bits it passes,
+/// until it finds a page whose bit is already clear.
+pub struct Clock {
+    /// The page in each slot and its reference bit.
+    slots: Vec<Option<(BufferPageId, bool)>>,
+    free_slots: Vec<usize>,
+    hand: usize,
+}
+
+impl Clock {
+    /// Creates a new CLOCK eviction handler with a given max capacity.
+    /// Returns `DbError::Oom` if allocation fails
+    pub fn new(capacity: usize) -> Result<Self, DbError> {
+        Ok(Self {
+            slots: empty_slots(capacity)?,
+            free_slots: free_slots(capacity)?,
+            hand: 0,
+        })
+    }
+}
+
+impl EvictionPolicy for Clock {
+    type Id = usize;
+
+    fn insert_new(&mut self, page_id: BufferPageId) -> usize {
+        let slot = self.free_slots.pop().expect("cannot insert above capacity");
+        self.slots[slot] = Some((page_id, false));
+        slot
+    }
+
+    fn touch(&mut self, id: usize) {
+        if let Some((_, referenced)) = &mut self.slots[id] {
+            *referenced = true;
+        }
+    }
+
+    /// Returns the pages that the hand would stop at in its first sweep, then the rest,
+    /// which it would stop at in the second sweep after clearing their bits.
+    fn choose_victim(&self) -> impl Iterator<Item = (usize, BufferPageId)> + '_ {
+        let len = self.slots.len();
+        let sweep = move |pass_referenced: bool| {
+            (0..len)
+                .map(move |i| (self.hand + i) % len)
+                .filter_map(move |slot| match self.slots[slot] {
+                    Some((page_id, referenced)) if referenced == pass_referenced => {
+                        Some((slot, page_id))
+                    }
+                    _ => None,
+                })
+        };
+        sweep(false).chain(sweep(true))
+    }
+
+    /// Clears the bits of the pages that the hand passes on its way to the victim,
+    /// and leaves the hand just past it.
+    fn evict(&mut self, victim: usize) {
+        let len = self.slots.len();
+        while self.hand != victim {
+            if let Some((_, referenced)) = &mut self.slots[self.hand] {
+                *referenced = false;
+            }
+            self.hand = (self.hand + 1) % len;
+        }
+        self.slots[victim] = None;
+        self.free_slots.push(victim);
+        self.hand = (victim + 1) % len;
+    }
+}
+
+/// Implements the LRU-K eviction policy. From [this paper](https://dl.acm.org/doi/10.1145/170036.170081).
+///
+/// Remembers the times of the last k accesses to each page, counting being added as an access,
+/// and evicts the page whose kth most recent access is the oldest.
+/// Pages accessed fewer than k times go first, least recently used first,
+/// so pages read once by a scan don't push out pages that are read repeatedly.
+/// The history of a page is forgotten when it is evicted.
+pub struct LruK {
+    k: usize,
+    /// The page in each slot and the times of its last k accesses, oldest first.
+    slots: Vec<Option<(BufferPageId, VecDeque<u64>)>>,
+    free_slots: Vec<usize>,
+    /// The occupied slots in eviction order, keyed by their `LruK::priority`.
+    order: BTreeSet<(u64, u64, usize)>,
+    /// The time of the most recent access. Starts at 0, before any access.
+    time: u64,
+}
+
+impl LruK {
+    /// Creates a new LRU-K eviction handler with a given max capacity, which remembers `k` accesses per page.
+    /// Returns `DbError::Oom` if allocation fails
+    pub fn new(capacity: usize, k: usize) -> Result<Self, DbError> {
+        assert!(k > 0, "LRU-K needs to remember at least one access");
+        Ok(Self {
+            k,
+            slots: empty_slots(capacity)?,
+            free_slots: free_slots(capacity)?,
+            order: BTreeSet::new(),
+            time: 0,
+        })
+    }
+
+    /// Returns the key of the given slot in `LruK::order`:
+    /// the time of its kth most recent access (0 if it has fewer), then of its most recent one.
+    fn priority(&self, slot: usize) -> (u64, u64, usize) {
+        let (_, history) = self.slots[slot].as_ref().unwrap();
+        let kth = if history.len() == self.k {
+            history[0]
+        } else {
+            0
+        };
+        (kth, *history.back().unwrap(), slot)
+    }
+
+    /// Records an access to the page in the given slot.
+    fn access(&mut self, slot: usize) {
+        self.time += 1;
+        let (_, history) = self.slots[slot].as_mut().unwrap();
+        if history.len() == self.k {
+            history.pop_front();
+        }
+        history.push_back(self.time);
+    }
+}
+
+impl EvictionPolicy for LruK {
+    type Id = usize;
+
+    fn insert_new(&mut self, page_id: BufferPageId) -> usize {
+        let slot = self.free_slots.pop().expect("cannot insert above capacity");
+        self.slots[slot] = Some((page_id, VecDeque::with_capacity(self.k)));
+        self.access(slot);
+        self.order.insert(self.priority(slot));
+        slot
+    }
+
+    fn touch(&mut self, id: usize) {
+        self.order.remove(&self.priority(id));
+        self.access(id);
+        self.order.insert(self.priority(id));
+    }
+
+    fn choose_victim(&self) -> impl Iterator<Item = (usize, BufferPageId)> + '_ {
+        self.order
+            .iter()
+            .map(|&(_, _, slot)| (slot, self.slots[slot].as_ref().unwrap().0))
+    }
+
+    fn evict(&mut self, victim: usize) {
+        self.order.remove(&self.priority(victim));
+        self.slots[victim] = None;
+        self.free_slots.push(victim);
+    }
+}
+
+/// Where a page tracked by `AdaptiveReplacement` is.
+#[derive(Clone, Copy, Debug)]
+struct ArcEntry {
+    page_id: BufferPageId,
+    /// Whether the page is in T2 rather than T1.
+    frequent: bool,
+    /// The page's entry in T1 or T2.
+    entry: EntryId,
+}
+
+/// Implements the ARC eviction policy.
+/// From [this paper](https://www.usenix.org/conference/fast-03/arc-self-tuning-low-overhead-replacement-cache).
+///
+/// Maintains a LRU queue called T1 for pages that have been accessed once since they were added,
+/// and a LRU queue called T2 for pages that have been accessed again (i.e. hot pages).
+/// Pages evicted from T1 and T2 are remembered in the ghost queues B1 and B2,
+/// which store only the page IDs, not the pages.
+///
+/// On eviction, we evict from T1 if it holds more than the target number of pages p, otherwise from T2.
+/// A page added while it is in B1 means T1 was too small, so p grows,
+/// and a page added while it is in B2 means T2 was too small, so p shrinks.
+/// Either way it goes straight into T2.
+///
+/// T1 and B1 together hold at most c pages, and all four queues at most 2c, where c is the capacity.
+pub struct AdaptiveReplacement {
+    capacity: usize,
+    /// The target size of T1, p.
+    target: usize,
+    /// Where each page is, by slot.
+    slots: Vec<Option<ArcEntry>>,
+    free_slots: Vec<usize>,
+    t1: List<usize>,
+    t2: List<usize>,
+    b1: List<BufferPageId>,
+    b2: List<BufferPageId>,
+    map_b1: HashTable<BufferPageId, EntryId>,
+    map_b2: HashTable<BufferPageId, EntryId>,
+}
+
+impl AdaptiveReplacement {
+    /// Creates a new ARC eviction handler with a given max capacity,
+    /// whose tables of recently evicted pages hash with the given seed.
+    /// Returns `DbError::Oom` if allocation fails
+    pub fn new(capacity: usize, seed: u32) -> Result<Self, DbError> {
+        Ok(Self {
+            capacity,
+            target: 0,
+            slots: empty_slots(capacity)?,
+            free_slots: free_slots(capacity)?,
+            t1: List::new(capacity)?,
+            t2: List::new(capacity)?,
+            b1: List::new(capacity)?,
+            b2: List::new(2 * capacity)?,
+            map_b1: HashTable::new(capacity, seed)?,
+            map_b2: HashTable::new(2 * capacity, !seed)?,
+        })
+    }
+
+    /// Returns the total number of pages in all four queues.
+    fn total_len(&self) -> usize {
+        self.t1.len() + self.t2.len() + self.b1.len() + self.b2.len()
+    }
+
+    /// Forgets the oldest page in the given ghost queue.
+    fn forget_oldest(list: &mut List<BufferPageId>, map: &mut HashTable<BufferPageId, EntryId>) {
+        if let Some(page_id) = list.pop_front() {
+            map.remove(page_id);
+        }
+    }
+}
+
+impl EvictionPolicy for AdaptiveReplacement {
+    type Id = usize;
+
+    fn insert_new(&mut self, page_id: BufferPageId) -> usize {
+        let frequent = if let Some(&entry) = self.map_b1.get(page_id) {
+            let delta = (self.b2.len() / self.b1.len()).max(1);
+            self.target = (self.target + delta).min(self.capacity);
+            self.b1.delete(entry);
+            self.map_b1.remove(page_id);
+            true
+        } else if let Some(&entry) = self.map_b2.get(page_id) {
+            let delta = (self.b1.len() / self.b2.len()).max(1);
+            self.target = self.target.saturating_sub(delta);
+            self.b2.delete(entry);
+            self.map_b2.remove(page_id);
+            true
+        } else {
+            // Make space in the ghost queues for the new page
+            if self.t1.len() + self.b1.len() >= self.capacity {
+                Self::forget_oldest(&mut self.b1, &mut self.map_b1);
+            } else if self.total_len() >= 2 * self.capacity {
+                Self::forget_oldest(&mut self.b2, &mut self.map_b2);
+            }
+            false
+        };
+
+        let slot = self.free_slots.pop().expect("cannot insert above capacity");
+        let entry = if frequent {
+            self.t2.push_back(slot)
+        } else {
+            self.t1.push_back(slot)
+        };
+        self.slots[slot] = Some(ArcEntry {
+            page_id,
+            frequent,
+            entry,
+        });
+        slot
+    }
+
+    fn touch(&mut self, id: usize) {
+        let arc_entry = self.slots[id].as_mut().unwrap();
+        if arc_entry.frequent {
+            self.t2.move_to_back(arc_entry.entry);
+        } else {
+            self.t1.delete(arc_entry.entry);
+            arc_entry.entry = self.t2.push_back(id);
+            arc_entry.frequent = true;
+        }
+    }
+
+    fn choose_victim(&self) -> impl Iterator<Item = (usize, BufferPageId)> + '_ {
+        let (first, second) = if self.t1.len() > self.target || self.t2.is_empty() {
+            (&self.t1, &self.t2)
+        } else {
+            (&self.t2, &self.t1)
+        };
+        iter_list(first)
+            .chain(iter_list(second))
+            .map(|(_, &slot)| (slot, self.slots[slot].unwrap().page_id))
+    }
+
+    fn evict(&mut self, victim: usize) {
+        let arc_entry = self.slots[victim].take().unwrap();
+        self.free_slots.push(victim);
+        if arc_entry.frequent {
+            self.t2.delete(arc_entry.entry);
+            let ghost = self.b2.push_back(arc_entry.page_id);
+            self.map_b2.insert(arc_entry.page_id, ghost);
+        } else {
+            self.t1.delete(arc_entry.entry);
+            let ghost = self.b1.push_back(arc_entry.page_id);
+            self.map_b1.insert(arc_entry.page_id, ghost);
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum EvictionId {
+pub enum TwoQId {
     AIn(EntryId),
     AM(EntryId),
 }
@@ -17,10 +453,10 @@ pub enum EvictionId {
 /// Another FIFO queue called A_out for pages that have been evicted from A_in recently.
 /// And a LRU queue called A_m for pages that were accessed again while in A_out (i.e. hot pages).
 ///
-/// A_in has a capacity of k_in which we set to ~25% of the total capacity of the buffer pool.
+/// A_in has a capacity of k_in, which is ~25% of the total capacity of the buffer pool by default.
 /// A_in is not kept strictly within capacity k_in.
 ///
-/// A_out has a capacity of k_out which we set to ~50% of the total capacity of the buffer pool.
+/// A_out has a capacity of k_out, which is ~50% of the total capacity of the buffer pool by default.
 /// A_out *is* kept strictly within capacity k_out.
 ///
 /// On eviction, if A_in has at least k_in elements, we evict the from A_in. Otherwise, we evict
@@ -30,7 +466,7 @@ pub enum EvictionId {
 /// reference in A_out if it is at capacity.
 /// A_out only stores whether we've recently evicted a page, but not the actual page contents, so it is
 /// not too much memory.
-pub struct Eviction {
+pub struct TwoQ {
     a_in: List<BufferPageId>,
     a_m: List<BufferPageId>,
     a_out: List<BufferPageId>,
@@ -39,14 +475,16 @@ pub struct Eviction {
     k_out: usize,
 }
 
-impl Eviction {
-    /// Creates a new 2Q eviction handler with a given max capacity.
+impl TwoQ {
+    /// Creates a new 2Q eviction handler with a given max capacity,
+    /// whose A_in and A_out have the given fractions of it,
+    /// and whose table of recently evicted pages hashes with the given seed.
     /// Returns `DbError::Oom` if allocation fails
-    pub fn new(capacity: usize) -> Result<Self, DbError> {
-        let k_in = capacity / 4 + 1; // ~25%
-        let k_out = capacity / 2 + 1; // ~50%
+    pub fn new(capacity: usize, k_in: f64, k_out: f64, seed: u32) -> Result<Self, DbError> {
+        let k_in = (capacity as f64 * k_in) as usize + 1;
+        let k_out = (capacity as f64 * k_out) as usize + 1;
 
-        let map_out = HashTable::new(k_out)?;
+        let map_out = HashTable::new(k_out, seed)?;
 
         Ok(Self {
             a_in: List::new(capacity)?, // not k_in because it might grow past that as long as we haven't evicted
@@ -57,16 +495,20 @@ impl Eviction {
             k_out,
         })
     }
+}
+
+impl EvictionPolicy for TwoQ {
+    type Id = TwoQId;
 
     /// Returns a `VictimChooser` that allows you to select a victim to evict, ordered by how 2Q
     /// would have evicted them.
-    pub fn choose_victim(&'_ self) -> VictimChooser<'_> {
+    fn choose_victim(&self) -> impl Iterator<Item = (TwoQId, BufferPageId)> + '_ {
         VictimChooser::new(self)
     }
 
-    pub fn evict(&mut self, victim: EvictionId) {
+    fn evict(&mut self, victim: TwoQId) {
         match victim {
-            EvictionId::AIn(id) => {
+            TwoQId::AIn(id) => {
                 // Make space in a_out
                 if self.a_out.len() >= self.k_out {
                     // a_out and map_out can't be empty
@@ -78,7 +520,7 @@ impl Eviction {
                 let a_out_id = self.a_out.push_back(evicted_a_in);
                 self.map_out.insert(evicted_a_in, a_out_id);
             }
-            EvictionId::AM(id) => {
+            TwoQId::AM(id) => {
                 self.a_m.delete(id);
             }
         }
@@ -86,36 +528,36 @@ impl Eviction {
 
     /// Inserts a new page into the eviction handler. Must be a page that hasn't been inserted before.
     /// Panics if inserting above capacity
-    pub fn insert_new(&mut self, page_id: BufferPageId) -> EvictionId {
+    fn insert_new(&mut self, page_id: BufferPageId) -> TwoQId {
         if let Some(&idx_out) = self.map_out.get(page_id) {
             let removed = self.a_out.delete(idx_out);
             debug_assert_eq!(removed, page_id);
             self.map_out.remove(page_id);
             let id = self.a_m.push_back(page_id);
-            EvictionId::AM(id)
+            TwoQId::AM(id)
         } else {
             let id = self.a_in.push_back(page_id);
-            EvictionId::AIn(id)
+            TwoQId::AIn(id)
         }
     }
 
     /// Mark a page as used
-    pub fn touch(&mut self, id: EvictionId) {
+    fn touch(&mut self, id: TwoQId) {
         match id {
-            EvictionId::AIn(_) => {}
-            EvictionId::AM(id) => self.a_m.move_to_back(id),
+            TwoQId::AIn(_) => {}
+            TwoQId::AM(id) => self.a_m.move_to_back(id),
         }
     }
 }
 
 pub struct VictimChooser<'a> {
-    eviction: &'a Eviction,
-    last_id: Option<EvictionId>,
+    eviction: &'a TwoQ,
+    last_id: Option<TwoQId>,
     ended: bool,
 }
 
 impl<'a> VictimChooser<'a> {
-    fn new(eviction: &'a Eviction) -> Self {
+    fn new(eviction: &'a TwoQ) -> Self {
         Self {
             eviction,
             last_id: None,
@@ -124,7 +566,7 @@ impl<'a> VictimChooser<'a> {
     }
 }
 impl<'a> Iterator for VictimChooser<'a> {
-    type Item = (EvictionId, BufferPageId);
+    type Item = (TwoQId, BufferPageId);
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.ended {
@@ -140,13 +582,13 @@ impl<'a> Iterator for VictimChooser<'a> {
                 {
                     // At this point a_in can't be empty
                     let (id, &front) = self.eviction.a_in.front().unwrap();
-                    let eviction_id = EvictionId::AIn(id);
+                    let eviction_id = TwoQId::AIn(id);
                     self.last_id = Some(eviction_id);
                     Some((eviction_id, front))
                 }
                 // Otherwise try evict from a_m
                 else if let Some((id, &front)) = self.eviction.a_m.front() {
-                    let eviction_id = EvictionId::AM(id);
+                    let eviction_id = TwoQId::AM(id);
                     self.last_id = Some(eviction_id);
                     Some((eviction_id, front))
                 } else {
@@ -155,16 +597,16 @@ impl<'a> Iterator for VictimChooser<'a> {
                     None
                 }
             }
-            Some(EvictionId::AIn(id)) => {
+            Some(TwoQId::AIn(id)) => {
                 // They didn't want AIn(id) as the victim, choose next in a_in
                 if let Some((next_id, &next_entry)) = self.eviction.a_in.get_next(id) {
-                    let eviction_id = EvictionId::AIn(next_id);
+                    let eviction_id = TwoQId::AIn(next_id);
                     self.last_id = Some(eviction_id);
                     Some((eviction_id, next_entry))
                 }
                 // If done with a_in, switch to a_m
                 else if let Some((id, &front)) = self.eviction.a_m.front() {
-                    let eviction_id = EvictionId::AM(id);
+                    let eviction_id = TwoQId::AM(id);
                     self.last_id = Some(eviction_id);
                     Some((eviction_id, front))
                 } else {
@@ -173,10 +615,10 @@ impl<'a> Iterator for VictimChooser<'a> {
                     None
                 }
             }
-            Some(EvictionId::AM(id)) => {
+            Some(TwoQId::AM(id)) => {
                 // They didn't want AM(id) as the victim, choose next in a_m
                 if let Some((next_id, &next_entry)) = self.eviction.a_m.get_next(id) {
-                    let eviction_id = EvictionId::AM(next_id);
+                    let eviction_id = TwoQId::AM(next_id);
                     self.last_id = Some(eviction_id);
                     Some((eviction_id, next_entry))
                 } else {
@@ -203,7 +645,7 @@ mod tests {
 
     #[test]
     fn test_a_in_fifo() -> Result<()> {
-        let mut ev = Eviction::new(8)?;
+        let mut ev = TwoQ::new(8, 0.25, 0.5, 0)?;
 
         // Insert several new pages to A_in
         let page_id = make_page(1);
@@ -213,9 +655,9 @@ mod tests {
         let page_id = make_page(3);
         let p3 = ev.insert_new(page_id);
 
-        assert!(matches!(p1, EvictionId::AIn(_)));
-        assert!(matches!(p2, EvictionId::AIn(_)));
-        assert!(matches!(p3, EvictionId::AIn(_)));
+        assert!(matches!(p1, TwoQId::AIn(_)));
+        assert!(matches!(p2, TwoQId::AIn(_)));
+        assert!(matches!(p3, TwoQId::AIn(_)));
 
         assert!(ev.a_in.len() == 3);
         assert!(ev.a_out.is_empty());
@@ -241,7 +683,7 @@ mod tests {
 
     #[test]
     fn test_evict_to_a_out() -> Result<()> {
-        let mut ev = Eviction::new(4)?;
+        let mut ev = TwoQ::new(4, 0.25, 0.5, 0)?;
 
         for i in 0..4 {
             let page_id = make_page(i);
@@ -262,7 +704,7 @@ mod tests {
 
     #[test]
     fn test_reaccess_moves_to_am() -> Result<()> {
-        let mut ev = Eviction::new(6)?;
+        let mut ev = TwoQ::new(6, 0.25, 0.5, 0)?;
 
         // Insert to A_in
         let page_id = make_page(0);
@@ -279,7 +721,7 @@ mod tests {
 
         // Re-accessing should move it to A_m
         let new_id = ev.insert_new(page_id);
-        assert!(matches!(new_id, EvictionId::AM(_)));
+        assert!(matches!(new_id, TwoQId::AM(_)));
         assert!(ev.a_m.len() == 1);
 
         Ok(())
@@ -287,7 +729,7 @@ mod tests {
 
     #[test]
     fn test_am_lru() -> Result<()> {
-        let mut ev = Eviction::new(8)?;
+        let mut ev = TwoQ::new(8, 0.25, 0.5, 0)?;
 
         let mut ids = Vec::new();
         for i in 0..3 {
@@ -327,7 +769,7 @@ mod tests {
 
     #[test]
     fn test_choose_victim_order() -> Result<()> {
-        let mut ev = Eviction::new(10)?;
+        let mut ev = TwoQ::new(10, 0.25, 0.5, 0)?;
 
         // Insert 10 pages
         for i in 0..10 {
@@ -348,16 +790,16 @@ mod tests {
         // Check eviction order matches FIFO/LRU expectations
         let mut chooser = ev.choose_victim();
 
-        assert!(matches!(chooser.next(), Some((EvictionId::AIn(_), p_n)) if p_n == make_page(5)));
-        assert!(matches!(chooser.next(), Some((EvictionId::AIn(_), p_n)) if p_n == make_page(6)));
-        assert!(matches!(chooser.next(), Some((EvictionId::AIn(_), p_n)) if p_n == make_page(7)));
-        assert!(matches!(chooser.next(), Some((EvictionId::AIn(_), p_n)) if p_n == make_page(8)));
-        assert!(matches!(chooser.next(), Some((EvictionId::AIn(_), p_n)) if p_n == make_page(9)));
-        assert!(matches!(chooser.next(), Some((EvictionId::AM(_), p_n)) if p_n == make_page(0)));
-        assert!(matches!(chooser.next(), Some((EvictionId::AM(_), p_n)) if p_n == make_page(1)));
-        assert!(matches!(chooser.next(), Some((EvictionId::AM(_), p_n)) if p_n == make_page(2)));
-        assert!(matches!(chooser.next(), Some((EvictionId::AM(_), p_n)) if p_n == make_page(3)));
-        assert!(matches!(chooser.next(), Some((EvictionId::AM(_), p_n)) if p_n == make_page(4)));
+        assert!(matches!(chooser.next(), Some((TwoQId::AIn(_), p_n)) if p_n == make_page(5)));
+        assert!(matches!(chooser.next(), Some((TwoQId::AIn(_), p_n)) if p_n == make_page(6)));
+        assert!(matches!(chooser.next(), Some((TwoQId::AIn(_), p_n)) if p_n == make_page(7)));
+        assert!(matches!(chooser.next(), Some((TwoQId::AIn(_), p_n)) if p_n == make_page(8)));
+        assert!(matches!(chooser.next(), Some((TwoQId::AIn(_), p_n)) if p_n == make_page(9)));
+        assert!(matches!(chooser.next(), Some((TwoQId::AM(_), p_n)) if p_n == make_page(0)));
+        assert!(matches!(chooser.next(), Some((TwoQId::AM(_), p_n)) if p_n == make_page(1)));
+        assert!(matches!(chooser.next(), Some((TwoQId::AM(_), p_n)) if p_n == make_page(2)));
+        assert!(matches!(chooser.next(), Some((TwoQId::AM(_), p_n)) if p_n == make_page(3)));
+        assert!(matches!(chooser.next(), Some((TwoQId::AM(_), p_n)) if p_n == make_page(4)));
         assert!(chooser.next().is_none());
         assert!(chooser.next().is_none());
 
@@ -366,7 +808,7 @@ mod tests {
 
     #[test]
     fn test_a_out_capacity() -> Result<()> {
-        let mut ev = Eviction::new(8)?;
+        let mut ev = TwoQ::new(8, 0.25, 0.5, 0)?;
         let k_out = ev.k_out;
 
         // Fill beyond A_out capacity
@@ -382,4 +824,118 @@ mod tests {
 
         Ok(())
     }
+
+    /// Returns the pages in the order that the eviction handler would evict them.
+    fn victim_pages(ev: &impl EvictionPolicy) -> Vec<usize> {
+        ev.choose_victim()
+            .map(|(_, page_id)| page_id.page_number)
+            .collect()
+    }
+
+    #[test]
+    fn test_lru() -> Result<()> {
+        let mut ev = Lru::new(4)?;
+
+        let ids: Vec<_> = (0..3).map(|i| ev.insert_new(make_page(i))).collect();
+        ev.touch(ids[0]);
+        assert_eq!(victim_pages(&ev), [1, 2, 0]);
+
+        ev.evict(ids[1]);
+        ev.touch(ids[2]);
+        assert_eq!(victim_pages(&ev), [0, 2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clock_second_chance() -> Result<()> {
+        let mut ev = Clock::new(4)?;
+
+        let ids: Vec<_> = (0..4).map(|i| ev.insert_new(make_page(i))).collect();
+        ev.touch(ids[0]);
+        ev.touch(ids[1]);
+
+        // The hand passes the touched pages, clearing their bits
+        assert_eq!(victim_pages(&ev), [2, 3, 0, 1]);
+        ev.evict(ids[2]);
+
+        // The new page takes the free slot behind the hand
+        ev.insert_new(make_page(4));
+        assert_eq!(victim_pages(&ev), [3, 0, 1, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lru_k() -> Result<()> {
+        let mut ev = LruK::new(4, 2)?;
+
+        let ids: Vec<_> = (0..3).map(|i| ev.insert_new(make_page(i))).collect();
+        ev.touch(ids[0]);
+
+        // Pages accessed fewer than k times go first, least recently used first
+        assert_eq!(victim_pages(&ev), [1, 2, 0]);
+
+        // Then by the time of the kth most recent access,
+        // so page 1 goes before page 2 even though it was accessed more recently
+        ev.touch(ids[2]);
+        ev.touch(ids[1]);
+        ev.touch(ids[0]);
+        assert_eq!(victim_pages(&ev), [1, 2, 0]);
+
+        ev.evict(ids[1]);
+        assert_eq!(victim_pages(&ev), [2, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_arc_adapts() -> Result<()> {
+        let mut ev = AdaptiveReplacement::new(4, 0)?;
+
+        let ids: Vec<_> = (0..4).map(|i| ev.insert_new(make_page(i))).collect();
+        ev.touch(ids[0]);
+        assert_eq!((ev.t1.len(), ev.t2.len()), (3, 1));
+        assert_eq!(victim_pages(&ev), [1, 2, 3, 0]);
+
+        // Evicting from T1 remembers the page in B1
+        ev.evict(ids[1]);
+        assert!(ev.map_b1.get(make_page(1)).is_some());
+
+        // Adding it again grows the target of T1, and puts it in T2
+        ev.insert_new(make_page(1));
+        assert_eq!(ev.target, 1);
+        assert!(ev.b1.is_empty());
+        assert_eq!((ev.t1.len(), ev.t2.len()), (2, 2));
+        assert_eq!(victim_pages(&ev), [2, 3, 0, 1]);
+
+        // Evicting from T2 remembers the page in B2, and adding it again shrinks the target
+        let victim = ev
+            .choose_victim()
+            .find(|&(_, page_id)| page_id == make_page(0));
+        ev.evict(victim.unwrap().0);
+        assert!(ev.map_b2.get(make_page(0)).is_some());
+        ev.insert_new(make_page(0));
+        assert_eq!(ev.target, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_arc_ghost_capacity() -> Result<()> {
+        let mut ev = AdaptiveReplacement::new(4, 0)?;
+
+        // A scan of new pages never lets T1 and B1 together exceed the capacity
+        for i in 0..20 {
+            if i >= 4 {
+                let victim = ev.choose_victim().next().unwrap().0;
+                ev.evict(victim);
+            }
+            ev.insert_new(make_page(i));
+            assert!(ev.t1.len() + ev.b1.len() <= 4);
+            assert!(ev.total_len() <= 8);
+        }
+
+        Ok(())
+    }
 }
@@ -6,8 +6,11 @@ use std::{
     time::{Duration, Instant},
 };
 
-use bearr::{Database, DbConfiguration, LsmConfiguration};
-use clap::Parser;
+use bearr::{
+    Database, DbConfiguration, EvictionType, FilterType, HashType, LevelCompression,
+    LsmConfiguration, RangeFilterConfiguration, SearchStrategy,
+};
+use clap::{Parser, ValueEnum};
 use indicatif::ProgressStyle;
 use serde::{Deserialize, Serialize};
 
@@ -17,6 +20,24 @@ struct Cli {
     #[arg(long, default_value_t = 65_536)]
     buffer_pool_capacity: usize,
 
+    // Split the buffer pool into this many separately locked shards
+    #[arg(long)]
+    buffer_pool_shards: Option<usize>,
+
+    #[arg(long, value_enum, default_value_t = EvictionArg::TwoQ)]
+    eviction: EvictionArg,
+
+    // The number of accesses that LRU-K remembers per page
+    #[arg(long, default_value_t = 2)]
+    lru_k: usize,
+
+    // The fractions of the buffer pool capacity for the A_in and A_out queues of 2Q
+    #[arg(long, default_value_t = 0.25)]
+    two_q_k_in: f64,
+
+    #[arg(long, default_value_t = 0.5)]
+    two_q_k_out: f64,
+
     #[arg(long, default_value_t = 96)]
     write_buffering: usize,
 
@@ -36,9 +57,45 @@ struct Cli {
     #[arg(long, default_value_t = 13)]
     bloom_filter_bits: usize,
 
+    // Give every level `bloom_filter_bits` bits per entry instead of using Monkey
+    #[arg(long)]
+    uniform_bits: bool,
+
+    // Assign bloom filter bits from the actual level sizes and lookups instead of the level alone
+    #[arg(long)]
+    workload_aware_bits: bool,
+
+    #[arg(long, value_enum, default_value_t = SearchStrategyArg::BTree)]
+    search_strategy: SearchStrategyArg,
+
+    #[arg(long, value_enum, default_value_t = FilterTypeArg::Bloom)]
+    filter_type: FilterTypeArg,
+
+    // Hash keys for the filters with this algorithm instead of the filter type's default
+    #[arg(long, value_enum)]
+    filter_hash: Option<HashTypeArg>,
+
+    // Keep the partitioned filters of this many levels from the top in memory
+    #[arg(long, default_value_t = 0)]
+    pinned_filter_levels: usize,
+
+    // Give SSTs range filters over buckets of 2^bits keys
+    #[arg(long)]
+    range_filter_bucket_bits: Option<u32>,
+
+    #[arg(long, default_value_t = 10)]
+    range_filter_bits: usize,
+
     #[arg(long)]
     wal_buffer_size: Option<usize>,
 
+    #[arg(long)]
+    wal_archiving: bool,
+
+    // Derive every hash table and filter seed from this, for reproducible runs
+    #[arg(long)]
+    seed: Option<u64>,
+
     // 64M rows = 1 GiB
     #[arg(long, default_value_t = 64 * 1024 * 1024)]
     total_entries: usize,
@@ -59,6 +116,10 @@ struct Cli {
     #[arg(long, default_value_t = 1000)]
     entries_per_scan: usize,
 
+    // Scans end this far past their start key, so short scans often find nothing
+    #[arg(long, default_value_t = u64::MAX)]
+    scan_width: u64,
+
     #[arg(long)]
     get: Option<PathBuf>,
 
@@ -78,14 +139,93 @@ struct Cli {
     full_scan: Option<PathBuf>,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum SearchStrategyArg {
+    #[value(name = "btree")]
+    BTree,
+    Binary,
+    Interpolation,
+}
+
+impl From<SearchStrategyArg> for SearchStrategy {
+    fn from(arg: SearchStrategyArg) -> Self {
+        match arg {
+            SearchStrategyArg::BTree => SearchStrategy::BTree,
+            SearchStrategyArg::Binary => SearchStrategy::Binary,
+            SearchStrategyArg::Interpolation => SearchStrategy::Interpolation,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FilterTypeArg {
+    Bloom,
+    Blocked,
+    Xor,
+    Partitioned,
+}
+
+impl From<FilterTypeArg> for FilterType {
+    fn from(arg: FilterTypeArg) -> Self {
+        match arg {
+            FilterTypeArg::Bloom => FilterType::Bloom,
+            FilterTypeArg::Blocked => FilterType::Blocked,
+            FilterTypeArg::Xor => FilterType::Xor,
+            FilterTypeArg::Partitioned => FilterType::Partitioned,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HashTypeArg {
+    Murmur,
+    Murmur64,
+    #[value(name = "xxhash64")]
+    XxHash64,
+    U64Mixer,
+}
+impl From<HashTypeArg> for HashType {
+    fn from(arg: HashTypeArg) -> Self {
+        match arg {
+            HashTypeArg::Murmur => HashType::Murmur,
+            HashTypeArg::Murmur64 => HashType::Murmur64,
+            HashTypeArg::XxHash64 => HashType::XxHash64,
+            HashTypeArg::U64Mixer => HashType::U64Mixer,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EvictionArg {
+    Lru,
+    Clock,
+    LruK,
+    Arc,
+    #[value(name = "2q")]
+    TwoQ,
+}
+
 fn main() {
     let cli = Cli::parse();
 
     let db_config = DbConfiguration {
         buffer_pool_capacity: cli.buffer_pool_capacity, // 65,536 pages = 256 Mib
+        buffer_pool_shards: cli.buffer_pool_shards,
+        eviction: match cli.eviction {
+            EvictionArg::Lru => EvictionType::Lru,
+            EvictionArg::Clock => EvictionType::Clock,
+            EvictionArg::LruK => EvictionType::LruK { k: cli.lru_k },
+            EvictionArg::Arc => EvictionType::Arc,
+            EvictionArg::TwoQ => EvictionType::TwoQ {
+                k_in: cli.two_q_k_in,
+                k_out: cli.two_q_k_out,
+            },
+        },
         write_buffering: cli.write_buffering,
         readahead_buffering: cli.readahead_buffering,
         wal_buffer_size: cli.wal_buffer_size,
+        wal_archiving: cli.wal_archiving,
+        seed: cli.seed,
         lsm_configuration: LsmConfiguration {
             size_ratio: cli.size_ratio,
             memtable_capacity: cli.memtable_capacity, // 655,360 rows = 10 MiB
@@ -94,6 +234,20 @@ fn main() {
             // using Monkey with 13 bits per entry at the highest LSM tree level uses approx
             // the same total memory as having 8 bits per entry across all levels uniformly.
             bloom_filter_bits: cli.bloom_filter_bits,
+            compression: LevelCompression::default(),
+            pin_internal_nodes: false,
+            search_strategy: cli.search_strategy.into(),
+            uniform_bits: cli.uniform_bits,
+            workload_aware_bits: cli.workload_aware_bits,
+            filter_type: cli.filter_type.into(),
+            filter_hash: cli.filter_hash.map(Into::into),
+            pinned_filter_levels: cli.pinned_filter_levels,
+            range_filter: cli.range_filter_bucket_bits.map(|bucket_bits| {
+                RangeFilterConfiguration {
+                    bucket_bits,
+                    bits_per_bucket: cli.range_filter_bits,
+                }
+            }),
         },
     };
 
@@ -152,6 +306,7 @@ fn main() {
             scan_start_key_range: ..,
             scans_per_sample: ops_per_sample,
             entries_per_scan,
+            scan_width: cli.scan_width,
             sample_spacing,
             db_config,
         });
@@ -166,6 +321,7 @@ fn main() {
             scan_start_key_range: ..,
             scans_per_sample_per_thread: ops_per_sample,
             entries_per_scan,
+            scan_width: cli.scan_width,
             sample_spacing,
             db_config,
         });
@@ -533,6 +689,7 @@ struct BenchScanConfig<P: AsRef<Path>, R1: RangeBounds<u64> + Clone, R2: RangeBo
     scan_start_key_range: R2,
     scans_per_sample: usize,
     entries_per_scan: usize,
+    scan_width: u64,
     sample_spacing: usize,
     db_config: DbConfiguration,
 }
@@ -547,6 +704,7 @@ fn bench_scan<P: AsRef<Path>, R1: RangeBounds<u64> + Clone, R2: RangeBounds<u64>
         scan_start_key_range,
         scans_per_sample,
         entries_per_scan,
+        scan_width,
         sample_spacing,
         db_config,
     } = bench_config;
@@ -582,7 +740,7 @@ fn bench_scan<P: AsRef<Path>, R1: RangeBounds<u64> + Clone, R2: RangeBounds<u64>
                 let scan_start = rng.u64(scan_start_key_range.clone());
 
                 let scan = db
-                    .scan(scan_start..=u64::MAX)
+                    .scan(scan_start..=scan_start.saturating_add(scan_width))
                     .unwrap()
                     .take(entries_per_scan);
                 n_scanned_rows += scan.inspect(|row| assert!(row.is_ok())).count();
@@ -634,6 +792,7 @@ struct BenchConcurrentScanConfig<
     scan_start_key_range: R2,
     scans_per_sample_per_thread: usize,
     entries_per_scan: usize,
+    scan_width: u64,
     sample_spacing: usize,
     db_config: DbConfiguration,
 }
@@ -653,6 +812,7 @@ fn bench_concurrent_scan<
         scan_start_key_range,
         scans_per_sample_per_thread,
         entries_per_scan,
+        scan_width,
         sample_spacing,
         db_config,
     } = bench_config;
@@ -694,7 +854,7 @@ fn bench_concurrent_scan<
                             let scan_start = rng.u64(scan_start_key_range.clone());
 
                             let scan = db
-                                .scan(scan_start..=u64::MAX)
+                                .scan(scan_start..=scan_start.saturating_add(scan_width))
                                 .unwrap()
                                 .take(entries_per_scan);
                             thread_n_scanned_rows +=
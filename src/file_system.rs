@@ -4,15 +4,21 @@ use std::{
     ops::{DerefMut, Range},
     os::unix::fs::{FileExt, OpenOptionsExt},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, RwLock},
 };
 
 use crate::{
     DbError, PAGE_SIZE,
-    eviction::{Eviction, EvictionId},
+    eviction::{AdaptiveReplacement, Clock, EvictionPolicy, EvictionType, Lru, LruK, TwoQ},
+    hash::{MurmurHash, SeedSource, mix64},
     hashtable::HashTable,
 };
 
+/// The number of bytes at the end of every page that hold its checksum.
+pub const CHECKSUM_SIZE: usize = 4;
+/// The number of bytes at the start of every page that are available for data.
+pub const PAGE_DATA_SIZE: usize = PAGE_SIZE - CHECKSUM_SIZE;
+
 /// An aligned 4096-byte page, suitable for various transmutations.
 #[repr(C, align(4096))]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
@@ -33,6 +39,23 @@ impl Aligned {
     fn clear(&mut self) {
         self.0.fill(0);
     }
+
+    /// Returns the checksum of the data bytes of the page, seeded with the page's position in its file
+    /// so that pages written to the wrong place are also detected.
+    fn checksum(&self, page_number: usize) -> u32 {
+        MurmurHash::hash32(&self.0[..PAGE_DATA_SIZE], page_number as u32)
+    }
+
+    /// Writes the checksum of the page into its last bytes.
+    pub fn stamp_checksum(&mut self, page_number: usize) {
+        let checksum = self.checksum(page_number);
+        self.0[PAGE_DATA_SIZE..].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    /// Returns whether the checksum stored in the last bytes of the page matches its data.
+    pub fn verify_checksum(&self, page_number: usize) -> bool {
+        self.0[PAGE_DATA_SIZE..] == self.checksum(page_number).to_le_bytes()
+    }
 }
 
 /// Some information that identifies a data file in the database.
@@ -77,6 +100,19 @@ impl FileId {
         format!("data-lsm{lsm_level}-sst{sst_number}")
     }
 
+    /// Returns the file ID whose filename is the given name, or None if there isn't one.
+    pub fn parse(name: &str) -> Option<Self> {
+        let (lsm_level, sst_number) = name.strip_prefix("data-lsm")?.split_once("-sst")?;
+        let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if !is_number(lsm_level) || !is_number(sst_number) {
+            return None;
+        }
+        Some(Self {
+            lsm_level: lsm_level.parse().ok()?,
+            sst_number: sst_number.parse().ok()?,
+        })
+    }
+
     /// Returns the page ID for the page of this file with the given page number.
     pub fn page(self, page_number: usize) -> PageId {
         PageId {
@@ -106,27 +142,50 @@ impl BufferFileId {
 /// and puts, deletes, and flushes require a mutable (exclusive) reference to the database struct,
 /// so this should not happen.
 pub struct FileSystem {
-    inner: Mutex<InnerFs>,
+    /// Read-locked to look up the buffer ID of a file, so reads of the buffer pool don't wait on each other,
+    /// and write-locked only to assign or remove one.
+    file_map: RwLock<FileMap>,
+    /// Files written before pages had checksums, whose checksums are not verified.
+    unchecksummed_files: Mutex<Vec<FileId>>,
+    /// The buffer pool, split by page ID so that threads reading different pages rarely wait on each other.
+    shards: Shards,
     prefix: PathBuf,
-    capacity: usize,
     write_buffering: usize,
     readahead_buffering: usize,
+    seeds: SeedSource,
+}
+
+/// A part of the buffer pool, kept behind its own lock,
+/// that holds the pages whose IDs hash to it and evicts them independently of the other shards.
+struct Shard<E: EvictionPolicy> {
+    buffer_pool: HashTable<BufferPageId, BufferPoolEntry<E::Id>>,
+    eviction_handler: E,
+    capacity: usize,
+    hits: usize,
+    accesses: usize,
 }
 
-/// The parts of the file system that are collectively kept behind a lock.
-struct InnerFs {
-    buffer_pool: HashTable<BufferPageId, BufferPoolEntry>,
-    eviction_handler: Eviction,
-    file_map: FileMap,
-    buffer_pool_hits: usize,
-    buffer_pool_accesses: usize,
+/// The shards of the buffer pool, for the eviction policy that it was created with.
+enum Shards {
+    Lru(Vec<Mutex<Shard<Lru>>>),
+    Clock(Vec<Mutex<Shard<Clock>>>),
+    LruK(Vec<Mutex<Shard<LruK>>>),
+    Arc(Vec<Mutex<Shard<AdaptiveReplacement>>>),
+    TwoQ(Vec<Mutex<Shard<TwoQ>>>),
 }
 
-impl Drop for InnerFs {
+impl Drop for FileSystem {
     fn drop(&mut self) {
+        let (hits, accesses) = match &self.shards {
+            Shards::Lru(shards) => Shard::count_hits(shards),
+            Shards::Clock(shards) => Shard::count_hits(shards),
+            Shards::LruK(shards) => Shard::count_hits(shards),
+            Shards::Arc(shards) => Shard::count_hits(shards),
+            Shards::TwoQ(shards) => Shard::count_hits(shards),
+        };
         println!(
             "buffer pool hit rate : {}",
-            self.buffer_pool_hits as f64 / (self.buffer_pool_accesses.max(1) as f64),
+            hits as f64 / (accesses.max(1) as f64),
         );
     }
 }
@@ -145,13 +204,13 @@ struct FileMap {
 }
 
 /// The data stored for each page in the buffer pool.
-struct BufferPoolEntry {
-    eviction_id: EvictionId,
+struct BufferPoolEntry<I> {
+    eviction_id: I,
     page: Arc<Aligned>,
 }
 
 impl FileSystem {
-    /// Creates and returns a new file system with an empty buffer pool.
+    /// Creates and returns a new file system with an empty buffer pool and random seeds.
     ///
     /// The buffer pool will have the given capacity,
     /// and writes to the file system will be buffered until the given number of pages have been accumulated.
@@ -163,27 +222,104 @@ impl FileSystem {
         write_buffering: usize,
         readahead_buffering: usize,
     ) -> Result<Self, DbError> {
-        let buffer_pool = HashTable::new(capacity)?;
-        let eviction_handler = Eviction::new(capacity)?;
-        let inner = InnerFs {
-            buffer_pool,
-            eviction_handler,
-            file_map: FileMap::new(),
-            buffer_pool_hits: 0,
-            buffer_pool_accesses: 0,
+        Self::with_seed(prefix, capacity, write_buffering, readahead_buffering, None)
+    }
+
+    /// Creates and returns a new file system like `new`, whose hash tables,
+    /// and the filters of the SSTs written to it, derive their seeds from the given seed.
+    /// If it is None, they are random.
+    ///
+    /// Returns an error if creation of the buffer pool or eviction handler fails.
+    pub fn with_seed(
+        prefix: impl AsRef<Path>,
+        capacity: usize,
+        write_buffering: usize,
+        readahead_buffering: usize,
+        seed: Option<u64>,
+    ) -> Result<Self, DbError> {
+        Self::sharded(
+            prefix,
+            capacity,
+            1,
+            EvictionType::default(),
+            write_buffering,
+            readahead_buffering,
+            seed,
+        )
+    }
+
+    /// Creates and returns a new file system like `with_seed`,
+    /// whose buffer pool is split into the given number of shards that are locked separately,
+    /// and evicts pages with the given policy instead of 2Q.
+    /// The capacity is divided evenly between the shards, and each shard evicts its own pages.
+    ///
+    /// Panics if the number of shards is zero, or if the parameters of the policy are out of range.
+    ///
+    /// Returns an error if creation of the buffer pool or eviction handler fails.
+    pub fn sharded(
+        prefix: impl AsRef<Path>,
+        capacity: usize,
+        num_shards: usize,
+        eviction: EvictionType,
+        write_buffering: usize,
+        readahead_buffering: usize,
+        seed: Option<u64>,
+    ) -> Result<Self, DbError> {
+        assert!(num_shards > 0, "buffer pool needs at least one shard");
+        assert!(eviction.is_valid(), "invalid eviction policy: {eviction:?}");
+
+        let seeds = SeedSource::new(seed);
+        let shards = match eviction {
+            EvictionType::Lru => Shards::Lru(Shard::split(
+                capacity,
+                num_shards,
+                &seeds,
+                |capacity, _| Lru::new(capacity),
+            )?),
+            EvictionType::Clock => Shards::Clock(Shard::split(
+                capacity,
+                num_shards,
+                &seeds,
+                |capacity, _| Clock::new(capacity),
+            )?),
+            EvictionType::LruK { k } => Shards::LruK(Shard::split(
+                capacity,
+                num_shards,
+                &seeds,
+                |capacity, _| LruK::new(capacity, k),
+            )?),
+            EvictionType::Arc => Shards::Arc(Shard::split(
+                capacity,
+                num_shards,
+                &seeds,
+                AdaptiveReplacement::new,
+            )?),
+            EvictionType::TwoQ { k_in, k_out } => Shards::TwoQ(Shard::split(
+                capacity,
+                num_shards,
+                &seeds,
+                |capacity, seed| TwoQ::new(capacity, k_in, k_out, seed),
+            )?),
         };
 
         Ok(Self {
-            inner: Mutex::new(inner),
+            file_map: RwLock::new(FileMap::new()),
+            unchecksummed_files: Mutex::new(Vec::new()),
+            shards,
             prefix: prefix.as_ref().to_path_buf(),
-            capacity,
             write_buffering,
             readahead_buffering,
+            seeds,
         })
     }
 
+    /// Returns the next seed for a filter of an SST written to this file system.
+    pub fn next_seed(&self) -> u64 {
+        self.seeds.next()
+    }
+
     /// Translates a file ID to the corresponding path for this file system.
-    fn path(&self, file_id: FileId) -> PathBuf {
+    pub fn path(&self, file_id: FileId) -> PathBuf {
         self.prefix.join(file_id.name())
     }
 
@@ -229,13 +365,36 @@ impl FileSystem {
     /// Buffers the given range of pages for the file with the given ID,
     /// if the first page is not found in the buffer pool.
     ///
+    /// The checksum of every page read from disk is verified.
+    /// Readahead pages that fail verification are left out of the buffer pool,
+    /// so the error is only reported if they are actually requested.
+    ///
     /// Panics if the given range is empty.
     ///
+    /// Returns `DbError::Corruption` if the checksum of the first page doesn't match its contents,
+    /// unless the file has been marked with `FileSystem::mark_unchecksummed`.
+    ///
     /// Returns a reference to the bytes of the first page, or an error.
     fn get_range(
         &self,
         file_id: FileId,
         page_range: Range<usize>,
+    ) -> Result<Arc<Aligned>, DbError> {
+        match &self.shards {
+            Shards::Lru(shards) => self.get_range_in(shards, file_id, page_range),
+            Shards::Clock(shards) => self.get_range_in(shards, file_id, page_range),
+            Shards::LruK(shards) => self.get_range_in(shards, file_id, page_range),
+            Shards::Arc(shards) => self.get_range_in(shards, file_id, page_range),
+            Shards::TwoQ(shards) => self.get_range_in(shards, file_id, page_range),
+        }
+    }
+
+    /// Does the work of `FileSystem::get_range` for the given shards of the buffer pool.
+    fn get_range_in<E: EvictionPolicy>(
+        &self,
+        shards: &[Mutex<Shard<E>>],
+        file_id: FileId,
+        page_range: Range<usize>,
     ) -> Result<Arc<Aligned>, DbError> {
         assert!(
             !page_range.is_empty(),
@@ -245,17 +404,19 @@ impl FileSystem {
         let page_start = page_range.start;
         let num_pages_to_read = page_range.len();
 
-        // Hold lock to check if page is in buffer pool
+        let buffer_file_id = self.buffer_file_id(file_id);
+
+        // Hold the lock of the page's shard to check if page is in buffer pool
         {
-            let mut inner_lock = self.inner.lock().unwrap(); // If lock is poisoned, this is unrecoverable
-            let inner = inner_lock.deref_mut();
+            let buffer_page_id = buffer_file_id.page(page_start);
 
-            let buffer_page_id = inner.file_map.get_or_assign_file(file_id).page(page_start);
+            let mut shard_lock = Shard::of(shards, buffer_page_id).lock().unwrap();
+            let shard = shard_lock.deref_mut();
 
-            inner.buffer_pool_accesses += 1;
-            if let Some(entry) = inner.buffer_pool.get(buffer_page_id) {
-                inner.buffer_pool_hits += 1;
-                inner.eviction_handler.touch(entry.eviction_id);
+            shard.accesses += 1;
+            if let Some(entry) = shard.buffer_pool.get(buffer_page_id) {
+                shard.hits += 1;
+                shard.eviction_handler.touch(entry.eviction_id);
                 return Ok(Arc::clone(&entry.page));
             }
         }
@@ -279,47 +440,119 @@ impl FileSystem {
                 DbError::IoError(format!("failed exact read {file_id:?} {page_range:?}: {e}"))
             })?;
 
-        // Obtain lock again to put page in buffer pool
-        {
-            let mut inner_lock = self.inner.lock().unwrap();
-            let inner = inner_lock.deref_mut();
-
-            let buffer_file_id = inner.file_map.get_file(file_id).unwrap();
-
-            // Add readahead pages to buffer, but don't mark them as touched in the eviction handler
-            // if they happen to already be there since the application hasn't logically touched them yet
-            for i in (1..num_pages_to_read).rev() {
-                let buffer_page_id = buffer_file_id.page(page_start + i);
-                if inner.buffer_pool.get(buffer_page_id).is_none() {
-                    if inner.buffer_pool.len() == self.capacity {
-                        inner.evict_page()?;
-                    }
-
-                    let mut page = Aligned::new();
-                    page.inner_mut().unwrap().copy_from_slice(&buffer[i].0);
-
-                    inner.add_new_page(Arc::clone(&page), buffer_page_id);
-                }
-            }
+        let unchecksummed = self.is_unchecksummed(file_id);
+        if !buffer[0].verify_checksum(page_start) && !unchecksummed {
+            return Err(DbError::Corruption {
+                file: file_id.name(),
+                page: page_start,
+            });
+        }
 
-            // Add the requested page to buffer pool and mark it as touched if it happens to be there already
-            // (another thread could have inserted this page while we weren't holding the lock)
-            let buffer_page_id = buffer_file_id.page(page_start);
-            if let Some(page_entry) = inner.buffer_pool.get(buffer_page_id) {
-                inner.eviction_handler.touch(page_entry.eviction_id);
-                Ok(Arc::clone(&page_entry.page))
-            } else {
-                if inner.buffer_pool.len() == self.capacity {
-                    inner.evict_page()?;
+        // Obtain the shard locks again to put the pages in the buffer pool
+        // Add readahead pages to buffer, but don't mark them as touched in the eviction handler
+        // if they happen to already be there since the application hasn't logically touched them yet
+        for i in (1..num_pages_to_read).rev() {
+            let buffer_page_id = buffer_file_id.page(page_start + i);
+            let mut shard_lock = Shard::of(shards, buffer_page_id).lock().unwrap();
+            let shard = shard_lock.deref_mut();
+            if shard.buffer_pool.get(buffer_page_id).is_none()
+                && (buffer[i].verify_checksum(page_start + i) || unchecksummed)
+            {
+                if shard.buffer_pool.len() == shard.capacity {
+                    shard.evict_page()?;
                 }
 
                 let mut page = Aligned::new();
-                page.inner_mut().unwrap().copy_from_slice(&buffer[0].0);
+                page.inner_mut().unwrap().copy_from_slice(&buffer[i].0);
 
-                inner.add_new_page(Arc::clone(&page), buffer_page_id);
-                Ok(page)
+                shard.add_new_page(Arc::clone(&page), buffer_page_id);
             }
         }
+
+        // Add the requested page to buffer pool and mark it as touched if it happens to be there already
+        // (another thread could have inserted this page while we weren't holding the lock)
+        let buffer_page_id = buffer_file_id.page(page_start);
+        let mut shard_lock = Shard::of(shards, buffer_page_id).lock().unwrap();
+        let shard = shard_lock.deref_mut();
+        if let Some(page_entry) = shard.buffer_pool.get(buffer_page_id) {
+            shard.eviction_handler.touch(page_entry.eviction_id);
+            Ok(Arc::clone(&page_entry.page))
+        } else {
+            if shard.buffer_pool.len() == shard.capacity {
+                shard.evict_page()?;
+            }
+
+            let mut page = Aligned::new();
+            page.inner_mut().unwrap().copy_from_slice(&buffer[0].0);
+
+            shard.add_new_page(Arc::clone(&page), buffer_page_id);
+            Ok(page)
+        }
+    }
+
+    /// Returns the buffer ID for the file with the given ID, assigning a new one if there isn't one.
+    ///
+    /// Only takes the write lock of the file map if a new buffer ID is needed,
+    /// which happens on the first read of a file after it is written.
+    fn buffer_file_id(&self, file_id: FileId) -> BufferFileId {
+        // If lock is poisoned, this is unrecoverable
+        if let Some(buffer_file_id) = self.file_map.read().unwrap().get_file(file_id) {
+            return buffer_file_id;
+        }
+        self.file_map.write().unwrap().get_or_assign_file(file_id)
+    }
+
+    /// Invalidates everything known about the file with the given ID,
+    /// for when it is about to be modified or deleted.
+    fn forget_file(&self, file_id: FileId) {
+        self.file_map.write().unwrap().unassign_file(file_id);
+        self.unchecksummed_files
+            .lock()
+            .unwrap()
+            .retain(|&id| id != file_id);
+    }
+
+    /// Marks the file with the given ID as having been written before pages had checksums,
+    /// so that reads of its pages aren't verified.
+    ///
+    /// The mark follows the file when it is renamed, and is removed when the file is written to or deleted.
+    pub fn mark_unchecksummed(&self, file_id: FileId) {
+        let unchecksummed_files = &mut self.unchecksummed_files.lock().unwrap();
+        if !unchecksummed_files.contains(&file_id) {
+            unchecksummed_files.push(file_id);
+        }
+    }
+
+    /// Returns whether the file with the given ID has been marked with `FileSystem::mark_unchecksummed`.
+    pub fn is_unchecksummed(&self, file_id: FileId) -> bool {
+        self.unchecksummed_files.lock().unwrap().contains(&file_id)
+    }
+
+    /// Reads the given range of pages for the file with the given ID directly from disk,
+    /// bypassing the buffer pool entirely.
+    /// Checksums are not verified, so that callers can inspect every page.
+    ///
+    /// Returns `DbError::IoError` if the file can't be read or is shorter than the range.
+    pub fn read_unbuffered(
+        &self,
+        file_id: FileId,
+        page_range: Range<usize>,
+    ) -> Result<Vec<Aligned>, DbError> {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(self.path(file_id))?;
+
+        let mut buffer: Vec<Aligned> = bytemuck::allocation::zeroed_vec(page_range.len());
+        file.read_exact_at(
+            bytemuck::cast_slice_mut(&mut buffer),
+            (page_range.start * PAGE_SIZE) as u64,
+        )
+        .map_err(|e| {
+            DbError::IoError(format!("failed exact read {file_id:?} {page_range:?}: {e}"))
+        })?;
+
+        Ok(buffer)
     }
 
     /// Writes pages to a file starting at an offset, indicated by the page ID.
@@ -328,7 +561,9 @@ impl FileSystem {
     /// and fills the bytes before the offset with `0x00` if the file isn't long enough.
     ///
     /// Repeatedly calls `next_page` with an out argument.
-    /// If `next_page` returns true, the modified out argument is written to the file.
+    /// If `next_page` returns true, the modified out argument is written to the file,
+    /// with its last `CHECKSUM_SIZE` bytes overwritten by a checksum of the rest of the page.
+    /// So only the first `PAGE_DATA_SIZE` bytes of each page can hold data.
     /// When `next_page` returns false, stops writing.
     /// Writes are buffered for efficiency.
     ///
@@ -352,7 +587,7 @@ impl FileSystem {
             .custom_flags(libc::O_DIRECT | libc::O_SYNC)
             .open(&path)?;
 
-        self.inner.lock().unwrap().file_map.unassign_file(file_id);
+        self.forget_file(file_id);
 
         let mut buffer: Vec<Aligned> = bytemuck::allocation::zeroed_vec(self.write_buffering);
         let mut page_number_unwritten = starting_page_number;
@@ -362,6 +597,7 @@ impl FileSystem {
             for page in &mut buffer {
                 page.clear();
                 if next_page(page)? {
+                    page.stamp_checksum(page_number_unwritten);
                     page_number_unwritten += 1;
                 } else {
                     end = true;
@@ -395,7 +631,7 @@ impl FileSystem {
             panic!("Cannot delete non-existent file: {file_id:?}");
         }
 
-        self.inner.lock().unwrap().file_map.unassign_file(file_id);
+        self.forget_file(file_id);
 
         fs::remove_file(path)?;
 
@@ -421,35 +657,83 @@ impl FileSystem {
 
         fs::rename(old_path, new_path)?;
 
-        let file_map = &mut self.inner.lock().unwrap().file_map;
+        let mut file_map = self.file_map.write().unwrap();
         file_map.unassign_file(old_file_id);
         file_map.unassign_file(new_file_id);
+        let mut unchecksummed_files = self.unchecksummed_files.lock().unwrap();
+        if unchecksummed_files.contains(&old_file_id) {
+            unchecksummed_files.retain(|&id| id != old_file_id);
+            unchecksummed_files.push(new_file_id);
+        }
 
         Ok(())
     }
 }
 
-impl InnerFs {
-    /// Makes space in the buffer pool by evicting a page.
+impl<E: EvictionPolicy> Shard<E> {
+    /// Splits the given capacity evenly into the given number of shards,
+    /// each evicting with the policy that `new_policy` creates from its capacity and a seed.
+    ///
+    /// Returns an error if creation of a buffer pool or eviction handler fails.
+    fn split(
+        capacity: usize,
+        num_shards: usize,
+        seeds: &SeedSource,
+        new_policy: impl Fn(usize, u32) -> Result<E, DbError>,
+    ) -> Result<Vec<Mutex<Self>>, DbError> {
+        (0..num_shards)
+            .map(|i| {
+                let capacity = capacity / num_shards + usize::from(i < capacity % num_shards);
+                let buffer_pool = HashTable::new(capacity, seeds.next() as u32)?;
+                let eviction_handler = new_policy(capacity, seeds.next() as u32)?;
+                Ok(Mutex::new(Self {
+                    buffer_pool,
+                    eviction_handler,
+                    capacity,
+                    hits: 0,
+                    accesses: 0,
+                }))
+            })
+            .collect()
+    }
+
+    /// Returns the shard of the given shards that holds the page with the given ID.
+    fn of(shards: &[Mutex<Self>], page_id: BufferPageId) -> &Mutex<Self> {
+        let BufferPageId {
+            file_id: BufferFileId(file_id),
+            page_number,
+        } = page_id;
+        let hash = mix64(page_number as u64, file_id as u64);
+        &shards[(hash % shards.len() as u64) as usize]
+    }
+
+    /// Returns the number of buffer pool hits and accesses across the given shards.
+    fn count_hits(shards: &[Mutex<Self>]) -> (usize, usize) {
+        shards.iter().fold((0, 0), |(hits, accesses), shard| {
+            let shard = shard.lock().unwrap();
+            (hits + shard.hits, accesses + shard.accesses)
+        })
+    }
+
+    /// Makes space in the shard by evicting one of its pages.
     ///
     /// Will only evict pages that are not referenced by another `Arc` elsewhere.
     ///
     /// Returns `DbError::Oom` if no page can be evicted
     /// due to every page in the buffer pool being referenced by another `Arc`.
     pub fn evict_page(&mut self) -> Result<(), DbError> {
-        let chooser = self.eviction_handler.choose_victim();
-        for (victim, page_id) in chooser {
-            let page = &self.buffer_pool.get(page_id).unwrap().page;
-            if Arc::strong_count(page) == 1 {
-                self.buffer_pool.remove(page_id);
-                self.eviction_handler.evict(victim);
-                return Ok(());
-            }
-        }
-        Err(DbError::Oom)
+        let victim = self.eviction_handler.choose_victim().find(|&(_, page_id)| {
+            Arc::strong_count(&self.buffer_pool.get(page_id).unwrap().page) == 1
+        });
+        let Some((victim, page_id)) = victim else {
+            return Err(DbError::Oom);
+        };
+        self.buffer_pool.remove(page_id);
+        self.eviction_handler.evict(victim);
+        Ok(())
     }
 
-    /// Adds the given page to the buffer pool with the given page ID key,
+    /// Adds the given page to the shard with the given page ID key,
     /// updating the eviction handler appropriately.
     pub fn add_new_page(&mut self, page: Arc<Aligned>, page_id: BufferPageId) {
         let eviction_id = self.eviction_handler.insert_new(page_id);
@@ -506,9 +790,13 @@ impl FileMap {
     }
 
     /// Returns the buffer ID for the given regular ID, if one has been assigned.
-    fn get_file(&mut self, file_id: FileId) -> Option<BufferFileId> {
-        let (id, _) = self.access(file_id);
-        id.map(|id| BufferFileId(id.get()))
+    fn get_file(&self, file_id: FileId) -> Option<BufferFileId> {
+        let FileId {
+            lsm_level,
+            sst_number,
+        } = file_id;
+        let id = (*self.map.get(lsm_level)?.get(sst_number)?)?;
+        Some(BufferFileId(id.get()))
     }
 
     /// Removes any assigned buffer ID for the given regular ID.
@@ -553,7 +841,7 @@ mod tests {
         let bytes = s.bytes();
         for (page_number, a) in (starting_page_number..).zip(bytes) {
             let page = fs.get(file_id.page(page_number))?;
-            for &b in &page.0 {
+            for &b in &page.0[..PAGE_DATA_SIZE] {
                 assert_eq!(a, b);
             }
         }
@@ -573,7 +861,7 @@ mod tests {
         let bytes = s.bytes();
         for (page_number, a) in (starting_page_number..).zip(bytes) {
             let page = fs.get_sequential(file_id.page(page_number), file_size)?;
-            for &b in &page.0 {
+            for &b in &page.0[..PAGE_DATA_SIZE] {
                 assert_eq!(a, b);
             }
         }
@@ -584,6 +872,16 @@ mod tests {
         assert!(fs.get(page_id).is_err());
     }
 
+    fn assert_corrupt(fs: &FileSystem, page_id: PageId) {
+        assert_eq!(
+            fs.get(page_id).err(),
+            Some(DbError::Corruption {
+                file: page_id.file_id.name(),
+                page: page_id.page_number,
+            })
+        );
+    }
+
     #[test]
     fn test_basic() -> Result<()> {
         let path = &test_path("basic")?;
@@ -605,8 +903,11 @@ mod tests {
         write_string(fs, file_id_a.page("a monad ".len()), "is a monoid")?;
         write_string(fs, file_id_b.page(0), "in the ????????")?;
 
+        // The skipped pages were never written, so they have no checksum
         for _ in 0..3 {
-            assert_page_contents(fs, file_id_a.page(0), "\0\0\0\0\0\0\0\0")?;
+            for page_number in 0.."a monad ".len() {
+                assert_corrupt(fs, file_id_a.page(page_number));
+            }
         }
         for _ in 0..3 {
             assert_page_contents(fs, file_id_b.page("in the ".len()), "????????")?;
@@ -656,4 +957,194 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_checksums() -> Result<()> {
+        let path = &test_path("checksums")?;
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+
+        {
+            let fs = &FileSystem::new(path, 16, 8, 4)?;
+            write_string(fs, file_id.page(0), "checksum")?;
+        }
+
+        // Flip a bit in page 2 and swap the contents of pages 5 and 6
+        let file_path = path.as_ref().join(file_id.name());
+        let mut bytes = fs::read(&file_path)?;
+        bytes[2 * PAGE_SIZE + 100] ^= 0b100;
+        let (before, after) = bytes.split_at_mut(6 * PAGE_SIZE);
+        before[5 * PAGE_SIZE..].swap_with_slice(&mut after[..PAGE_SIZE]);
+        fs::write(&file_path, bytes)?;
+
+        let fs = &FileSystem::new(path, 16, 8, 4)?;
+
+        // Corrupt pages read ahead of an intact page are only reported when requested
+        assert_page_contents_sequential(fs, file_id.page(0), "ch", 8)?;
+        assert_corrupt(fs, file_id.page(2));
+        assert_page_contents(fs, file_id.page(3), "ck")?;
+        assert_corrupt(fs, file_id.page(5));
+        assert_corrupt(fs, file_id.page(6));
+        assert_page_contents_sequential(fs, file_id.page(7), "m", 8)?;
+
+        Ok(())
+    }
+
+    /// Tests that a sharded buffer pool spreads pages over its shards, keeps each within its capacity,
+    /// serves repeated reads as hits, and evicts within each shard when a file doesn't fit.
+    #[test]
+    fn test_shards() -> Result<()> {
+        let path = &test_path("shards")?;
+        let fs = &FileSystem::sharded(path, 64, 4, EvictionType::default(), 8, 1, Some(0))?;
+
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+        let contents = "abcdefghijklmnopqrstuvwxyzABCDEF";
+        write_string(fs, file_id.page(0), contents)?;
+
+        assert_page_contents(fs, file_id.page(0), contents)?;
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| assert_page_contents(fs, file_id.page(0), contents).unwrap());
+            }
+        });
+
+        let Shards::TwoQ(shards) = &fs.shards else {
+            unreachable!("2Q is the default eviction policy");
+        };
+        let (mut hits, mut accesses) = (0, 0);
+        for shard in shards {
+            let shard = shard.lock().unwrap();
+            assert_eq!(shard.capacity, 16);
+            assert!((1..=16).contains(&shard.buffer_pool.len()));
+            hits += shard.hits;
+            accesses += shard.accesses;
+        }
+        assert_eq!(accesses, 5 * contents.len());
+        assert_eq!(hits, 4 * contents.len());
+
+        let long_contents = contents.repeat(4);
+        write_string(fs, file_id.page(0), &long_contents)?;
+        assert_page_contents(fs, file_id.page(0), &long_contents)?;
+        for shard in shards {
+            assert!(shard.lock().unwrap().buffer_pool.len() <= 16);
+        }
+
+        Ok(())
+    }
+
+    /// Tests that buffer pool hits from many threads only take the lock of their page's shard:
+    /// with one shard and the file system's own mutex held locked,
+    /// reads of pages in the other shards still complete,
+    /// and every read from every thread after the first pass is a hit.
+    #[test]
+    fn test_concurrent_hits() -> Result<()> {
+        let path = &test_path("concurrent_hits")?;
+        let fs = &FileSystem::sharded(path, 64, 4, EvictionType::default(), 8, 1, Some(0))?;
+        let Shards::TwoQ(shards) = &fs.shards else {
+            unreachable!("2Q is the default eviction policy");
+        };
+
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+        let contents = "abcdefghijklmnopqrstuvwxyzABCDEF";
+        write_string(fs, file_id.page(0), contents)?;
+        assert_page_contents(fs, file_id.page(0), contents)?;
+
+        let buffer_file_id = fs.buffer_file_id(file_id);
+        let locked_shard = Shard::of(shards, buffer_file_id.page(0));
+        let unlocked_pages: Vec<_> = (0..contents.len())
+            .filter(|&i| !std::ptr::eq(Shard::of(shards, buffer_file_id.page(i)), locked_shard))
+            .collect();
+        assert!(!unlocked_pages.is_empty());
+
+        std::thread::scope(|scope| {
+            let guard = locked_shard.lock().unwrap();
+            let unchecksummed_guard = fs.unchecksummed_files.lock().unwrap();
+            let (sender, receiver) = std::sync::mpsc::channel();
+            for _ in 0..8 {
+                let sender = sender.clone();
+                let unlocked_pages = &unlocked_pages;
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        for &i in unlocked_pages {
+                            let page = fs.get(file_id.page(i)).unwrap();
+                            assert_eq!(page.0[0], contents.as_bytes()[i]);
+                        }
+                    }
+                    sender.send(()).unwrap();
+                });
+            }
+            let finished = (0..8).all(|_| {
+                receiver
+                    .recv_timeout(std::time::Duration::from_secs(30))
+                    .is_ok()
+            });
+            drop(guard);
+            drop(unchecksummed_guard);
+            assert!(finished, "hits waited on a lock other than their shard's");
+        });
+
+        let (hits, accesses) = Shard::count_hits(shards);
+        assert_eq!(accesses, contents.len() + 8 * 100 * unlocked_pages.len());
+        assert_eq!(hits, accesses - contents.len());
+
+        Ok(())
+    }
+
+    /// Tests that every eviction policy keeps reads correct when the buffer pool is full,
+    /// skips pages that are still referenced, and runs out of memory once every page is.
+    #[test]
+    fn test_eviction_policies() -> Result<()> {
+        let path = &test_path("eviction_policies")?;
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+        let contents = "the quick brown fox jumps over the lazy dog".repeat(2);
+
+        for eviction in [
+            EvictionType::Lru,
+            EvictionType::Clock,
+            EvictionType::LruK { k: 2 },
+            EvictionType::Arc,
+            EvictionType::default(),
+        ] {
+            let fs = &FileSystem::sharded(path, 16, 1, eviction, 8, 2, Some(0))?;
+            write_string(fs, file_id.page(0), &contents)?;
+
+            let pinned: Vec<_> = (0..4)
+                .map(|page_number| fs.get(file_id.page(page_number)))
+                .collect::<Result<_, _>>()?;
+            for _ in 0..2 {
+                assert_page_contents_sequential(fs, file_id.page(0), &contents, contents.len())?;
+                assert_page_contents(fs, file_id.page(10), &contents[10..20])?;
+            }
+            for (page_number, page) in pinned.iter().enumerate() {
+                assert!(std::ptr::eq(
+                    page.as_ref(),
+                    fs.get(file_id.page(page_number))?.as_ref()
+                ));
+            }
+
+            let pinned: Vec<_> = (0..16)
+                .map(|page_number| fs.get(file_id.page(page_number)))
+                .collect::<Result<_, _>>()?;
+            assert_eq!(
+                fs.get(file_id.page(16)).err(),
+                Some(DbError::Oom),
+                "{eviction:?}"
+            );
+            drop(pinned);
+            assert_page_contents(fs, file_id.page(16), &contents[16..17])?;
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,399 @@
+use std::{
+    fmt::Debug,
+    ops::AddAssign,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DbError,
+    bloom_filter::{self, BloomFilter},
+    hash::{HashFunction, HashType},
+    partitioned_filter::{self, PartitionedFilter},
+    xor_filter::{self, XorFilter},
+};
+
+/// A filter over the keys of an SST, which answers whether a key might be in it.
+pub trait Filter: Debug + Send + Sync {
+    /// Returns false if the key is definitely not in the filter.
+    /// May return true for keys that aren't in it (false positives), but never false for keys that are.
+    fn query(&self, key: u64) -> bool;
+
+    /// Turns the filter into the bytes stored in the SST, which `from_bytes` reads back.
+    fn turn_to_bytes(&self) -> Vec<u8>;
+
+    /// The number recorded as `num_hashes` in SST metadata, which `from_bytes` needs to read the filter back.
+    fn num_hashes(&self) -> usize;
+
+    /// The expected rate of false positives, given the number of keys that were inserted.
+    fn false_positive_rate(&self, n_entries: usize) -> f64;
+}
+
+/// Counts of how the filter of an SST answered point lookups, kept in memory while the SST is open.
+#[derive(Debug, Default)]
+pub struct FilterStats {
+    probes: AtomicU64,
+    positives: AtomicU64,
+    false_positives: AtomicU64,
+}
+
+impl FilterStats {
+    /// Records a lookup that probed the filter,
+    /// with whether the filter answered that the key might be there and whether it was.
+    pub fn record(&self, positive: bool, found: bool) {
+        self.probes.fetch_add(1, Ordering::Relaxed);
+        if positive {
+            self.positives.fetch_add(1, Ordering::Relaxed);
+            if !found {
+                self.false_positives.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The counts recorded so far.
+    pub fn counts(&self) -> FilterCounts {
+        FilterCounts {
+            probes: self.probes.load(Ordering::Relaxed),
+            positives: self.positives.load(Ordering::Relaxed),
+            false_positives: self.false_positives.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// How many lookups probed a filter, and how they were answered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FilterCounts {
+    /// Lookups that probed the filter
+    pub probes: u64,
+    /// Lookups that the filter answered might be in the SST
+    pub positives: u64,
+    /// Positive lookups for keys that weren't in the SST after all
+    pub false_positives: u64,
+}
+
+impl FilterCounts {
+    /// The number of lookups for keys that weren't in the SST,
+    /// which are the only ones that a filter saves reads for.
+    pub fn negatives(&self) -> u64 {
+        let hits = self.positives.saturating_sub(self.false_positives);
+        self.probes.saturating_sub(hits)
+    }
+
+    /// The observed rate of false positives among lookups for keys that weren't in the SST,
+    /// or None if there were no such lookups.
+    pub fn false_positive_rate(&self) -> Option<f64> {
+        let negatives = self.negatives();
+        (negatives > 0).then(|| self.false_positives as f64 / negatives as f64)
+    }
+}
+
+impl AddAssign for FilterCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.probes += other.probes;
+        self.positives += other.positives;
+        self.false_positives += other.false_positives;
+    }
+}
+
+/// The type of the filter of an SST.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FilterType {
+    /// A bloom filter in which every probe has its own hash function and can land anywhere in the bitmap.
+    #[default]
+    Bloom,
+    /// A bloom filter in which all the probes for a key land in one 64-byte block,
+    /// so a query touches a single cache line.
+    /// The probes are derived from one 64-bit hash with double hashing.
+    /// Gives slightly more false positives than `Bloom` for the same number of bits.
+    Blocked,
+    /// A static xor filter, built from all the keys of the SST at once.
+    /// Gives fewer false positives than `Bloom` for the same number of bits,
+    /// but needs to hold every key in memory while the SST is written.
+    Xor,
+    /// A bloom filter partitioned by key range into one page per partition,
+    /// which stays on the SST's filter pages and is read through the buffer pool,
+    /// so that only a small index of the partitions is always in memory.
+    /// A query reads the one page of the partition that covers the key.
+    Partitioned,
+}
+
+impl FilterType {
+    /// The number identifying the filter type in SST metadata.
+    pub fn tag(self) -> u64 {
+        match self {
+            FilterType::Bloom => 0,
+            FilterType::Blocked => 1,
+            FilterType::Xor => 2,
+            FilterType::Partitioned => 3,
+        }
+    }
+
+    /// Returns the filter type with the given tag, or None if there is no such filter type.
+    pub fn from_tag(tag: u64) -> Option<Self> {
+        match tag {
+            0 => Some(FilterType::Bloom),
+            1 => Some(FilterType::Blocked),
+            2 => Some(FilterType::Xor),
+            3 => Some(FilterType::Partitioned),
+            _ => None,
+        }
+    }
+
+    /// The hash algorithm of filters of this type, unless another one is configured,
+    /// and of every filter of this type from before the hash algorithm was recorded.
+    /// Standard bloom filters use the 32-bit MurmurHash3, and the others `mix64`.
+    pub fn default_hash(self) -> HashType {
+        match self {
+            FilterType::Bloom => HashType::Murmur,
+            FilterType::Blocked | FilterType::Xor | FilterType::Partitioned => HashType::U64Mixer,
+        }
+    }
+
+    /// The number of bytes that come before the bitmap or fingerprints,
+    /// for a filter of this type with the given number of hashes.
+    /// For a partitioned filter, this is the smallest index, which takes up a whole page.
+    pub fn header_size(self, num_hashes: u64) -> u64 {
+        let seed_size = size_of::<HashFunction>() as u64;
+        match self {
+            FilterType::Bloom => num_hashes.saturating_mul(seed_size),
+            FilterType::Blocked => seed_size,
+            FilterType::Xor => xor_filter::HEADER_SIZE as u64,
+            FilterType::Partitioned => partitioned_filter::PARTITION_SIZE as u64,
+        }
+    }
+
+    /// Returns whether a filter of this type with the given number of hashes can take up the given number of bytes:
+    /// the header has to be followed by a bitmap, made of whole blocks for a blocked filter,
+    /// by whole words of fingerprints for an xor filter,
+    /// or by whole pages of partitions for a partitioned filter.
+    pub fn is_valid_size(self, num_hashes: u64, size: u64) -> bool {
+        let header_size = self.header_size(num_hashes);
+        match self {
+            FilterType::Bloom => header_size < size || (header_size == size && num_hashes == 0),
+            FilterType::Blocked => {
+                size > header_size
+                    && (size - header_size).is_multiple_of(bloom_filter::BLOCK_SIZE as u64)
+            }
+            FilterType::Xor => {
+                num_hashes <= xor_filter::MAX_FINGERPRINT_BITS as u64
+                    && size >= header_size
+                    && (size - header_size).is_multiple_of(8)
+            }
+            FilterType::Partitioned => {
+                size > header_size && size.is_multiple_of(partitioned_filter::PARTITION_SIZE as u64)
+            }
+        }
+    }
+}
+
+/// Builds the filter of an SST from its keys, in order, as they're written.
+///
+/// Filters are sized by the number of keys actually written,
+/// which merges that drop overwritten entries and tombstones can't know in advance.
+/// Xor and partitioned filters are built from all the keys at once, so those collect the keys as they're written.
+/// Bloom and blocked filters only need the final number of keys to be sized,
+/// so they don't hold on to keys, and `build` adds them one at a time, read back from the SST's leaves.
+pub struct FilterBuilder {
+    filter_type: FilterType,
+    hash: HashType,
+    bits_per_entry: usize,
+    seed: u64,
+    /// The keys written so far, for filter types that are built from all of them at once.
+    keys: Vec<u64>,
+}
+
+impl FilterBuilder {
+    /// Starts building a filter of the given type and hash algorithm with the given number of bits per entry,
+    /// whose seeds derive from the given seed.
+    pub fn new(filter_type: FilterType, hash: HashType, bits_per_entry: usize, seed: u64) -> Self {
+        Self {
+            filter_type,
+            hash,
+            bits_per_entry,
+            seed,
+            keys: Vec::new(),
+        }
+    }
+
+    /// Returns whether the filter is built from all of its keys at once, and so keeps the keys given to `insert`.
+    pub fn collects_keys(&self) -> bool {
+        matches!(self.filter_type, FilterType::Xor | FilterType::Partitioned)
+    }
+
+    /// Adds a key to the filter, if it collects keys. Keys must be distinct and added in increasing order.
+    pub fn insert(&mut self, key: u64) {
+        if self.collects_keys() {
+            self.keys.push(key);
+        }
+    }
+
+    /// Finishes building the filter, for the given number of keys.
+    ///
+    /// Filters that don't collect keys are sized by that number, and then take their keys from `keys`,
+    /// which must yield the same keys that were written. It isn't used by filters that collect keys.
+    ///
+    /// Returns the first error that `keys` yields.
+    pub fn build(
+        self,
+        n_entries: usize,
+        keys: impl Iterator<Item = Result<u64, DbError>>,
+    ) -> Result<Box<dyn Filter>, DbError> {
+        let blocked = match self.filter_type {
+            FilterType::Bloom => false,
+            FilterType::Blocked => true,
+            FilterType::Xor => {
+                return Ok(Box::new(XorFilter::build(
+                    &self.keys,
+                    self.bits_per_entry,
+                    self.hash,
+                    self.seed,
+                )));
+            }
+            FilterType::Partitioned => {
+                return Ok(Box::new(PartitionedFilter::build(
+                    &self.keys,
+                    self.bits_per_entry,
+                    self.hash,
+                    self.seed,
+                )));
+            }
+        };
+        // At least one entry, so that the bitmap isn't empty
+        let mut filter = BloomFilter::empty(
+            n_entries.max(1),
+            self.bits_per_entry,
+            blocked,
+            self.hash,
+            self.seed,
+        );
+        for key in keys {
+            filter.insert(key?);
+        }
+        Ok(Box::new(filter))
+    }
+}
+
+/// Reads a filter of the given type and hash algorithm back from the bytes that `Filter::turn_to_bytes` returned,
+/// given the number that `Filter::num_hashes` returned.
+///
+/// The size of the bytes must have been checked with `FilterType::is_valid_size`.
+pub fn from_bytes(
+    filter_bytes: &[u8],
+    num_hashes: usize,
+    filter_type: FilterType,
+    hash: HashType,
+) -> Box<dyn Filter> {
+    match filter_type {
+        FilterType::Bloom => Box::new(BloomFilter::from_bytes(
+            filter_bytes,
+            num_hashes,
+            false,
+            hash,
+        )),
+        FilterType::Blocked => Box::new(BloomFilter::from_bytes(
+            filter_bytes,
+            num_hashes,
+            true,
+            hash,
+        )),
+        FilterType::Xor => Box::new(XorFilter::from_bytes(filter_bytes, num_hashes, hash)),
+        FilterType::Partitioned => Box::new(PartitionedFilter::from_bytes(
+            filter_bytes,
+            num_hashes,
+            hash,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTER_TYPES: [FilterType; 4] = [
+        FilterType::Bloom,
+        FilterType::Blocked,
+        FilterType::Xor,
+        FilterType::Partitioned,
+    ];
+
+    #[test]
+    fn test_tags() {
+        for filter_type in FILTER_TYPES {
+            assert_eq!(FilterType::from_tag(filter_type.tag()), Some(filter_type));
+        }
+        assert_eq!(FilterType::from_tag(4), None);
+    }
+
+    /// Tests that every type of filter, with every hash algorithm, has no false negatives,
+    /// survives a round trip through bytes, and has about the false positive rate it should for 10 bits per entry,
+    /// close to what it expects.
+    #[test]
+    fn test_filters() {
+        let n_entries = 10_000;
+        // About 0.8% for a bloom filter, 1.1% for a blocked one, 0.4% for an xor filter,
+        // and less for a partitioned one, which rounds up to whole partitions
+        let max_false_positives = [120, 150, 70, 120];
+        for (filter_type, max_false_positives) in FILTER_TYPES.into_iter().zip(max_false_positives)
+        {
+            for hash in (0..4).map(|tag| HashType::from_tag(tag).unwrap()) {
+                let mut builder = FilterBuilder::new(filter_type, hash, 10, 0);
+                for i in 0..n_entries {
+                    builder.insert(i * 2);
+                }
+                // Only filters built from every key at once hold on to them
+                let collects_keys =
+                    matches!(filter_type, FilterType::Xor | FilterType::Partitioned);
+                assert_eq!(builder.collects_keys(), collects_keys);
+                assert_eq!(
+                    builder.keys.len(),
+                    collects_keys as usize * n_entries as usize
+                );
+                let filter = builder
+                    .build(n_entries as usize, (0..n_entries).map(|i| Ok(i * 2)))
+                    .unwrap();
+
+                let bytes = filter.turn_to_bytes();
+                let num_hashes = filter.num_hashes();
+                assert!(filter_type.is_valid_size(num_hashes as u64, bytes.len() as u64));
+                let filter = from_bytes(&bytes, num_hashes, filter_type, hash);
+                assert_eq!(filter.turn_to_bytes(), bytes);
+
+                for i in 0..n_entries {
+                    assert!(filter.query(i * 2), "{filter_type:?} {hash:?}");
+                }
+                let false_positives = (0..n_entries).filter(|i| filter.query(i * 2 + 1)).count();
+                assert!(
+                    false_positives < max_false_positives,
+                    "{filter_type:?} {hash:?}: {false_positives}"
+                );
+                let expected = filter.false_positive_rate(n_entries as usize) * n_entries as f64;
+                assert!(
+                    (expected / 2.0..expected * 2.0).contains(&(false_positives as f64)),
+                    "{filter_type:?} {hash:?}: {false_positives} false positives, expected {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_stats() {
+        let stats = FilterStats::default();
+        stats.record(false, false);
+        stats.record(true, true);
+        stats.record(true, false);
+        stats.record(true, false);
+        let counts = stats.counts();
+        assert_eq!(
+            counts,
+            FilterCounts {
+                probes: 4,
+                positives: 3,
+                false_positives: 2,
+            }
+        );
+        assert_eq!(counts.negatives(), 3);
+        assert_eq!(counts.false_positive_rate(), Some(2.0 / 3.0));
+        assert_eq!(FilterCounts::default().false_positive_rate(), None);
+    }
+}
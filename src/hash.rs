@@ -1,4 +1,11 @@
-use std::{array, marker::PhantomData, num::Wrapping as W};
+use std::{
+    array,
+    marker::PhantomData,
+    num::Wrapping as W,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
 
 /// A hash function with a specific seed to influence the output.
 #[repr(transparent)]
@@ -10,34 +17,85 @@ pub struct HashFunction<H = MurmurHash> {
 
 /// An algorithm for hashing bytes.
 pub trait HashAlgorithm: bytemuck::Pod {
-    /// Hashes the given key to a `u32` with the given `u32` seed.
-    fn hash(key: &[u8], seed: u32) -> u32;
+    /// The number of bits in the hashes that it returns, which is 32 or 64.
+    const BITS: u32;
+
+    /// Hashes the given key with the given seed.
+    /// Only the lowest `BITS` bits of the hash can be set.
+    fn hash(key: &[u8], seed: u64) -> u64;
+
+    /// Hashes the given key to 64 bits with the given seed.
+    /// A 32-bit algorithm hashes the key twice, with the seed and with its complement.
+    fn hash64(key: &[u8], seed: u64) -> u64 {
+        if Self::BITS >= 64 {
+            Self::hash(key, seed)
+        } else {
+            (Self::hash(key, seed) << 32) | Self::hash(key, !seed)
+        }
+    }
 }
 
 impl<H: HashAlgorithm> HashFunction<H> {
-    /// Returns a new hash function with a random seed.
-    #[allow(clippy::new_without_default)]
-    pub fn new() -> Self {
+    /// Returns a hash function with the given seed.
+    pub fn with_seed(seed: u32) -> Self {
         Self {
-            seed: fastrand::u32(..),
+            seed,
             _hash: PhantomData,
         }
     }
 
     /// Hashes the given key to an index into a container with the given length.
+    /// With a 32-bit algorithm, only the first `2^32` indices can be reached.
     pub fn hash_to_index(&self, key: impl bytemuck::Pod, length: usize) -> usize {
-        H::hash(bytemuck::bytes_of(&key), self.seed) as usize % length
+        (H::hash(bytemuck::bytes_of(&key), self.seed as u64) % length as u64) as usize
+    }
+}
+
+/// Hands out the seeds of every hash table and filter of a database.
+///
+/// With a base seed, the `i`th seed handed out is `mix64(i, base seed)`,
+/// so a database that does the same operations in the same order gets the same hash tables and filters,
+/// bit for bit. Without one, every seed is random.
+#[derive(Debug)]
+pub struct SeedSource {
+    seed: Option<u64>,
+    counter: AtomicU64,
+}
+
+impl SeedSource {
+    /// Returns a seed source with the given base seed, or a random one if it's None.
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            seed,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the next seed.
+    pub fn next(&self) -> u64 {
+        match self.seed {
+            Some(seed) => mix64(self.counter.fetch_add(1, Ordering::Relaxed), seed),
+            None => fastrand::u64(..),
+        }
     }
 }
 
+/// Hashes a `u64` key to 64 bits with the given seed, using the finalizer of the 64-bit MurmurHash3.
+/// Different keys always get different hashes for the same seed.
+pub fn mix64(key: u64, seed: u64) -> u64 {
+    fmix64(key ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
+
 /// Implementation of the 32-bit MurmurHash3 hash function.
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct MurmurHash;
 
-impl HashAlgorithm for MurmurHash {
+impl MurmurHash {
+    /// Hashes the given key to a `u32` with the given `u32` seed.
+    ///
     /// https://en.wikipedia.org/wiki/MurmurHash
-    fn hash(key: &[u8], seed: u32) -> u32 {
+    pub fn hash32(key: &[u8], seed: u32) -> u32 {
         let len = key.len();
 
         let c1 = W(0xcc9e2d51);
@@ -86,6 +144,260 @@ impl HashAlgorithm for MurmurHash {
     }
 }
 
+impl HashAlgorithm for MurmurHash {
+    const BITS: u32 = 32;
+
+    /// Only the lowest 32 bits of the seed are used.
+    fn hash(key: &[u8], seed: u64) -> u64 {
+        Self::hash32(key, seed as u32) as u64
+    }
+}
+
+/// Implementation of the 64-bit variant of MurmurHash3: the first half of `MurmurHash3_x64_128`.
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MurmurHash64;
+
+impl HashAlgorithm for MurmurHash64 {
+    const BITS: u32 = 64;
+
+    /// https://github.com/aappleby/smhasher/blob/master/src/MurmurHash3.cpp
+    /// The reference implementation takes a 32-bit seed, which this matches for seeds below `2^32`.
+    fn hash(key: &[u8], seed: u64) -> u64 {
+        let c1 = W(0x87c3_7b91_1142_53d5);
+        let c2 = W(0x4cf5_ad43_2745_937f);
+
+        let mut h1 = W(seed);
+        let mut h2 = W(seed);
+
+        let (chunks, remainder) = key.as_chunks::<16>();
+        for chunk in chunks {
+            let (words, _) = chunk.as_chunks::<8>();
+            let mut k1 = W(u64::from_le_bytes(words[0]));
+            let mut k2 = W(u64::from_le_bytes(words[1]));
+
+            k1 *= c1;
+            k1 = W(k1.0.rotate_left(31));
+            k1 *= c2;
+            h1 ^= k1;
+
+            h1 = W(h1.0.rotate_left(27));
+            h1 += h2;
+            h1 = h1 * W(5) + W(0x52dc_e729);
+
+            k2 *= c2;
+            k2 = W(k2.0.rotate_left(33));
+            k2 *= c1;
+            h2 ^= k2;
+
+            h2 = W(h2.0.rotate_left(31));
+            h2 += h1;
+            h2 = h2 * W(5) + W(0x3849_5ab5);
+        }
+
+        let remainder: [u8; 16] = array::from_fn(|i| remainder.get(i).copied().unwrap_or(0));
+        let (words, _) = remainder.as_chunks::<8>();
+        {
+            let mut k2 = W(u64::from_le_bytes(words[1]));
+            k2 *= c2;
+            k2 = W(k2.0.rotate_left(33));
+            k2 *= c1;
+            h2 ^= k2;
+
+            let mut k1 = W(u64::from_le_bytes(words[0]));
+            k1 *= c1;
+            k1 = W(k1.0.rotate_left(31));
+            k1 *= c2;
+            h1 ^= k1;
+        }
+
+        h1 ^= key.len() as u64;
+        h2 ^= key.len() as u64;
+
+        h1 += h2;
+        h2 += h1;
+
+        h1 = W(fmix64(h1.0));
+        h2 = W(fmix64(h2.0));
+
+        (h1 + h2).0
+    }
+}
+
+/// The finalizer of the 64-bit MurmurHash3, which mixes every bit of the input into every bit of the output.
+/// It is a bijection, so different inputs always get different outputs.
+fn fmix64(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^= hash >> 33;
+    hash
+}
+
+/// Implementation of the xxHash64 hash function.
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XxHash64;
+
+impl XxHash64 {
+    const PRIME_1: W<u64> = W(0x9e37_79b1_85eb_ca87);
+    const PRIME_2: W<u64> = W(0xc2b2_ae3d_27d4_eb4f);
+    const PRIME_3: W<u64> = W(0x1656_67b1_9e37_79f9);
+    const PRIME_4: W<u64> = W(0x85eb_ca77_c2b2_ae63);
+    const PRIME_5: W<u64> = W(0x27d4_eb2f_1656_67c5);
+
+    /// Mixes 8 bytes of input into an accumulator.
+    fn round(accumulator: W<u64>, input: u64) -> W<u64> {
+        let accumulator = accumulator + W(input) * Self::PRIME_2;
+        W(accumulator.0.rotate_left(31)) * Self::PRIME_1
+    }
+
+    /// Merges one of the four accumulators of the long-input loop into the hash.
+    fn merge_accumulator(hash: W<u64>, accumulator: W<u64>) -> W<u64> {
+        (hash ^ Self::round(W(0), accumulator.0)) * Self::PRIME_1 + Self::PRIME_4
+    }
+}
+
+impl HashAlgorithm for XxHash64 {
+    const BITS: u32 = 64;
+
+    /// https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md
+    fn hash(key: &[u8], seed: u64) -> u64 {
+        let seed = W(seed);
+        let (stripes, mut remainder) = key.as_chunks::<32>();
+
+        let mut hash = if stripes.is_empty() {
+            seed + Self::PRIME_5
+        } else {
+            let mut accumulators = [
+                seed + Self::PRIME_1 + Self::PRIME_2,
+                seed + Self::PRIME_2,
+                seed,
+                seed - Self::PRIME_1,
+            ];
+            for stripe in stripes {
+                let (lanes, _) = stripe.as_chunks::<8>();
+                for (accumulator, &lane) in accumulators.iter_mut().zip(lanes) {
+                    *accumulator = Self::round(*accumulator, u64::from_le_bytes(lane));
+                }
+            }
+            let [a1, a2, a3, a4] = accumulators;
+            let hash = W(a1.0.rotate_left(1))
+                + W(a2.0.rotate_left(7))
+                + W(a3.0.rotate_left(12))
+                + W(a4.0.rotate_left(18));
+            accumulators.into_iter().fold(hash, Self::merge_accumulator)
+        };
+
+        hash += key.len() as u64;
+
+        while let Some((word, rest)) = remainder.split_first_chunk::<8>() {
+            hash ^= Self::round(W(0), u64::from_le_bytes(*word));
+            hash = W(hash.0.rotate_left(27)) * Self::PRIME_1 + Self::PRIME_4;
+            remainder = rest;
+        }
+        if let Some((word, rest)) = remainder.split_first_chunk::<4>() {
+            hash ^= W(u32::from_le_bytes(*word) as u64) * Self::PRIME_1;
+            hash = W(hash.0.rotate_left(23)) * Self::PRIME_2 + Self::PRIME_3;
+            remainder = rest;
+        }
+        for &byte in remainder {
+            hash ^= W(byte as u64) * Self::PRIME_5;
+            hash = W(hash.0.rotate_left(11)) * Self::PRIME_1;
+        }
+
+        hash ^= hash >> 33;
+        hash *= Self::PRIME_2;
+        hash ^= hash >> 29;
+        hash *= Self::PRIME_3;
+        hash ^= hash >> 32;
+        hash.0
+    }
+}
+
+/// A hash specialized for `u64` keys, which mixes the key with the seed using `mix64`.
+/// Other keys are mixed 8 bytes at a time, starting from their length.
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct U64Mixer;
+
+impl HashAlgorithm for U64Mixer {
+    const BITS: u32 = 64;
+
+    fn hash(key: &[u8], seed: u64) -> u64 {
+        if let Ok(&word) = key.try_into() {
+            return mix64(u64::from_le_bytes(word), seed);
+        }
+        key.chunks(8)
+            .fold(mix64(key.len() as u64, seed), |hash, chunk| {
+                let word: [u8; 8] = array::from_fn(|i| chunk.get(i).copied().unwrap_or(0));
+                mix64(u64::from_le_bytes(word), hash)
+            })
+    }
+}
+
+/// The hash algorithm of a filter, chosen at runtime and recorded in SST metadata.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashType {
+    /// The 32-bit MurmurHash3, which limits standard bloom filters to `2^32` bits
+    Murmur,
+    /// The 64-bit MurmurHash3
+    Murmur64,
+    /// xxHash64
+    XxHash64,
+    /// `mix64` on the key, the fastest for `u64` keys
+    U64Mixer,
+}
+
+impl HashType {
+    /// The number identifying the hash algorithm in SST metadata.
+    pub fn tag(self) -> u64 {
+        match self {
+            HashType::Murmur => 0,
+            HashType::Murmur64 => 1,
+            HashType::XxHash64 => 2,
+            HashType::U64Mixer => 3,
+        }
+    }
+
+    /// Returns the hash algorithm with the given tag, or None if there is no such hash algorithm.
+    pub fn from_tag(tag: u64) -> Option<Self> {
+        match tag {
+            0 => Some(HashType::Murmur),
+            1 => Some(HashType::Murmur64),
+            2 => Some(HashType::XxHash64),
+            3 => Some(HashType::U64Mixer),
+            _ => None,
+        }
+    }
+
+    /// Hashes a key to 64 bits with the given seed, using `HashAlgorithm::hash64`.
+    pub fn hash64(self, key: u64, seed: u64) -> u64 {
+        let key = &key.to_le_bytes();
+        match self {
+            HashType::Murmur => MurmurHash::hash64(key, seed),
+            HashType::Murmur64 => MurmurHash64::hash64(key, seed),
+            HashType::XxHash64 => XxHash64::hash64(key, seed),
+            HashType::U64Mixer => U64Mixer::hash64(key, seed),
+        }
+    }
+
+    /// Hashes a key to an index into a container with the given length,
+    /// like a `HashFunction` of the algorithm with the given seed.
+    pub fn hash_to_index(self, key: u64, seed: u32, length: usize) -> usize {
+        fn index<H: HashAlgorithm>(key: u64, seed: u32, length: usize) -> usize {
+            HashFunction::<H>::with_seed(seed).hash_to_index(key.to_le_bytes(), length)
+        }
+        match self {
+            HashType::Murmur => index::<MurmurHash>(key, seed, length),
+            HashType::Murmur64 => index::<MurmurHash64>(key, seed, length),
+            HashType::XxHash64 => index::<XxHash64>(key, seed, length),
+            HashType::U64Mixer => index::<U64Mixer>(key, seed, length),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,12 +425,75 @@ mod tests {
             ),
         ];
         for (seed, expected, key) in cases {
-            assert_eq!(MurmurHash::hash(key.as_bytes(), seed), expected);
+            assert_eq!(MurmurHash::hash32(key.as_bytes(), seed), expected);
         }
     }
 
+    #[test]
+    fn test_murmur64() {
+        // The first half of MurmurHash3_x64_128, from the reference implementation
+        let cases = [
+            (0, 0x0000000000000000, ""),
+            (
+                0,
+                0xe34bbc7bbc071b6c,
+                "The quick brown fox jumps over the lazy dog",
+            ),
+            (0, 0xe271865701f54561, "foo"),
+        ];
+        for (seed, expected, key) in cases {
+            assert_eq!(MurmurHash64::hash(key.as_bytes(), seed), expected, "{key}");
+        }
+    }
+
+    #[test]
+    fn test_xxhash64() {
+        // From the reference implementation
+        let cases = [
+            (0, 0xef46db3751d8e999, ""),
+            (0, 0xd24ec4f1a98c6e5b, "a"),
+            (0, 0x44bc2cf5ad770999, "abc"),
+            (
+                0,
+                0xfbcea83c8a378bf1,
+                "Nobody inspects the spammish repetition",
+            ),
+        ];
+        for (seed, expected, key) in cases {
+            assert_eq!(XxHash64::hash(key.as_bytes(), seed), expected, "{key}");
+        }
+    }
+
+    /// Tests that the mixer is `mix64` on `u64` keys, and that every algorithm gives 64 bits through `hash64`.
+    #[test]
+    fn test_hash_types() {
+        assert_eq!(U64Mixer::hash(&7u64.to_le_bytes(), 3), mix64(7, 3));
+        assert_ne!(U64Mixer::hash(&[7], 3), U64Mixer::hash(&[7, 0], 3));
+
+        for tag in 0..4 {
+            let hash_type = HashType::from_tag(tag).unwrap();
+            assert_eq!(hash_type.tag(), tag);
+            let hashes: Vec<u64> = (0..1000).map(|key| hash_type.hash64(key, 42)).collect();
+            assert!(hashes.iter().any(|&hash| hash >> 32 != 0), "{hash_type:?}");
+            assert!(hashes.iter().any(|&hash| hash as u32 != 0), "{hash_type:?}");
+            assert_ne!(hash_type.hash64(1, 42), hash_type.hash64(1, 43));
+            assert!(hash_type.hash_to_index(1, 42, 10) < 10);
+        }
+        assert_eq!(HashType::from_tag(4), None);
+
+        // 32-bit Murmur can't reach past 2^32, but the 64-bit algorithms can
+        let length = 1 << 40;
+        let past_u32 = |hash_type: HashType| {
+            (0..100).any(|key| hash_type.hash_to_index(key, 0, length) >> 32 != 0)
+        };
+        assert!(!past_u32(HashType::Murmur));
+        assert!(past_u32(HashType::Murmur64));
+        assert!(past_u32(HashType::XxHash64));
+        assert!(past_u32(HashType::U64Mixer));
+    }
+
     fn murmur_hash_to_index(key: &str, length: usize, seed: u32) -> usize {
-        MurmurHash::hash(key.as_bytes(), seed) as usize % length
+        MurmurHash::hash32(key.as_bytes(), seed) as usize % length
     }
 
     #[test]
@@ -22,10 +22,11 @@ pub struct HashTable<K, V, H = MurmurHash> {
 }
 
 impl<K: bytemuck::Pod + Eq, V, H: HashAlgorithm> HashTable<K, V, H> {
-    /// Creates and returns an empty hash table with the given capacity.
+    /// Creates and returns an empty hash table with the given capacity,
+    /// whose hash function has the given seed.
     ///
     /// Returns `DbError::Oom` if allocation fails.
-    pub fn new(capacity: usize) -> Result<Self, DbError> {
+    pub fn new(capacity: usize, seed: u32) -> Result<Self, DbError> {
         // Use a load factor of 75% -- simply going off what Java does.
         // Not entirely sure how differences in design affect things,
         // but it's more than the 10%-20% extra capacity in the lecture slides.
@@ -44,7 +45,7 @@ impl<K: bytemuck::Pod + Eq, V, H: HashAlgorithm> HashTable<K, V, H> {
             inner,
             capacity,
             len: 0,
-            hash_function: HashFunction::new(),
+            hash_function: HashFunction::with_seed(seed),
         })
     }
 
@@ -192,9 +193,11 @@ mod tests {
     pub struct MockHash;
 
     impl HashAlgorithm for MockHash {
-        fn hash(key: &[u8], _seed: u32) -> u32 {
+        const BITS: u32 = 64;
+
+        fn hash(key: &[u8], _seed: u64) -> u64 {
             // Just returns the page number of a `BufferPageId`
-            usize::from_ne_bytes(key[8..16].try_into().unwrap()) as u32
+            usize::from_ne_bytes(key[8..16].try_into().unwrap()) as u64
         }
     }
 
@@ -241,7 +244,7 @@ mod tests {
 
     #[test]
     pub fn test_basic() -> Result<()> {
-        let table: &mut HashTable<_, _, MockHash> = &mut HashTable::new(32)?;
+        let table: &mut HashTable<_, _, MockHash> = &mut HashTable::new(32, 0)?;
         let n = table.num_buckets();
         let [a, b, c, d, e, f] = [10, 12, 14, n - 2, 0, n - 4];
 
@@ -338,7 +341,7 @@ mod tests {
 
     #[test]
     fn test_remove() -> Result<()> {
-        let table: &mut HashTable<_, _, MockHash> = &mut HashTable::new(32)?;
+        let table: &mut HashTable<_, _, MockHash> = &mut HashTable::new(32, 0)?;
         let n = table.num_buckets();
         let [a, b, c, d, e, f] = [10, 12, 14, n - 2, 0, n - 4];
 
@@ -411,7 +414,7 @@ mod tests {
 
     #[test]
     fn test_over_capacity() -> Result<()> {
-        let mut table: HashTable<_, _, MockHash> = HashTable::new(128)?;
+        let mut table: HashTable<_, _, MockHash> = HashTable::new(128, 0)?;
 
         for i in 0..128 {
             table.insert(page_id(0, i), i);
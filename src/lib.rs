@@ -1,22 +1,54 @@
+mod backup;
 mod bloom_filter;
 mod btree;
+mod compression;
 mod database;
+mod delta_leaf;
+mod endian;
 mod error;
 mod eviction;
 mod file_system;
+mod filter;
 mod hash;
 mod hashtable;
 mod list;
 mod lsm;
 mod memtable;
 mod merge;
+mod partitioned_filter;
+mod range_filter;
 mod sst;
+mod verify;
+mod xor_filter;
 
 #[cfg(test)]
 mod test_util;
 
-pub use database::{Database, DbConfiguration};
+pub use backup::{BackupEngine, BackupInfo};
+pub use btree::{SearchStrategy, SstProperties};
+pub use compression::{Compression, LevelCompression};
+pub use database::{Database, DbConfiguration, RecoveryTarget, RepairReport};
 pub use error::DbError;
-pub use lsm::LsmConfiguration;
+pub use eviction::EvictionType;
+pub use filter::{FilterCounts, FilterType};
+pub use hash::HashType;
+pub use lsm::{FilterReport, LsmConfiguration};
+pub use range_filter::RangeFilterConfiguration;
+pub use verify::{ProblemKind, ScrubStatus, Scrubber, VerifyProblem, VerifyReport};
 
 const PAGE_SIZE: usize = 4096;
+
+/// The version of the on-disk format written by this version of BEARR.
+/// Older versions can still be read, and `Database::upgrade` rewrites them in this version.
+///
+/// - 0: The original format.
+/// - 1: Pages of SST files end with a checksum, and SST metadata and `config.json` record the format version.
+/// - 2: Leaf pages of SST files can be compressed, as recorded in SST metadata.
+/// - 3: Leaf pages of SST files can store keys as deltas with restart points.
+/// - 4: SST metadata holds properties: the key range, tombstone count, creation time, and origin level.
+/// - 5: SST metadata records the type of the bloom filter, which can be blocked by cache line.
+/// - 6: SSTs can have xor filters instead of bloom filters.
+/// - 7: SSTs can have range filters, stored after the bloom filter and recorded in SST metadata.
+/// - 8: SSTs can have partitioned filters, with one page per partition after an index.
+/// - 9: SST metadata records the hash algorithm of the filter.
+const FORMAT_VERSION: u64 = 9;
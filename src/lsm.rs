@@ -3,10 +3,15 @@ use std::ops::RangeInclusive;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    DbError,
+    DbError, FORMAT_VERSION,
+    btree::SearchStrategy,
+    compression::{Compression, LevelCompression},
     file_system::{FileId, FileSystem},
+    filter::{FilterCounts, FilterType},
+    hash::HashType,
     memtable::MemTable,
     merge::{self, MergedIterator},
+    range_filter::RangeFilterConfiguration,
     sst::Sst,
 };
 
@@ -21,16 +26,61 @@ pub struct LsmConfiguration {
     pub memtable_capacity: usize,
     /// The number of bits per entry for bloom filters at the topmost LSM level.
     pub bloom_filter_bits: usize,
+    /// The compression for the leaf pages of SSTs at each LSM level, starting from the top.
+    /// Levels past the end of the list use its last entry, and if it's empty, nothing is compressed.
+    #[serde(default)]
+    pub compression: LevelCompression,
+    /// Whether to keep the internal B-tree nodes of every SST in memory,
+    /// so that a point lookup reads at most one leaf through the buffer pool.
+    #[serde(default)]
+    pub pin_internal_nodes: bool,
+    /// How point lookups find the leaf page of a key in an SST.
+    #[serde(default)]
+    pub search_strategy: SearchStrategy,
+    /// Whether to give bloom filters at every level `bloom_filter_bits` bits per entry,
+    /// rather than assigning them with Monkey.
+    #[serde(default)]
+    pub uniform_bits: bool,
+    /// Whether to assign bloom filter bits from the actual number of entries in each level
+    /// and how often lookups probed its filters for keys that it didn't hold, rather than from the level alone.
+    /// The filters take up about as much memory as with Monkey. Ignored if `uniform_bits` is set.
+    #[serde(default)]
+    pub workload_aware_bits: bool,
+    /// The type of the filters of new SSTs, which get as many bits per entry as bloom filters would.
+    /// Existing SSTs keep the type they were written with.
+    #[serde(default)]
+    pub filter_type: FilterType,
+    /// The hash algorithm of the filters of new SSTs, or None for the default of the filter type.
+    /// It is recorded in each SST, so existing SSTs keep the algorithm they were written with.
+    #[serde(default)]
+    pub filter_hash: Option<HashType>,
+    /// The number of levels, from the top, whose partitioned filters are kept in memory.
+    /// The partitioned filters of lower levels are read through the buffer pool when probed,
+    /// so their memory is bounded by its capacity. Only affects SSTs with `FilterType::Partitioned`.
+    #[serde(default)]
+    pub pinned_filter_levels: usize,
+    /// The range filters of new SSTs, which let scans skip SSTs that hold nothing in the range,
+    /// or None for no range filters. Existing SSTs keep the range filter they were written with.
+    #[serde(default)]
+    pub range_filter: Option<RangeFilterConfiguration>,
 }
 
 impl LsmConfiguration {
     pub fn validate(&self) -> Result<(), DbError> {
-        if self.memtable_capacity > 0 && self.size_ratio >= 2 {
+        let valid_range_filter = self
+            .range_filter
+            .is_none_or(|range_filter| range_filter.bucket_bits < u64::BITS);
+        if self.memtable_capacity > 0 && self.size_ratio >= 2 && valid_range_filter {
             Ok(())
         } else {
             Err(DbError::InvalidConfiguration)
         }
     }
+
+    /// The hash algorithm of the filters of new SSTs.
+    fn filter_hash(&self) -> HashType {
+        self.filter_hash.unwrap_or(self.filter_type.default_hash())
+    }
 }
 
 /// Metadata for an LSM tree, persisted separately from the actual data.
@@ -47,14 +97,59 @@ impl LsmMetadata {
             bottom_leveling: 0,
         }
     }
+
+    /// Reconstructs metadata from the number of entries in each SST of each level,
+    /// for when the metadata file has been lost.
+    ///
+    /// The bottom leveling isn't recorded in the SSTs, so it is estimated
+    /// by comparing the size of the bottom level to the size of an SST that has been merged into it.
+    pub fn reconstruct(entries_per_sst: &[Vec<usize>], configuration: LsmConfiguration) -> Self {
+        let Some(bottom_level_number) = entries_per_sst.iter().rposition(|level| !level.is_empty())
+        else {
+            return Self::empty();
+        };
+
+        let t = configuration.size_ratio;
+        let merged_sst_size = configuration
+            .memtable_capacity
+            .saturating_mul(t.saturating_pow(bottom_level_number as u32));
+        let bottom_leveling = entries_per_sst[bottom_level_number]
+            .iter()
+            .map(|&n_entries| usize::max(n_entries.div_ceil(merged_sst_size), 1))
+            .sum::<usize>()
+            .clamp(1, t - 1);
+
+        Self {
+            ssts_per_level: entries_per_sst[..=bottom_level_number]
+                .iter()
+                .map(Vec::len)
+                .collect(),
+            bottom_leveling,
+        }
+    }
 }
 
 pub const TOMBSTONE: u64 = u64::MAX;
 
+/// How bloom filter bits are assigned to the levels of an LSM tree, and how the filters have done.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterReport {
+    /// The number of bits per entry that new SSTs at each level get, from the top level down
+    pub bits_per_entry: Vec<usize>,
+    /// The number of entries in each level, including overwritten entries and tombstones
+    pub entries_per_level: Vec<usize>,
+    /// How the filters of each level answered point lookups since the database was opened,
+    /// including those of SSTs that have since been merged
+    pub filter_counts: Vec<FilterCounts>,
+    /// The expected number of filters that answer positive for a key in no SST,
+    /// summed over the filters of every SST
+    pub expected_false_positive_rate: f64,
+}
+
 /// An LSM tree, consisting of a memtable and several levels of SSTs.
 ///
 /// Makes use of Monkey for assigning bloom filter bits
-/// (unless the `uniform_bits` feature is enabled)
+/// (unless `uniform_bits` or `workload_aware_bits` is configured)
 /// and Dostoevsky for compaction.
 pub struct LsmTree {
     memtable: MemTable<u64, u64>,
@@ -64,6 +159,9 @@ pub struct LsmTree {
     /// The number of original SSTs that the SST at the bottom level consists of.
     bottom_leveling: usize,
     configuration: LsmConfiguration,
+    /// How the filters of SSTs that have been merged answered point lookups, for each level they were in,
+    /// so that the lookups a level gets outlive its SSTs
+    merged_filter_counts: Vec<FilterCounts>,
 }
 
 impl LsmTree {
@@ -83,13 +181,14 @@ impl LsmTree {
             let mut level = Vec::with_capacity(num_ssts);
 
             for sst_number in 0..num_ssts {
-                let sst = Sst::open(
+                let mut sst = Sst::open(
                     FileId {
                         lsm_level,
                         sst_number,
                     },
                     file_system,
                 )?;
+                Self::configure_sst(&configuration, &mut sst, lsm_level, file_system)?;
                 level.push(sst);
             }
 
@@ -101,6 +200,7 @@ impl LsmTree {
             levels,
             bottom_leveling: metadata.bottom_leveling,
             configuration,
+            merged_filter_counts: Vec::new(),
         })
     }
 
@@ -157,7 +257,9 @@ impl LsmTree {
         scans.push(merge::Sources::MemTable(memtable_scan));
 
         for level in &self.levels {
-            for sst in level.iter().rev() {
+            // SSTs whose key range or range filter rules out the range can't contribute,
+            // so skip them without reading anything
+            for sst in level.iter().rev().filter(|sst| sst.may_hold(&range)) {
                 let sst_scan = sst.scan(range.clone(), file_system)?;
                 scans.push(merge::Sources::BTree(sst_scan));
             }
@@ -171,14 +273,42 @@ impl LsmTree {
         self.levels.len().checked_sub(1)
     }
 
-    #[cfg(feature = "uniform_bits")]
-    fn monkey(&self, _level: usize) -> usize {
-        self.configuration.bloom_filter_bits
+    /// Applies the configured search strategy to an SST that was just opened or created at the given level,
+    /// and pins its internal nodes and filter if configured.
+    fn configure_sst(
+        configuration: &LsmConfiguration,
+        sst: &mut Sst,
+        level: usize,
+        file_system: &FileSystem,
+    ) -> Result<(), DbError> {
+        sst.search_strategy = configuration.search_strategy;
+        if configuration.pin_internal_nodes {
+            sst.pin_nodes(file_system)?;
+        }
+        Self::configure_filter(configuration, sst, level, file_system)
     }
 
-    /// Returns the number of bits per entry for a bloom filter at the given level according to Monkey.
-    #[cfg(not(feature = "uniform_bits"))]
+    /// Pins the filter of an SST at the given level if it's within `pinned_filter_levels`, and unpins it otherwise.
+    fn configure_filter(
+        configuration: &LsmConfiguration,
+        sst: &mut Sst,
+        level: usize,
+        file_system: &FileSystem,
+    ) -> Result<(), DbError> {
+        if level < configuration.pinned_filter_levels {
+            sst.pin_filter(file_system)
+        } else {
+            sst.unpin_filter();
+            Ok(())
+        }
+    }
+
+    /// Returns the number of bits per entry for a bloom filter at the given level according to Monkey,
+    /// or `bloom_filter_bits` at every level if `uniform_bits` is configured.
     fn monkey(&self, level: usize) -> usize {
+        if self.configuration.uniform_bits {
+            return self.configuration.bloom_filter_bits;
+        }
         let t = self.configuration.size_ratio as f64;
         let m_0 = self.configuration.bloom_filter_bits as f64;
         // Let T be the size ratio.
@@ -201,6 +331,92 @@ impl LsmTree {
         f64::max(m_0 - (level as f64) * t.log2() / 2_f64.ln(), 0.0).ceil() as usize
     }
 
+    /// Returns the number of bits per entry for bloom filters at each level,
+    /// given the number of entries that each level will have.
+    ///
+    /// With `workload_aware_bits`, the bits are assigned by `allocate_bits`,
+    /// weighting each level by the number of lookups that its filters answered for keys it didn't hold,
+    /// plus one so that levels that haven't been probed yet still get bits.
+    /// The total is what Monkey would assign to the same levels.
+    /// Otherwise, this is `monkey` at each level.
+    fn bits_per_entry(&self, level_sizes: &[usize]) -> Vec<usize> {
+        let monkey: Vec<usize> = (0..level_sizes.len()).map(|i| self.monkey(i)).collect();
+        if self.configuration.uniform_bits || !self.configuration.workload_aware_bits {
+            return monkey;
+        }
+
+        let total_bits = level_sizes
+            .iter()
+            .zip(&monkey)
+            .map(|(&n_entries, &bits)| (n_entries * bits) as f64)
+            .sum();
+        let filter_counts = self.filter_counts();
+        let weights: Vec<f64> = (0..level_sizes.len())
+            .map(|i| {
+                let negatives = filter_counts.get(i).map_or(0, FilterCounts::negatives);
+                (negatives + 1) as f64
+            })
+            .collect();
+        allocate_bits(level_sizes, &weights, total_bits)
+    }
+
+    /// The number of entries in each level, including overwritten entries and tombstones.
+    fn level_sizes(&self) -> Vec<usize> {
+        self.levels
+            .iter()
+            .map(|level| level.iter().map(Sst::num_entries).sum())
+            .collect()
+    }
+
+    /// How the filters of each level answered point lookups since the LSM tree was opened,
+    /// including those of SSTs that have since been merged.
+    fn filter_counts(&self) -> Vec<FilterCounts> {
+        let mut filter_counts = self.merged_filter_counts.clone();
+        filter_counts.resize(
+            usize::max(filter_counts.len(), self.levels.len()),
+            FilterCounts::default(),
+        );
+        for (level, counts) in self.levels.iter().zip(&mut filter_counts) {
+            for sst in level {
+                *counts += sst.filter_stats.counts();
+            }
+        }
+        filter_counts
+    }
+
+    /// Destroys an SST that was merged out of the given level, keeping the counts of its filter.
+    fn retire_sst(
+        merged_filter_counts: &mut Vec<FilterCounts>,
+        level: usize,
+        sst: Sst,
+        file_system: &FileSystem,
+    ) -> Result<(), DbError> {
+        if merged_filter_counts.len() <= level {
+            merged_filter_counts.resize(level + 1, FilterCounts::default());
+        }
+        merged_filter_counts[level] += sst.filter_stats.counts();
+        sst.destroy(file_system)
+    }
+
+    /// Returns the current assignment of bloom filter bits to each level
+    /// and how the filters have answered point lookups.
+    pub fn filter_report(&self) -> FilterReport {
+        let entries_per_level = self.level_sizes();
+        let mut filter_counts = self.filter_counts();
+        filter_counts.truncate(self.levels.len());
+        FilterReport {
+            bits_per_entry: self.bits_per_entry(&entries_per_level),
+            filter_counts,
+            expected_false_positive_rate: self.ssts().map(Sst::expected_false_positive_rate).sum(),
+            entries_per_level,
+        }
+    }
+
+    /// Returns the compression for the leaf pages of SSTs at the given level.
+    fn compression(&self, level: usize) -> Compression {
+        self.configuration.compression.level(level)
+    }
+
     /// Flushes the memtable into an SST, and merges SSTs as necessary
     pub fn flush_memtable(&mut self, file_system: &FileSystem) -> Result<(), DbError> {
         if self.memtable.size() == 0 {
@@ -219,13 +435,22 @@ impl LsmTree {
             sst_number: self.levels[0].len(),
         };
 
-        let sst = Sst::create(
+        let mut level_sizes = self.level_sizes();
+        level_sizes[0] += mem_table_size;
+        let bits_per_entry = self.bits_per_entry(&level_sizes)[0];
+
+        let mut sst = Sst::create(
             key_values.map(Ok),
-            mem_table_size,
-            self.monkey(0),
+            bits_per_entry,
+            self.configuration.filter_type,
+            self.configuration.filter_hash(),
+            self.configuration.range_filter,
+            self.compression(0),
+            0,
             file_id,
             file_system,
         )?;
+        Self::configure_sst(&self.configuration, &mut sst, 0, file_system)?;
 
         self.levels[0].push(sst);
 
@@ -247,20 +472,22 @@ impl LsmTree {
 
         // Merge non-bottom levels
         for i in 0..bottom_level_number {
-            let bits_per_entry = self.monkey(i + 1);
-
-            let [level, level_below] = self.levels.get_disjoint_mut([i, i + 1]).unwrap();
-
-            if level.len() < t {
+            if self.levels[i].len() < t {
                 continue;
             }
 
+            // The entries of this level are about to move into the level below
+            let mut level_sizes = self.level_sizes();
+            level_sizes[i + 1] += std::mem::take(&mut level_sizes[i]);
+            let bits_per_entry = self.bits_per_entry(&level_sizes)[i + 1];
+            let compression = self.compression(i + 1);
+
+            let [level, level_below] = self.levels.get_disjoint_mut([i, i + 1]).unwrap();
+
             let mut scans = Vec::new();
-            let mut n_entries_hint = 0;
             for sst in level.iter().rev() {
                 let sst_scan = sst.scan(u64::MIN..=u64::MAX, file_system)?;
                 scans.push(sst_scan);
-                n_entries_hint += sst.num_entries();
             }
             let key_values = MergedIterator::new(scans, false)?;
 
@@ -269,33 +496,37 @@ impl LsmTree {
                 sst_number: level_below.len(),
             };
 
-            let sst = Sst::create(
+            let mut sst = Sst::create(
                 key_values,
-                n_entries_hint,
                 bits_per_entry,
+                self.configuration.filter_type,
+                self.configuration.filter_hash(),
+                self.configuration.range_filter,
+                compression,
+                i + 1,
                 file_id,
                 file_system,
             )?;
+            Self::configure_sst(&self.configuration, &mut sst, i + 1, file_system)?;
             level_below.push(sst);
 
             for sst in level.drain(..) {
-                sst.destroy(file_system)?;
+                Self::retire_sst(&mut self.merged_filter_counts, i, sst, file_system)?;
             }
         }
 
         // Merge bottom level
-        let bottom_bits_per_entry = self.monkey(bottom_level_number);
+        let bottom_bits_per_entry = self.bits_per_entry(&self.level_sizes())[bottom_level_number];
+        let bottom_compression = self.compression(bottom_level_number);
         let bottom_level = &mut self.levels[bottom_level_number];
         debug_assert_ne!(bottom_level.len(), 0);
         if bottom_level.len() > 1 {
             self.bottom_leveling += bottom_level.len() - 1;
 
             let mut scans = Vec::new();
-            let mut n_entries_hint = 0;
             for sst in bottom_level.iter().rev() {
                 let sst_scan = sst.scan(u64::MIN..=u64::MAX, file_system)?;
                 scans.push(sst_scan);
-                n_entries_hint += sst.num_entries();
             }
             let key_values = MergedIterator::new(scans, true)?;
 
@@ -308,8 +539,12 @@ impl LsmTree {
 
             let new_sst = Sst::create(
                 key_values,
-                n_entries_hint,
                 bottom_bits_per_entry,
+                self.configuration.filter_type,
+                self.configuration.filter_hash(),
+                self.configuration.range_filter,
+                bottom_compression,
+                bottom_level_number,
                 file_id,
                 file_system,
             )?;
@@ -322,17 +557,32 @@ impl LsmTree {
                 new_sst.destroy(file_system)?;
                 Sst::create(
                     [Ok((0, TOMBSTONE))],
-                    1,
                     bottom_bits_per_entry,
+                    self.configuration.filter_type,
+                    self.configuration.filter_hash(),
+                    self.configuration.range_filter,
+                    bottom_compression,
+                    bottom_level_number,
                     file_id,
                     file_system,
                 )?
             } else {
                 new_sst
             };
+            Self::configure_sst(
+                &self.configuration,
+                &mut new_sst,
+                bottom_level_number,
+                file_system,
+            )?;
 
             for sst in bottom_level.drain(..) {
-                sst.destroy(file_system)?;
+                Self::retire_sst(
+                    &mut self.merged_filter_counts,
+                    bottom_level_number,
+                    sst,
+                    file_system,
+                )?;
             }
 
             let new_file_id = FileId {
@@ -360,6 +610,12 @@ impl LsmTree {
                 sst_number: 0,
             };
             sst.rename(new_file_id, file_system)?;
+            Self::configure_filter(
+                &self.configuration,
+                &mut sst,
+                bottom_level_number + 1,
+                file_system,
+            )?;
             new_bottom_level.push(sst);
 
             self.bottom_leveling = 1;
@@ -368,6 +624,78 @@ impl LsmTree {
         Ok(())
     }
 
+    /// Rewrites every SST written with an older format version in the current format,
+    /// keeping its position in the LSM tree and its entries, tombstones included.
+    ///
+    /// Returns the number of SSTs rewritten.
+    pub fn rewrite_outdated_ssts(&mut self, file_system: &FileSystem) -> Result<usize, DbError> {
+        // Pick some file ID that doesn't exist, as in `merge_levels`
+        let temporary_file_id = FileId {
+            lsm_level: self.levels.len(),
+            sst_number: 0,
+        };
+
+        let bits_per_entry = self.bits_per_entry(&self.level_sizes());
+        let mut rewritten = 0;
+        for lsm_level in 0..self.levels.len() {
+            let bits_per_entry = bits_per_entry[lsm_level];
+            let compression = self.compression(lsm_level);
+            for sst_number in 0..self.levels[lsm_level].len() {
+                let sst = &self.levels[lsm_level][sst_number];
+                if sst.format_version() >= FORMAT_VERSION {
+                    continue;
+                }
+
+                let mut old_sst = Sst::create(
+                    sst.scan(u64::MIN..=u64::MAX, file_system)?,
+                    bits_per_entry,
+                    self.configuration.filter_type,
+                    self.configuration.filter_hash(),
+                    self.configuration.range_filter,
+                    compression,
+                    lsm_level,
+                    temporary_file_id,
+                    file_system,
+                )?;
+                Self::configure_sst(&self.configuration, &mut old_sst, lsm_level, file_system)?;
+
+                let sst = &mut self.levels[lsm_level][sst_number];
+                let file_id = sst.file_id;
+                std::mem::swap(sst, &mut old_sst);
+                Self::retire_sst(
+                    &mut self.merged_filter_counts,
+                    lsm_level,
+                    old_sst,
+                    file_system,
+                )?;
+                sst.rename(file_id, file_system)?;
+                rewritten += 1;
+            }
+        }
+
+        Ok(rewritten)
+    }
+
+    /// The file IDs of every SST in the LSM tree, from the top level down.
+    pub fn file_ids(&self) -> impl Iterator<Item = FileId> {
+        self.levels.iter().flatten().map(|sst| sst.file_id)
+    }
+
+    /// Every SST in the LSM tree, from the top level down.
+    pub fn ssts(&self) -> impl Iterator<Item = &Sst> {
+        self.levels.iter().flatten()
+    }
+
+    /// The number of bytes of internal nodes pinned in memory across all SSTs.
+    pub fn pinned_bytes(&self) -> usize {
+        self.ssts().map(Sst::pinned_bytes).sum()
+    }
+
+    /// The number of bytes that the filters of all SSTs hold in memory.
+    pub fn filter_bytes(&self) -> usize {
+        self.ssts().map(Sst::filter_bytes).sum()
+    }
+
     /// Metadata for the LSM tree calculated from its fields.
     pub fn metadata(&self) -> LsmMetadata {
         LsmMetadata {
@@ -377,11 +705,53 @@ impl LsmTree {
     }
 }
 
+/// Assigns bits per entry to levels with the given numbers of entries, so that their filters take up
+/// about `total_bits` bits and minimize the expected number of false positives,
+/// weighting the false positive rate of each level by how often it is probed for keys it doesn't hold.
+///
+/// With `ep(M) = 2^(-M ln 2)` as in `LsmTree::monkey`, `w_k` the weight and `n_k` the entries of level `k`,
+/// minimizing `sum w_k ep(M_k)` subject to `sum n_k M_k = total_bits` with a Lagrange multiplier `λ`
+/// gives `ep(M_k) = λ n_k / w_k`, so `M_k = -(ln λ + ln(n_k / w_k)) / ln(2)^2`.
+/// Substituting into the constraint solves for `ln λ`.
+/// Levels that would get no more than 0 bits are given 0 and left out while solving again for the rest.
+/// Empty levels get 0 bits.
+fn allocate_bits(level_sizes: &[usize], weights: &[f64], total_bits: f64) -> Vec<usize> {
+    let ln2_squared = 2_f64.ln().powi(2);
+    let ln_ratios: Vec<f64> = level_sizes
+        .iter()
+        .zip(weights)
+        .map(|(&n_entries, &weight)| (n_entries as f64 / weight).ln())
+        .collect();
+    let mut active: Vec<usize> = (0..level_sizes.len())
+        .filter(|&k| level_sizes[k] > 0)
+        .collect();
+
+    loop {
+        let active_entries: f64 = active.iter().map(|&k| level_sizes[k] as f64).sum();
+        let weighted_ln_ratios: f64 = active
+            .iter()
+            .map(|&k| level_sizes[k] as f64 * ln_ratios[k])
+            .sum();
+        let minus_ln_lambda = (total_bits * ln2_squared + weighted_ln_ratios) / active_entries;
+        let bits = |k: usize| (minus_ln_lambda - ln_ratios[k]) / ln2_squared;
+
+        let len = active.len();
+        active.retain(|&k| bits(k) > 0.0);
+        if active.len() == len {
+            let mut bits_per_entry = vec![0; level_sizes.len()];
+            for &k in &active {
+                bits_per_entry[k] = bits(k).round() as usize;
+            }
+            return bits_per_entry;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
 
-    use crate::test_util::TestFs;
+    use crate::{bloom_filter::BloomFilter, filter::Filter, test_util::TestFs};
 
     use super::*;
 
@@ -396,6 +766,15 @@ mod tests {
                 size_ratio: 3,
                 memtable_capacity: 6,
                 bloom_filter_bits: 5,
+                compression: LevelCompression::default(),
+                pin_internal_nodes: false,
+                search_strategy: SearchStrategy::BTree,
+                uniform_bits: false,
+                workload_aware_bits: false,
+                filter_type: FilterType::Bloom,
+                filter_hash: None,
+                pinned_filter_levels: 0,
+                range_filter: None,
             },
             fs,
         )?;
@@ -523,6 +902,215 @@ mod tests {
         Ok(())
     }
 
+    /// Tests that scans over recent keys don't read any pages of SSTs that only hold older keys.
+    #[test]
+    fn test_key_range_pruning() -> Result<()> {
+        let fs = &test_fs("key_range_pruning");
+        let mut lsm = empty_lsm(fs)?;
+
+        // Increasing keys like timestamps, so that the two SSTs hold separate ranges
+        for i in 0..24 {
+            lsm.put(i * 10, i, fs)?;
+        }
+        assert_state(&lsm, &[&[6], &[18]], 1);
+        let old_sst = &lsm.levels[1][0];
+        assert_eq!(old_sst.key_range, 0..=170);
+
+        // Break the checksums of the old SST's leaves and nodes, then reopen with a new buffer pool
+        let path = fs.path(old_sst.file_id);
+        let mut bytes = std::fs::read(&path)?;
+        for page in old_sst.btree_metadata.leafs_offset..old_sst.btree_metadata.bloom_offset {
+            bytes[page as usize * crate::PAGE_SIZE + 8] ^= 1;
+        }
+        std::fs::write(&path, bytes)?;
+        let fs = &FileSystem::new(
+            crate::test_util::get_path("lsm", "key_range_pruning"),
+            16,
+            1,
+            1,
+        )?;
+        let lsm = LsmTree::open(lsm.metadata(), lsm.configuration, fs)?;
+
+        let scanned: Vec<(u64, u64)> = lsm.scan(175..=u64::MAX, fs)?.collect::<Result<_, _>>()?;
+        assert_eq!(scanned, (18..24).map(|i| (i * 10, i)).collect::<Vec<_>>());
+        assert_eq!(lsm.get(230, fs)?, Some(23));
+        assert_eq!(lsm.get(1000, fs)?, None);
+        let scanned: Result<Vec<(u64, u64)>, DbError> =
+            lsm.scan(0..=u64::MAX, fs).and_then(|scan| scan.collect());
+        assert!(matches!(scanned, Err(DbError::Corruption { .. })));
+
+        Ok(())
+    }
+
+    /// Tests that scans over gaps between keys don't read any pages of SSTs with range filters.
+    #[test]
+    fn test_range_filter_pruning() -> Result<()> {
+        let fs = &test_fs("range_filter_pruning");
+        let mut lsm = empty_lsm(fs)?;
+        lsm.configuration.range_filter = Some(RangeFilterConfiguration {
+            bucket_bits: 4,
+            bits_per_bucket: 20,
+        });
+
+        // Keys far apart, with the SSTs' key ranges overlapping
+        for i in 0..18 {
+            lsm.put(i * 2000, i, fs)?;
+        }
+        for i in 0..6 {
+            lsm.put(i * 2000 + 1000, i, fs)?;
+        }
+        assert_state(&lsm, &[&[6], &[18]], 1);
+
+        // Break the checksums of every leaf and node, then reopen with a new buffer pool
+        for sst in lsm.ssts() {
+            let path = fs.path(sst.file_id);
+            let mut bytes = std::fs::read(&path)?;
+            for page in sst.btree_metadata.leafs_offset..sst.btree_metadata.bloom_offset {
+                bytes[page as usize * crate::PAGE_SIZE + 8] ^= 1;
+            }
+            std::fs::write(&path, bytes)?;
+        }
+        let fs = &FileSystem::new(
+            crate::test_util::get_path("lsm", "range_filter_pruning"),
+            16,
+            1,
+            1,
+        )?;
+        let lsm = LsmTree::open(lsm.metadata(), lsm.configuration, fs)?;
+
+        for start in [1500, 2500, 4100, 9990, 30_100] {
+            let scanned: Vec<(u64, u64)> =
+                lsm.scan(start..=start + 5, fs)?.collect::<Result<_, _>>()?;
+            assert_eq!(scanned, []);
+        }
+        let scanned: Result<Vec<(u64, u64)>, DbError> =
+            lsm.scan(3000..=3005, fs).and_then(|scan| scan.collect());
+        assert!(matches!(scanned, Err(DbError::Corruption { .. })));
+
+        Ok(())
+    }
+
+    /// Tests that with equal weights, bits are assigned like Monkey, within the total,
+    /// and that levels that are rarely probed get fewer bits.
+    #[test]
+    fn test_allocate_bits() {
+        let level_sizes = [1000, 3000, 9000];
+        let total_bits = (1000 * 10 + 3000 * 8 + 9000 * 6) as f64;
+
+        let bits = allocate_bits(&level_sizes, &[1.0; 3], total_bits);
+        // Monkey takes log2(3) / ln 2 = 2.3 bits away from each level below
+        assert_eq!(bits, [10, 8, 6]);
+
+        let bits = allocate_bits(&level_sizes, &[1000.0, 1000.0, 1.0], total_bits);
+        assert!(bits[0] > 10 && bits[1] > 8 && bits[2] < 6, "{bits:?}");
+        let used: usize = level_sizes.iter().zip(&bits).map(|(n, m)| n * m).sum();
+        assert!(
+            (used as f64 - total_bits).abs() <= 13_000.0 / 2.0,
+            "{bits:?}"
+        );
+
+        // A level that is never worth a bit gets none, and the others share the total
+        let bits = allocate_bits(&level_sizes, &[1e9, 1e9, 1e-9], total_bits);
+        assert_eq!(bits[2], 0);
+        assert!((2..=3).contains(&(bits[0] - bits[1])), "{bits:?}");
+
+        assert_eq!(allocate_bits(&[0, 10], &[1.0; 2], 0.0), [0, 0]);
+    }
+
+    /// Tests that lookups that keep missing the top level give its new SSTs more bits,
+    /// and that they are reported.
+    #[test]
+    fn test_workload_aware_bits() -> Result<()> {
+        let fs = &test_fs("workload_aware_bits");
+        let mut lsm = empty_lsm(fs)?;
+        lsm.configuration.workload_aware_bits = true;
+
+        // Keys spread out, so that both SSTs' key ranges cover every lookup
+        for i in 0..24 {
+            lsm.put(i * 7 % 24 * 10, i, fs)?;
+        }
+        assert_state(&lsm, &[&[6], &[18]], 1);
+        let report = lsm.filter_report();
+        assert_eq!(report.bits_per_entry, [5, 3]);
+        assert_eq!(report.entries_per_level, [6, 18]);
+        assert_eq!(report.filter_counts, [FilterCounts::default(); 2]);
+        assert!(report.expected_false_positive_rate > 0.0);
+
+        // Every lookup goes past the top level to find its key in the bottom level
+        for _ in 0..100 {
+            for i in 0..18 {
+                assert_eq!(lsm.get(i * 7 % 24 * 10, fs)?, Some(i));
+            }
+        }
+        let report = lsm.filter_report();
+        let top_counts = report.filter_counts[0];
+        assert!(top_counts.probes > 0);
+        assert_eq!(top_counts.negatives(), top_counts.probes);
+        assert_eq!(report.filter_counts[1].probes, 1800);
+        assert_eq!(report.filter_counts[1].negatives(), 0);
+        assert!(report.bits_per_entry[0] > 5, "{report:?}");
+        assert!(report.bits_per_entry[1] < 3, "{report:?}");
+
+        // The next SST at the top level is built with the new allocation
+        for i in 24..30 {
+            lsm.put(i, i, fs)?;
+        }
+        assert_state(&lsm, &[&[6, 6], &[18]], 1);
+        let [old, new] = &lsm.levels[0][..] else {
+            unreachable!()
+        };
+        assert!(new.btree_metadata.num_hashes > old.btree_metadata.num_hashes);
+
+        Ok(())
+    }
+
+    /// Tests that only the partitioned filters of the top levels are pinned,
+    /// including after SSTs move down a level.
+    #[test]
+    fn test_pinned_filter_levels() -> Result<()> {
+        let fs = &test_fs("pinned_filter_levels");
+        let mut lsm = empty_lsm(fs)?;
+        lsm.configuration.filter_type = FilterType::Partitioned;
+        lsm.configuration.pinned_filter_levels = 1;
+
+        let expected_bytes = |lsm: &LsmTree| {
+            // Each SST has one partition, and its index has one first key
+            let pinned = lsm.levels.first().map_or(0, Vec::len) * crate::PAGE_SIZE;
+            lsm.ssts().count() * 8 + pinned
+        };
+        for i in 0..60 {
+            lsm.put(i, i, fs)?;
+            assert_eq!(lsm.filter_bytes(), expected_bytes(&lsm));
+        }
+        assert!(lsm.levels.len() > 1);
+        for i in 0..60 {
+            assert_eq!(lsm.get(i, fs)?, Some(i));
+        }
+
+        Ok(())
+    }
+
+    /// Tests that a merge that drops overwritten entries sizes the filter by the entries it keeps.
+    #[test]
+    fn test_exact_filter_size() -> Result<()> {
+        let fs = &test_fs("exact_filter_size");
+        let mut lsm = empty_lsm(fs)?;
+
+        for i in 0..18 {
+            lsm.put(i % 6, i, fs)?;
+        }
+        assert_state(&lsm, &[&[], &[6]], 1);
+        let sst = &lsm.levels[1][0];
+        // Merged while the top level was the bottom level, from SSTs with 18 entries in all
+        let exact = BloomFilter::empty(6, lsm.monkey(0), false, HashType::Murmur, 0);
+        assert_eq!(
+            sst.btree_metadata.bloom_size,
+            exact.turn_to_bytes().len() as u64
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_full_delete() -> Result<()> {
         let fs = &test_fs("full_delete");
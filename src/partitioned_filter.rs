@@ -0,0 +1,291 @@
+use crate::{bloom_filter, file_system::PAGE_DATA_SIZE, filter::Filter, hash::HashType};
+
+/// The number of bytes in each partition, which fills the data part of one page.
+pub const PARTITION_SIZE: usize = PAGE_DATA_SIZE;
+/// The number of bits in each partition.
+const PARTITION_BITS: usize = PARTITION_SIZE * 8;
+/// The number of bytes in the index before the first keys: the seed and the number of partitions.
+const INDEX_HEADER_SIZE: usize = 16;
+
+/// The index of a partitioned filter, which tells which partition covers a key and how to probe it.
+/// It is small enough to keep in memory while the partitions themselves are read through the buffer pool.
+#[derive(Clone, Debug)]
+pub struct PartitionIndex {
+    hash: HashType,
+    seed: u64,
+    num_probes: usize,
+    /// The smallest key of each partition
+    first_keys: Vec<u64>,
+}
+
+impl PartitionIndex {
+    /// The number of bytes in the index of a filter, read from the first bytes of the filter,
+    /// or None if there aren't enough of them.
+    pub fn size(filter_bytes: &[u8]) -> Option<u64> {
+        let n_partitions = u64::from_le_bytes(filter_bytes.get(8..16)?.try_into().unwrap());
+        n_partitions
+            .checked_mul(8)?
+            .checked_add(INDEX_HEADER_SIZE as u64)
+    }
+
+    /// Reads the index from the start of the bytes of a filter with the given number of probes,
+    /// the given total size in bytes and the given hash algorithm.
+    ///
+    /// Returns None if the index isn't followed by exactly one page for each of its partitions,
+    /// or if its first keys aren't in order.
+    pub fn from_bytes(
+        index_bytes: &[u8],
+        num_probes: usize,
+        filter_size: u64,
+        hash: HashType,
+    ) -> Option<Self> {
+        let size = Self::size(index_bytes)?;
+        if (index_bytes.len() as u64) < size {
+            return None;
+        }
+        let (words, _) = index_bytes[..size as usize].as_chunks::<8>();
+        let mut words = words.iter().map(|&word| u64::from_le_bytes(word));
+        let seed = words.next()?;
+        words.next()?;
+        let index = Self {
+            hash,
+            seed,
+            num_probes,
+            first_keys: words.collect(),
+        };
+
+        let pages = index.index_pages() + index.first_keys.len() as u64;
+        let consistent = !index.first_keys.is_empty()
+            && index.first_keys.is_sorted()
+            && pages.checked_mul(PARTITION_SIZE as u64) == Some(filter_size);
+        consistent.then_some(index)
+    }
+
+    /// The bytes of the index, padded to whole pages.
+    fn turn_to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = [self.seed, self.first_keys.len() as u64]
+            .into_iter()
+            .chain(self.first_keys.iter().copied())
+            .flat_map(u64::to_le_bytes)
+            .collect();
+        bytes.resize(self.index_pages() as usize * PARTITION_SIZE, 0);
+        bytes
+    }
+
+    /// The number of pages that the index takes up before the first partition.
+    fn index_pages(&self) -> u64 {
+        (INDEX_HEADER_SIZE as u64 + self.first_keys.len() as u64 * 8)
+            .div_ceil(PARTITION_SIZE as u64)
+    }
+
+    /// The number of partitions.
+    pub fn n_partitions(&self) -> usize {
+        self.first_keys.len()
+    }
+
+    /// The partition that covers the given key: the last one whose first key is at most the key,
+    /// or the first one if there is none.
+    pub fn partition(&self, key: u64) -> usize {
+        self.first_keys
+            .partition_point(|&first_key| first_key <= key)
+            .saturating_sub(1)
+    }
+
+    /// The page that the given partition is on, counted from the first page of the filter.
+    pub fn partition_page(&self, partition: usize) -> u64 {
+        self.index_pages() + partition as u64
+    }
+
+    /// Returns false if the key is definitely not in the given partition, which must be the one that covers it.
+    pub fn query_partition(&self, key: u64, partition: &[u8]) -> bool {
+        probes(self.hash.hash64(key, self.seed), self.num_probes)
+            .all(|bit| partition[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    /// The number of bytes that the index takes up in memory.
+    pub fn memory_bytes(&self) -> usize {
+        self.first_keys.len() * size_of::<u64>()
+    }
+
+    /// The expected rate of false positives, given the number of keys that were inserted.
+    /// Partitions are treated as one bitmap, as for blocked bloom filters.
+    pub fn false_positive_rate(&self, n_entries: usize) -> f64 {
+        bloom_filter::false_positive_rate(
+            self.first_keys.len() * PARTITION_BITS,
+            self.num_probes,
+            n_entries,
+        )
+    }
+}
+
+/// A bloom filter partitioned by key range into partitions of one page each,
+/// so that probing a key touches a single page of the SST.
+///
+/// The keys are split evenly into runs of consecutive keys, as few as give every key the bits per entry,
+/// and each run's bits go to its own partition. The probes within a partition come from
+/// a single 64-bit hash of the key by double hashing, as in a blocked bloom filter.
+///
+/// Stored as the index, padded to whole pages, followed by the partitions, one page each.
+/// The index is the seed and the number of partitions as little-endian `u64`s,
+/// followed by the smallest key of each partition as a little-endian `u64`.
+/// Bit `i` of a partition is bit `i % 8` of its byte `i / 8`.
+/// The number of probes is recorded as `num_hashes` in SST metadata.
+#[derive(Clone, Debug)]
+pub struct PartitionedFilter {
+    index: PartitionIndex,
+    /// Every partition, one after the other
+    partitions: Vec<u8>,
+}
+
+impl PartitionedFilter {
+    /// Builds a filter from the given keys, which must be distinct and in increasing order,
+    /// with the given number of bits per entry, hashing them with the given algorithm and seed.
+    pub fn build(keys: &[u64], bits_per_entry: usize, hash: HashType, seed: u64) -> Self {
+        // Calculate how many probes it needs to be optimal: bits_per_entry * ln(2)
+        let num_probes = (bits_per_entry as f32 * f32::ln(2.0)).ceil() as usize;
+        // At least one partition, so that every key has a partition to go to,
+        // with the keys spread evenly so that the last partition isn't left mostly empty
+        let n_partitions = (keys.len() * bits_per_entry)
+            .div_ceil(PARTITION_BITS)
+            .max(1);
+        let keys_per_partition = keys.len().div_ceil(n_partitions).max(1);
+
+        let mut first_keys: Vec<u64> = keys.chunks(keys_per_partition).map(|run| run[0]).collect();
+        if first_keys.is_empty() {
+            first_keys.push(0);
+        }
+
+        let mut filter = Self {
+            index: PartitionIndex {
+                hash,
+                seed,
+                num_probes,
+                first_keys,
+            },
+            partitions: Vec::new(),
+        };
+        filter.partitions = vec![0; filter.index.n_partitions() * PARTITION_SIZE];
+        for (i, run) in keys.chunks(keys_per_partition).enumerate() {
+            let partition = &mut filter.partitions[i * PARTITION_SIZE..(i + 1) * PARTITION_SIZE];
+            for &key in run {
+                for bit in probes(hash.hash64(key, filter.index.seed), num_probes) {
+                    partition[bit / 8] |= 1 << (bit % 8);
+                }
+            }
+        }
+        filter
+    }
+
+    /// Reads a filter back from the bytes that `turn_to_bytes` returned, with the given number of probes
+    /// and hash algorithm. A filter whose index doesn't match its size matches every key.
+    pub fn from_bytes(filter_bytes: &[u8], num_probes: usize, hash: HashType) -> Self {
+        match PartitionIndex::from_bytes(filter_bytes, num_probes, filter_bytes.len() as u64, hash)
+        {
+            Some(index) => {
+                let partitions_offset = index.partition_page(0) as usize * PARTITION_SIZE;
+                Self {
+                    partitions: filter_bytes[partitions_offset..].to_vec(),
+                    index,
+                }
+            }
+            None => Self {
+                index: PartitionIndex {
+                    hash,
+                    seed: 0,
+                    num_probes: 0,
+                    first_keys: Vec::new(),
+                },
+                partitions: Vec::new(),
+            },
+        }
+    }
+}
+
+impl Filter for PartitionedFilter {
+    fn query(&self, key: u64) -> bool {
+        let partition = self.index.partition(key);
+        match self
+            .partitions
+            .get(partition * PARTITION_SIZE..(partition + 1) * PARTITION_SIZE)
+        {
+            Some(partition) => self.index.query_partition(key, partition),
+            None => true,
+        }
+    }
+
+    fn turn_to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.index.turn_to_bytes();
+        bytes.extend_from_slice(&self.partitions);
+        bytes
+    }
+
+    fn num_hashes(&self) -> usize {
+        self.index.num_probes
+    }
+
+    fn false_positive_rate(&self, n_entries: usize) -> f64 {
+        self.index.false_positive_rate(n_entries)
+    }
+}
+
+/// The bits within a partition that a hash sets, by double hashing:
+/// probe `i` is `h1 + i * h2`, scaled to the partition, where `h1` and `h2` are the halves of the hash.
+fn probes(hash: u64, num_probes: usize) -> impl Iterator<Item = usize> {
+    let h1 = hash as u32;
+    // Odd, so that the probes are spread over the whole partition
+    let h2 = (hash >> 32) as u32 | 1;
+    (0..num_probes as u32).map(move |i| {
+        let probe = h1.wrapping_add(i.wrapping_mul(h2));
+        ((probe as u64 * PARTITION_BITS as u64) >> 32) as usize
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that consecutive keys share a partition, that every partition fills a page after the index,
+    /// and that the index can be read back on its own.
+    #[test]
+    fn test_partitions() {
+        let keys: Vec<u64> = (0..10_000).map(|i| i * 2).collect();
+        let filter = PartitionedFilter::build(&keys, 10, HashType::U64Mixer, 0);
+        let n_partitions = (keys.len() * 10).div_ceil(PARTITION_BITS);
+        assert_eq!(filter.index.n_partitions(), n_partitions);
+        let keys_per_partition = keys.len().div_ceil(n_partitions);
+        assert_eq!(filter.index.partition(0), 0);
+        assert_eq!(filter.index.partition(keys[keys_per_partition] - 1), 0);
+        assert_eq!(filter.index.partition(keys[keys_per_partition]), 1);
+        assert_eq!(
+            filter.index.partition(u64::MAX),
+            filter.index.n_partitions() - 1
+        );
+
+        let bytes = filter.turn_to_bytes();
+        assert_eq!(
+            bytes.len(),
+            (1 + filter.index.n_partitions()) * PARTITION_SIZE
+        );
+        let index_size = PartitionIndex::size(&bytes).unwrap();
+        let index = PartitionIndex::from_bytes(
+            &bytes[..index_size as usize],
+            7,
+            bytes.len() as u64,
+            HashType::U64Mixer,
+        )
+        .unwrap();
+        for &key in &keys {
+            let partition = index.partition(key);
+            let offset = index.partition_page(partition) as usize * PARTITION_SIZE;
+            assert!(index.query_partition(key, &bytes[offset..offset + PARTITION_SIZE]));
+        }
+
+        // The index has to account for exactly the pages that follow it
+        let size = bytes.len() as u64;
+        assert!(PartitionIndex::from_bytes(&bytes, 7, size - 1, HashType::U64Mixer).is_none());
+        assert!(PartitionIndex::from_bytes(&bytes[..8], 7, size, HashType::U64Mixer).is_none());
+
+        let filter = PartitionedFilter::build(&[], 10, HashType::U64Mixer, 0);
+        assert_eq!(filter.turn_to_bytes().len(), 2 * PARTITION_SIZE);
+    }
+}
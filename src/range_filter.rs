@@ -0,0 +1,154 @@
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{bloom_filter::BloomFilter, filter::Filter, hash::HashType};
+
+/// The most buckets a range filter probes for one scan.
+/// Scans over more buckets than this are assumed to overlap the SST.
+pub const MAX_PROBES: u64 = 16;
+
+/// Configuration of the range filters of SSTs.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeFilterConfiguration {
+    /// The base-2 logarithm of the number of consecutive keys in each bucket.
+    /// Scans over up to 16 buckets can be ruled out, so this should fit the length of typical scans.
+    /// Must be less than 64.
+    pub bucket_bits: u32,
+    /// The number of bits per bucket that holds a key, for the bloom filter over the buckets.
+    pub bits_per_bucket: usize,
+}
+
+/// A prefix bloom filter over the keys of an SST, which answers whether a range might hold any of them.
+///
+/// Keys are grouped into buckets of `2^bucket_bits` consecutive keys,
+/// and the bucket of every key in the SST is inserted into a standard bloom filter.
+/// A scan probes the filter for each bucket it covers, so an empty short scan
+/// is usually ruled out without reading any page of the SST.
+///
+/// Stored the same way as a standard bloom filter over the buckets, which always hashes them with `HashType::Murmur`.
+#[derive(Clone, Debug)]
+pub struct RangeFilter {
+    bucket_bits: u32,
+    filter: BloomFilter,
+}
+
+impl RangeFilter {
+    /// Reads a range filter back from the bytes that `turn_to_bytes` returned.
+    pub fn from_bytes(filter_bytes: &[u8], num_hashes: usize, bucket_bits: u32) -> Self {
+        Self {
+            bucket_bits,
+            filter: BloomFilter::from_bytes(filter_bytes, num_hashes, false, HashType::Murmur),
+        }
+    }
+
+    /// Returns false if no key in the filter is in the given range.
+    /// May return true for ranges that hold none of them, and always does for ranges over too many buckets.
+    pub fn may_overlap(&self, range: &RangeInclusive<u64>) -> bool {
+        if range.is_empty() {
+            return false;
+        }
+        let first = range.start() >> self.bucket_bits;
+        let last = range.end() >> self.bucket_bits;
+        if last - first >= MAX_PROBES {
+            return true;
+        }
+        (first..=last).any(|bucket| self.filter.query(bucket))
+    }
+
+    /// Turns the filter into the bytes stored in the SST.
+    pub fn turn_to_bytes(&self) -> Vec<u8> {
+        self.filter.turn_to_bytes()
+    }
+
+    /// The number of hash functions of the bloom filter over the buckets.
+    pub fn num_hashes(&self) -> usize {
+        self.filter.num_hashes()
+    }
+
+    /// The base-2 logarithm of the number of keys in each bucket.
+    pub fn bucket_bits(&self) -> u32 {
+        self.bucket_bits
+    }
+}
+
+/// Builds the range filter of an SST from its keys, in increasing order, as they're written.
+pub struct RangeFilterBuilder {
+    configuration: RangeFilterConfiguration,
+    seed: u64,
+    /// The distinct buckets seen so far, so that the filter is sized by the number of buckets rather than keys
+    buckets: Vec<u64>,
+}
+
+impl RangeFilterBuilder {
+    /// Starts building a range filter with the given configuration, whose seeds derive from the given seed.
+    pub fn new(configuration: RangeFilterConfiguration, seed: u64) -> Self {
+        Self {
+            configuration,
+            seed,
+            buckets: Vec::new(),
+        }
+    }
+
+    /// Adds a key to the filter. Keys must be added in increasing order.
+    pub fn insert(&mut self, key: u64) {
+        let bucket = key >> self.configuration.bucket_bits;
+        if self.buckets.last() != Some(&bucket) {
+            self.buckets.push(bucket);
+        }
+    }
+
+    /// Finishes building the filter.
+    pub fn build(self) -> RangeFilter {
+        // At least one bucket, so that the bitmap isn't empty
+        let mut filter = BloomFilter::empty(
+            self.buckets.len().max(1),
+            self.configuration.bits_per_bucket,
+            false,
+            HashType::Murmur,
+            self.seed,
+        );
+        for bucket in self.buckets {
+            filter.insert(bucket);
+        }
+        RangeFilter {
+            bucket_bits: self.configuration.bucket_bits,
+            filter,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that ranges holding a key always overlap, that a round trip through bytes keeps the filter,
+    /// and that most short ranges between keys are ruled out.
+    #[test]
+    fn test_range_filter() {
+        let configuration = RangeFilterConfiguration {
+            bucket_bits: 4,
+            bits_per_bucket: 10,
+        };
+        let keys: Vec<u64> = (0..1000).map(|i| i * 1000).collect();
+        let mut builder = RangeFilterBuilder::new(configuration, 0);
+        for &key in &keys {
+            builder.insert(key);
+        }
+        let filter = builder.build();
+        let filter = RangeFilter::from_bytes(&filter.turn_to_bytes(), filter.num_hashes(), 4);
+
+        for &key in &keys {
+            assert!(filter.may_overlap(&(key..=key)));
+            assert!(filter.may_overlap(&(key.saturating_sub(50)..=key + 50)));
+        }
+        let false_positives = keys
+            .iter()
+            .filter(|&&key| filter.may_overlap(&(key + 100..=key + 150)))
+            .count();
+        assert!(false_positives < 80, "{false_positives}");
+
+        // Too wide to rule out, even though no key is in it
+        assert!(filter.may_overlap(&(1..=999)));
+    }
+}
@@ -1,10 +1,14 @@
-use std::ops::RangeInclusive;
+use std::{fmt::Debug, ops::RangeInclusive, sync::Arc};
 
 use crate::{
-    DbError,
-    bloom_filter::BloomFilter,
-    btree::{BTree, BTreeIter, BTreeMetadata},
-    file_system::{FileId, FileSystem},
+    DbError, PAGE_SIZE,
+    btree::{BTree, BTreeIter, BTreeMetadata, SearchStrategy, SstProperties},
+    compression::Compression,
+    file_system::{Aligned, FileId, FileSystem},
+    filter::{Filter, FilterStats, FilterType},
+    hash::HashType,
+    partitioned_filter::{PARTITION_SIZE, PartitionIndex},
+    range_filter::{RangeFilter, RangeFilterConfiguration},
 };
 
 /// A handle to an SST (Sorted String Table) file.
@@ -23,11 +27,21 @@ use crate::{
 /// │  - File offsets (metadata, leafs, nodes, bloom)     │
 /// │  - Tree depth and size information                  │
 /// │  - Entry count and bloom filter parameters          │
+/// │  - Properties: key range, tombstones, creation time │
 /// ├─────────────────────────────────────────────────────┤
-/// │ BloomFilter (in memory)                             │
+/// │ Key range (in memory)                               │
+/// │  - skips SSTs that can't hold a key or range        │
+/// ├─────────────────────────────────────────────────────┤
+/// │ Pinned internal nodes (in memory, optional)         │
+/// │  - B-tree nodes searched without the buffer pool    │
+/// ├─────────────────────────────────────────────────────┤
+/// │ Filter (in memory, or its index if partitioned)     │
 /// │  - checks if something exists in an sst             │
 /// │  - False positives possible, no false negatives     │
 /// ├─────────────────────────────────────────────────────┤
+/// │ Range filter (in memory, optional)                  │
+/// │  - checks if a short range may hold anything        │
+/// ├─────────────────────────────────────────────────────┤
 /// │ FileId                                              │
 /// │  - LSM level and SST number                         │
 /// │  - Used to locate the file on disk                  │
@@ -45,8 +59,52 @@ pub struct Sst {
     pub btree_metadata: BTreeMetadata,
     /// Identifier for locating the SST file on disk
     pub file_id: FileId,
-    /// In-memory bloom filter for quick negative lookups
-    pub filter: BloomFilter,
+    /// Bloom, xor, or partitioned filter for quick negative lookups
+    pub filter: SstFilter,
+    /// In-memory prefix bloom filter for quick empty scans, if the SST was written with one
+    pub range_filter: Option<RangeFilter>,
+    /// How the filter answered point lookups since the SST was opened
+    pub filter_stats: FilterStats,
+    /// The smallest and largest keys in the SST, empty if it has no entries.
+    /// Checked before the bloom filter or any page, so SSTs outside a lookup are never touched.
+    pub key_range: RangeInclusive<u64>,
+    /// How point lookups find the leaf page of a key, `SearchStrategy::BTree` unless set
+    pub search_strategy: SearchStrategy,
+    /// Copies of the internal node pages, from `nodes_offset` to `bloom_offset`, if they are pinned in memory
+    pinned_nodes: Option<PinnedPages>,
+}
+
+/// Pages of an SST kept in memory.
+pub struct PinnedPages(Vec<Arc<Aligned>>);
+
+impl Debug for PinnedPages {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} pinned pages", self.0.len())
+    }
+}
+
+/// The filter of an SST, as it is held in memory.
+#[derive(Debug)]
+pub enum SstFilter {
+    /// A bloom or xor filter, entirely in memory
+    InMemory(Box<dyn Filter>),
+    /// A partitioned filter, of which only the index is always in memory.
+    /// Its partitions are read through the buffer pool, unless they're pinned.
+    Partitioned {
+        index: PartitionIndex,
+        /// Copies of the partition pages, in order, if they are pinned in memory
+        pinned: Option<PinnedPages>,
+    },
+}
+
+impl SstFilter {
+    /// The expected rate of false positives, given the number of keys that were inserted.
+    pub fn false_positive_rate(&self, n_entries: usize) -> f64 {
+        match self {
+            Self::InMemory(filter) => filter.false_positive_rate(n_entries),
+            Self::Partitioned { index, .. } => index.false_positive_rate(n_entries),
+        }
+    }
 }
 
 impl Sst {
@@ -60,8 +118,12 @@ impl Sst {
     ///
     /// # Arguments
     /// * `key_values` - Iterator of (key, value) pairs. **Must be sorted by key.**
-    /// * `n_entries_hint` - Upper bound estimate of the number of entries (for bloom filter sizing)
     /// * `bits_per_entry` - Bits per entry in bloom filter (higher = fewer false positives)
+    /// * `filter_type` - Type of the filter
+    /// * `filter_hash` - Hash algorithm of the filter
+    /// * `range_filter` - Configuration of the range filter, or None for no range filter
+    /// * `compression` - Compression for the leaf pages
+    /// * `lsm_level` - LSM level the SST is written for, recorded in its properties
     /// * `file_id` - Identifier for the SST file (determines LSM level and file number)
     /// * `file_system` - File system to write the SST to
     ///
@@ -75,33 +137,52 @@ impl Sst {
     /// ```text
     /// let sst = Sst::create(
     ///     vec![(1, 100), (2, 200), (3, 300)].into_iter().map(Ok),
-    ///     3,           // hint: 3 entries
     ///     8,           // 8 bits per entry
+    ///     FilterType::Bloom,
+    ///     HashType::Murmur,
+    ///     None,        // no range filter
+    ///     Compression::None,
+    ///     0,           // written for level 0
     ///     file_id,
     ///     &mut fs,
     /// )?;
     /// ```
+    #[allow(clippy::too_many_arguments)]
     pub fn create(
         key_values: impl IntoIterator<Item = Result<(u64, u64), DbError>>,
-        n_entries_hint: usize,
         bits_per_entry: usize,
+        filter_type: FilterType,
+        filter_hash: HashType,
+        range_filter: Option<RangeFilterConfiguration>,
+        compression: Compression,
+        lsm_level: usize,
         file_id: FileId,
         file_system: &FileSystem,
     ) -> Result<Sst, DbError> {
         let key_values = key_values.into_iter();
 
-        let (btree_metadata, filter) = BTree::write(
+        let (btree_metadata, filter, range_filter) = BTree::write(
             file_id,
             key_values,
-            n_entries_hint,
             bits_per_entry,
+            filter_type,
+            filter_hash,
+            range_filter,
+            compression,
+            lsm_level,
             file_system,
         )?;
+        let key_range = Self::key_range(file_id, &btree_metadata, file_system)?;
 
         Ok(Sst {
             file_id,
             btree_metadata,
             filter,
+            range_filter,
+            filter_stats: FilterStats::default(),
+            key_range,
+            search_strategy: SearchStrategy::BTree,
+            pinned_nodes: None,
         })
     }
 
@@ -125,24 +206,190 @@ impl Sst {
     /// # Errors
     /// * `DbError::CorruptSst` - If the file has an invalid magic number or corrupted metadata
     pub fn open(file_id: FileId, file_system: &FileSystem) -> Result<Sst, DbError> {
-        let (btree_metadata, filter) = BTree::open(file_id, file_system)?;
+        let (btree_metadata, filter, range_filter) = BTree::open(file_id, file_system)?;
+        let key_range = Self::key_range(file_id, &btree_metadata, file_system)?;
 
         Ok(Sst {
             file_id,
             btree_metadata,
             filter,
+            range_filter,
+            filter_stats: FilterStats::default(),
+            key_range,
+            search_strategy: SearchStrategy::BTree,
+            pinned_nodes: None,
         })
     }
 
+    /// Returns the key range recorded in the SST's properties,
+    /// or reads it from the first and last leaves if the SST is older than the properties.
+    fn key_range(
+        file_id: FileId,
+        btree_metadata: &BTreeMetadata,
+        file_system: &FileSystem,
+    ) -> Result<RangeInclusive<u64>, DbError> {
+        match &btree_metadata.properties {
+            Some(properties) => Ok(properties.min_key..=properties.max_key),
+            None => BTree::read_key_range(file_id, btree_metadata, file_system),
+        }
+    }
+
+    /// Reads the internal node pages into memory, so that searches no longer read them
+    /// through the buffer pool. The pages are copied, so the buffer pool can still evict its own copies.
+    ///
+    /// # Errors
+    /// * `DbError` - If reading the pages fails
+    pub fn pin_nodes(&mut self, file_system: &FileSystem) -> Result<(), DbError> {
+        let node_pages = self.btree_metadata.nodes_offset..self.btree_metadata.bloom_offset;
+        self.pinned_nodes = Some(self.copy_pages(node_pages, file_system)?);
+        Ok(())
+    }
+
+    /// Reads the partitions of a partitioned filter into memory, so that queries no longer read them
+    /// through the buffer pool. Does nothing for other filters, which are always in memory.
+    ///
+    /// # Errors
+    /// * `DbError` - If reading the pages fails
+    pub fn pin_filter(&mut self, file_system: &FileSystem) -> Result<(), DbError> {
+        if let SstFilter::Partitioned {
+            index,
+            pinned: None,
+        } = &self.filter
+        {
+            let first_page = self.btree_metadata.bloom_offset + index.partition_page(0);
+            let pages = self.copy_pages(
+                first_page..first_page + index.n_partitions() as u64,
+                file_system,
+            )?;
+            if let SstFilter::Partitioned { pinned, .. } = &mut self.filter {
+                *pinned = Some(pages);
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the copies of the partitions of a partitioned filter, if they are pinned,
+    /// so that queries read them through the buffer pool again.
+    pub fn unpin_filter(&mut self) {
+        if let SstFilter::Partitioned { pinned, .. } = &mut self.filter {
+            *pinned = None;
+        }
+    }
+
+    /// Copies the given pages into memory, reading them through the buffer pool.
+    fn copy_pages(
+        &self,
+        page_numbers: std::ops::Range<u64>,
+        file_system: &FileSystem,
+    ) -> Result<PinnedPages, DbError> {
+        let pages = page_numbers
+            .map(|page_number| {
+                let page = file_system.get(self.file_id.page(page_number as usize))?;
+                Ok(Arc::new(*page))
+            })
+            .collect::<Result<_, DbError>>()?;
+        Ok(PinnedPages(pages))
+    }
+
+    /// Returns the number of bytes that the filter holds in memory:
+    /// the whole filter, or the index of a partitioned filter and its pinned partitions.
+    pub fn filter_bytes(&self) -> usize {
+        match &self.filter {
+            SstFilter::InMemory(_) => self.btree_metadata.bloom_size as usize,
+            SstFilter::Partitioned { index, pinned } => {
+                index.memory_bytes() + pinned.as_ref().map_or(0, |pages| pages.0.len() * PAGE_SIZE)
+            }
+        }
+    }
+
+    /// Returns false if the key is definitely not in the SST, according to its filter.
+    /// A partitioned filter reads the partition that covers the key, from memory if it's pinned
+    /// and through the buffer pool otherwise.
+    ///
+    /// # Errors
+    /// * `DbError` - If reading the partition fails
+    fn query_filter(&self, key: u64, file_system: &FileSystem) -> Result<bool, DbError> {
+        match &self.filter {
+            SstFilter::InMemory(filter) => Ok(filter.query(key)),
+            SstFilter::Partitioned { index, pinned } => {
+                let partition = index.partition(key);
+                let page = match pinned {
+                    Some(pages) => Arc::clone(&pages.0[partition]),
+                    None => {
+                        let page_number =
+                            self.btree_metadata.bloom_offset + index.partition_page(partition);
+                        file_system.get(self.file_id.page(page_number as usize))?
+                    }
+                };
+                Ok(index.query_partition(key, &page.0[..PARTITION_SIZE]))
+            }
+        }
+    }
+
+    /// Returns the number of bytes of internal node pages pinned in memory.
+    pub fn pinned_bytes(&self) -> usize {
+        self.pinned_nodes
+            .as_ref()
+            .map_or(0, |nodes| nodes.0.len() * PAGE_SIZE)
+    }
+
+    /// Returns the internal node page with the given page number,
+    /// from memory if the nodes are pinned and through the buffer pool otherwise.
+    ///
+    /// # Errors
+    /// * `DbError::CorruptSst` - If the page isn't an internal node page
+    pub fn node_page(
+        &self,
+        page_number: u64,
+        file_system: &FileSystem,
+    ) -> Result<Arc<Aligned>, DbError> {
+        let metadata = &self.btree_metadata;
+        if !(metadata.nodes_offset..metadata.bloom_offset).contains(&page_number) {
+            return Err(DbError::CorruptSst);
+        }
+        match &self.pinned_nodes {
+            Some(nodes) => Ok(Arc::clone(
+                &nodes.0[(page_number - metadata.nodes_offset) as usize],
+            )),
+            None => file_system.get(self.file_id.page(page_number as usize)),
+        }
+    }
+
+    /// Returns whether the SST may hold keys in the given range, judging by its key range alone.
+    pub fn overlaps(&self, range: &RangeInclusive<u64>) -> bool {
+        !range.is_empty()
+            && !self.key_range.is_empty()
+            && range.start() <= self.key_range.end()
+            && self.key_range.start() <= range.end()
+    }
+
+    /// Returns whether the SST may hold keys in the given range,
+    /// judging by its key range and then by its range filter, if it has one.
+    /// The range filter is only probed over the part of the range within the key range.
+    pub fn may_hold(&self, range: &RangeInclusive<u64>) -> bool {
+        if !self.overlaps(range) {
+            return false;
+        }
+        let Some(range_filter) = &self.range_filter else {
+            return true;
+        };
+        let start = *range.start().max(self.key_range.start());
+        let end = *range.end().min(self.key_range.end());
+        range_filter.may_overlap(&(start..=end))
+    }
+
     /// Retrieves the value associated with a key.
     ///
     /// # Process
-    /// 1. First checks the bloom filter - if it returns false, the key is definitely not present
-    /// 2. If bloom filter returns true, performs a B-tree search
-    /// 3. Returns the value if found, None otherwise
+    /// 1. First checks the key range - if the key is outside it, the key is definitely not present
+    /// 2. Then checks the bloom filter - if it returns false, the key is definitely not present
+    /// 3. If bloom filter returns true, performs a B-tree search
+    /// 4. Returns the value if found, None otherwise
+    ///
+    /// Lookups that reach the bloom filter are counted in `filter_stats`.
     ///
     /// # Performance
-    /// The bloom filter allows us to avoid expensive disk I/O for keys that don't exist
+    /// The key range and the bloom filter allow us to avoid expensive disk I/O for keys that don't exist
     /// in this SST, making negative lookups very fast.
     ///
     /// # Arguments
@@ -156,13 +403,21 @@ impl Sst {
     /// # Errors
     /// * `DbError` - If reading pages from disk fails
     pub fn get(&self, key: u64, file_system: &FileSystem) -> Result<Option<u64>, DbError> {
+        // Key range check: keys outside the SST's range are never in it
+        if !self.key_range.contains(&key) {
+            return Ok(None);
+        }
+
         // Bloom filter check: fast negative lookup
-        if !self.filter.query(key) {
+        if !self.query_filter(key, file_system)? {
+            self.filter_stats.record(false, false);
             return Ok(None);
         }
 
         // Bloom filter says "maybe present" - do actual tree search
-        BTree::get(self, key, file_system)
+        let value = BTree::get(self, key, file_system)?;
+        self.filter_stats.record(true, value.is_some());
+        Ok(value)
     }
 
     /// Creates an iterator for scanning a range of keys.
@@ -202,6 +457,23 @@ impl Sst {
         self.btree_metadata.n_entries as usize
     }
 
+    /// Returns the rate of false positives that the SST's filter should give, for its number of entries,
+    /// to compare against the rate observed in `filter_stats`.
+    pub fn expected_false_positive_rate(&self) -> f64 {
+        self.filter.false_positive_rate(self.num_entries())
+    }
+
+    /// Returns the format version that the SST was written with.
+    pub fn format_version(&self) -> u64 {
+        self.btree_metadata.version
+    }
+
+    /// Returns the key range and statistics recorded when the SST was written,
+    /// or None if it was written before format version 4.
+    pub fn properties(&self) -> Option<&SstProperties> {
+        self.btree_metadata.properties.as_ref()
+    }
+
     /// Destroys the SST and its associated file.
     pub fn destroy(self, file_system: &FileSystem) -> Result<(), DbError> {
         file_system.delete_file(self.file_id)?;
@@ -222,7 +494,12 @@ impl Sst {
 mod tests {
     use anyhow::Result;
 
-    use crate::test_util::TestFs;
+    use crate::{
+        PAGE_SIZE,
+        file_system::PAGE_DATA_SIZE,
+        hash::{MurmurHash, SeedSource},
+        test_util::{TestFs, TestPath, get_path},
+    };
 
     use super::*;
 
@@ -242,13 +519,736 @@ mod tests {
             sst_number: 14,
         };
 
-        Sst::create(vec![], 1, 1, file_id, fs)?;
+        Sst::create(
+            vec![],
+            1,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
+            file_id,
+            fs,
+        )?;
+
+        assert!(matches!(Sst::open(file_id, fs), Err(DbError::CorruptSst)));
+
+        Ok(())
+    }
+
+    /// Tests that a flipped bit in a leaf page is detected rather than returning a wrong value.
+    #[test]
+    fn test_detect_flipped_bit() -> Result<()> {
+        let fs = &test_fs("detect_flipped_bit");
+
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+
+        let sst = Sst::create(
+            (0..1000).map(|i| Ok((i, i * 2))),
+            8,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
+            file_id,
+            fs,
+        )?;
+        assert_eq!(sst.get(500, fs)?, Some(1000));
+
+        // Leaf pages start at page 1, after the metadata page
+        let dir = get_path("sst", "detect_flipped_bit");
+        let path = dir.join(file_id.name());
+        let mut bytes = std::fs::read(&path)?;
+        bytes[PAGE_SIZE + 100] ^= 1;
+        std::fs::write(&path, bytes)?;
+
+        let fs = &FileSystem::new(dir, 16, 1, 1)?;
+        let sst = Sst::open(file_id, fs)?;
+        assert_eq!(
+            sst.get(1, fs),
+            Err(DbError::Corruption {
+                file: file_id.name(),
+                page: 1,
+            })
+        );
+
+        Ok(())
+    }
+
+    /// Tests that SSTs are written byte for byte like `test_data/golden/data-lsm0-sst0`,
+    /// which locks the little-endian layout of every page, and that the golden file can be read back.
+    /// Run with `BEARR_UPDATE_GOLDEN` set to regenerate it after an intended format change.
+    #[test]
+    fn test_golden_file() -> Result<()> {
+        let path = TestPath::create("sst", "golden_file");
+        std::fs::create_dir_all(&path)?;
+        let fs = &FileSystem::with_seed(&path, 16, 1, 1, Some(0xBEA22))?;
+
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+
+        Sst::create(
+            (0..300).map(|i| Ok((i * 3, i + (1 << 40)))),
+            8,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
+            file_id,
+            fs,
+        )?;
+
+        let mut bytes = std::fs::read(get_path("sst", "golden_file").join(file_id.name()))?;
+        let golden_path = std::path::Path::new("test_data/golden").join(file_id.name());
+        if std::env::var_os("BEARR_UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden_path, &bytes)?;
+        }
+        let golden = std::fs::read(&golden_path)?;
+
+        // The creation time differs between runs, so take the golden file's, and update the checksum
+        let created_at = 152;
+        bytes[created_at..created_at + 8].copy_from_slice(&golden[created_at..created_at + 8]);
+        let checksum = MurmurHash::hash32(&bytes[..PAGE_SIZE - 4], 0);
+        bytes[PAGE_SIZE - 4..PAGE_SIZE].copy_from_slice(&checksum.to_le_bytes());
+
+        assert_eq!(bytes.len(), golden.len());
+        if let Some(offset) = (0..bytes.len()).find(|&i| bytes[i] != golden[i]) {
+            panic!("SST differs from the golden file at byte {offset}");
+        }
+
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(golden[offset..offset + 8].try_into().unwrap());
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(golden[offset..offset + 4].try_into().unwrap());
+
+        // Metadata page: magic, offsets, depth, size, bloom size and hashes, entries, version, compression
+        assert_eq!(u64_at(0), crate::btree::BEAR_MAGIC);
+        let [leafs_offset, nodes_offset, bloom_offset, tree_depth, size] =
+            [8, 16, 24, 32, 40].map(u64_at);
+        assert_eq!((leafs_offset, nodes_offset, bloom_offset), (1, 3, 4));
+        assert_eq!((tree_depth, size), (1, 5));
+        assert_eq!(golden.len(), 5 * PAGE_SIZE);
+        assert_eq!(u64_at(48), 6 * 4 + 2400 / 8);
+        assert_eq!(u64_at(56), 6);
+        assert_eq!(u64_at(64), 300);
+        assert_eq!(u64_at(72), crate::FORMAT_VERSION);
+        assert_eq!(u64_at(80), Compression::None.tag());
+
+        // Properties: their number, then (tag, value) pairs for the smallest and largest keys,
+        // tombstones, creation time, origin level, filter type, and filter hash
+        assert_eq!(u64_at(88), 7);
+        let properties =
+            [96, 112, 128, 160, 176, 192].map(|offset| [u64_at(offset), u64_at(offset + 8)]);
+        assert_eq!(
+            properties,
+            [[1, 0], [2, 897], [3, 0], [5, 0], [6, 0], [10, 0]]
+        );
+        assert_eq!(u64_at(created_at - 8), 4);
+        assert!(u64_at(created_at) > 0);
+
+        // Leaves: the number of pairs, then keys and values
+        assert_eq!(u64_at(PAGE_SIZE), 255);
+        assert_eq!(u64_at(PAGE_SIZE + 8), 0);
+        assert_eq!(u64_at(PAGE_SIZE + 16), 1 << 40);
+        assert_eq!(u64_at(PAGE_SIZE + 8 + 254 * 16), 254 * 3);
+        assert_eq!(u64_at(2 * PAGE_SIZE), 45);
+
+        // Internal node: the largest key of each leaf and the leaf's number
+        assert_eq!(u64_at(3 * PAGE_SIZE), 2);
+        assert_eq!(
+            [8, 16, 24, 32].map(|i| u64_at(3 * PAGE_SIZE + i)),
+            [762, 0, 897, 1]
+        );
+
+        // Bloom filter: the hash function seeds, then the bitmap.
+        // The filter takes the third seed of the file system, after its buffer pool and eviction handler.
+        let seeds = SeedSource::new(Some(0xBEA22));
+        let filter_seed = [seeds.next(), seeds.next(), seeds.next()][2];
+        assert_eq!(
+            u32_at(4 * PAGE_SIZE),
+            fastrand::Rng::with_seed(filter_seed).u32(..)
+        );
+
+        // Every page ends with its checksum
+        let checksum = MurmurHash::hash32(&golden[PAGE_SIZE..2 * PAGE_SIZE - 4], 1);
+        assert_eq!(u32_at(2 * PAGE_SIZE - 4), checksum);
+
+        let dir = TestPath::create("sst", "golden_file_read");
+        std::fs::create_dir_all(&dir)?;
+        std::fs::copy(&golden_path, dir.as_ref().join(file_id.name()))?;
+        let fs = &FileSystem::new(&dir, 16, 1, 1)?;
+        let sst = Sst::open(file_id, fs)?;
+        assert_eq!(sst.num_entries(), 300);
+        for i in 0..300 {
+            assert_eq!(sst.get(i * 3, fs)?, Some(i + (1 << 40)));
+            assert_eq!(sst.get(i * 3 + 1, fs)?, None);
+        }
+
+        Ok(())
+    }
+
+    /// Tests that SSTs written by a newer version of BEARR are rejected.
+    #[test]
+    fn test_newer_format_version() -> Result<()> {
+        let fs = &test_fs("newer_format_version");
+
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+
+        Sst::create(
+            (0..10).map(|i| Ok((i, i))),
+            8,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
+            file_id,
+            fs,
+        )?;
+
+        // The version is the tenth field of the metadata
+        let version_offset = 72;
+        let path = get_path("sst", "newer_format_version").join(file_id.name());
+        let mut bytes = std::fs::read(&path)?;
+        let mut page = crate::file_system::Aligned([0; PAGE_SIZE]);
+        page.0.copy_from_slice(&bytes[..PAGE_SIZE]);
+        page.0[version_offset..version_offset + 8]
+            .copy_from_slice(&(crate::FORMAT_VERSION + 1).to_le_bytes());
+        page.stamp_checksum(0);
+        bytes[..PAGE_SIZE].copy_from_slice(&page.0);
+        std::fs::write(&path, bytes)?;
+
+        let fs = &FileSystem::new(get_path("sst", "newer_format_version"), 16, 1, 1)?;
+        assert_eq!(
+            Sst::open(file_id, fs).err(),
+            Some(DbError::UnsupportedFormatVersion(crate::FORMAT_VERSION + 1))
+        );
+
+        Ok(())
+    }
+
+    /// Tests that the key range, tombstone count, creation time and origin level are recorded.
+    #[test]
+    fn test_properties() -> Result<()> {
+        let fs = &test_fs("properties");
+
+        let file_id = FileId {
+            lsm_level: 3,
+            sst_number: 0,
+        };
+
+        let before = std::time::SystemTime::now();
+        let pairs = (0..1000).map(|i| {
+            let value = if i % 4 == 0 { crate::lsm::TOMBSTONE } else { i };
+            Ok((100 + i * 7, value))
+        });
+        let sst = Sst::create(
+            pairs,
+            8,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            2,
+            file_id,
+            fs,
+        )?;
+        let properties = *sst.properties().unwrap();
+        assert_eq!(properties.min_key, 100);
+        assert_eq!(properties.max_key, 100 + 999 * 7);
+        assert_eq!(properties.n_tombstones, 250);
+        assert_eq!(properties.origin_level, 2);
+        let created_at =
+            std::time::UNIX_EPOCH + std::time::Duration::from_micros(properties.created_at);
+        assert!(created_at >= before - std::time::Duration::from_secs(1));
+        assert!(created_at <= std::time::SystemTime::now());
+
+        let fs = &FileSystem::new(get_path("sst", "properties"), 16, 1, 1)?;
+        assert_eq!(Sst::open(file_id, fs)?.properties(), Some(&properties));
+
+        // Missing properties are rejected, since version 4 SSTs always have them
+        let path = get_path("sst", "properties").join(file_id.name());
+        let mut bytes = std::fs::read(&path)?;
+        let mut page = crate::file_system::Aligned([0; PAGE_SIZE]);
+        page.0.copy_from_slice(&bytes[..PAGE_SIZE]);
+        page.0[88..96].copy_from_slice(&4u64.to_le_bytes());
+        page.stamp_checksum(0);
+        bytes[..PAGE_SIZE].copy_from_slice(&page.0);
+        std::fs::write(&path, bytes)?;
+
+        let fs = &FileSystem::new(get_path("sst", "properties"), 16, 1, 1)?;
+        assert_eq!(Sst::open(file_id, fs).err(), Some(DbError::CorruptSst));
+
+        Ok(())
+    }
+
+    /// Tests that gets outside the key range of an SST don't read any of its pages.
+    #[test]
+    fn test_key_range() -> Result<()> {
+        let fs = &test_fs("key_range");
+
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+        let pairs = (0..1000).map(|i| Ok((1000 + i * 2, i)));
+        let sst = Sst::create(
+            pairs,
+            8,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
+            file_id,
+            fs,
+        )?;
+        assert_eq!(sst.key_range, 1000..=2998);
+        assert!(sst.overlaps(&(0..=1000)));
+        assert!(sst.overlaps(&(2000..=u64::MAX)));
+        assert!(!sst.overlaps(&(0..=999)));
+        assert!(!sst.overlaps(&(2999..=u64::MAX)));
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 2000..=1000;
+        assert!(!sst.overlaps(&empty));
+
+        // Open with a new buffer pool, then break the checksums of every leaf and node
+        let fs = &FileSystem::new(get_path("sst", "key_range"), 16, 1, 1)?;
+        let sst = Sst::open(file_id, fs)?;
+        assert_eq!(sst.key_range, 1000..=2998);
+        let path = get_path("sst", "key_range").join(file_id.name());
+        let mut bytes = std::fs::read(&path)?;
+        for page in sst.btree_metadata.leafs_offset..sst.btree_metadata.bloom_offset {
+            bytes[page as usize * PAGE_SIZE + 8] ^= 1;
+        }
+        std::fs::write(&path, bytes)?;
+
+        for key in [0, 999, 2999, u64::MAX] {
+            assert_eq!(sst.get(key, fs)?, None);
+        }
+        assert!(matches!(sst.get(1000, fs), Err(DbError::Corruption { .. })));
+
+        Ok(())
+    }
+
+    /// Tests that searches through pinned internal nodes only read leaves from the file system.
+    #[test]
+    fn test_pinned_nodes() -> Result<()> {
+        let fs = &test_fs("pinned_nodes");
+
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+        // Enough leaves for two levels of internal nodes
+        let pairs = (0..100_000).map(|i| Ok((i * 2, i)));
+        Sst::create(
+            pairs,
+            8,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
+            file_id,
+            fs,
+        )?;
+
+        let fs = &FileSystem::new(get_path("sst", "pinned_nodes"), 16, 1, 1)?;
+        let unpinned = Sst::open(file_id, fs)?;
+        let mut sst = Sst::open(file_id, fs)?;
+        assert_eq!(sst.btree_metadata.tree_depth, 2);
+        assert_eq!(sst.pinned_bytes(), 0);
+        sst.pin_nodes(fs)?;
+        let node_pages = sst.btree_metadata.bloom_offset - sst.btree_metadata.nodes_offset;
+        assert_eq!(sst.pinned_bytes(), node_pages as usize * PAGE_SIZE);
 
+        // Break the checksums of the internal nodes, on disk and with a new buffer pool
+        let path = get_path("sst", "pinned_nodes").join(file_id.name());
+        let mut bytes = std::fs::read(&path)?;
+        for page in sst.btree_metadata.nodes_offset..sst.btree_metadata.bloom_offset {
+            bytes[page as usize * PAGE_SIZE + 8] ^= 1;
+        }
+        std::fs::write(&path, bytes)?;
+        let fs = &FileSystem::new(get_path("sst", "pinned_nodes"), 16, 1, 1)?;
+
+        for i in (0..100_000).step_by(997) {
+            assert_eq!(sst.get(i * 2, fs)?, Some(i));
+            assert_eq!(sst.get(i * 2 + 1, fs)?, None);
+        }
+        assert!(matches!(
+            unpinned.get(0, fs),
+            Err(DbError::Corruption { .. })
+        ));
+
+        Ok(())
+    }
+
+    /// Tests that SSTs record the hash algorithm of their filter and read it back,
+    /// and that an unknown one makes the SST corrupt.
+    #[test]
+    fn test_filter_hash() -> Result<()> {
+        let fs = &test_fs("filter_hash");
+
+        for (sst_number, filter_type) in
+            [FilterType::Bloom, FilterType::Xor].into_iter().enumerate()
+        {
+            let file_id = FileId {
+                lsm_level: 0,
+                sst_number,
+            };
+            let pairs = (0..1000).map(|i| Ok((i * 2, i)));
+            Sst::create(
+                pairs,
+                10,
+                filter_type,
+                HashType::XxHash64,
+                None,
+                Compression::None,
+                0,
+                file_id,
+                fs,
+            )?;
+
+            let fs = &FileSystem::new(get_path("sst", "filter_hash"), 16, 1, 1)?;
+            let sst = Sst::open(file_id, fs)?;
+            assert_eq!(sst.btree_metadata.filter_hash(), HashType::XxHash64);
+            for i in 0..1000 {
+                assert_eq!(sst.get(i * 2, fs)?, Some(i));
+            }
+            let false_positives = (0..1000)
+                .filter(|i| sst.query_filter(i * 2 + 1, fs).unwrap())
+                .count();
+            assert!(false_positives < 30, "{false_positives}");
+        }
+
+        // The hash algorithm comes right after the filter type
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+        let path = get_path("sst", "filter_hash").join(file_id.name());
+        let mut bytes = std::fs::read(&path)?;
+        let filter_hash = 176 + 24;
+        assert_eq!(bytes[filter_hash - 8], 10);
+        assert_eq!(bytes[filter_hash], 2);
+        bytes[filter_hash] = 4;
+        let checksum = MurmurHash::hash32(&bytes[..PAGE_SIZE - 4], 0);
+        bytes[PAGE_SIZE - 4..PAGE_SIZE].copy_from_slice(&checksum.to_le_bytes());
+        std::fs::write(&path, bytes)?;
+        let fs = &FileSystem::new(get_path("sst", "filter_hash"), 16, 1, 1)?;
         assert!(matches!(Sst::open(file_id, fs), Err(DbError::CorruptSst)));
 
         Ok(())
     }
 
+    /// Tests that file systems with the same seed write identical filters, for every filter type,
+    /// and that a different seed gives different ones.
+    #[test]
+    fn test_seeded_filters() -> Result<()> {
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+
+        for filter_type in [FilterType::Bloom, FilterType::Xor, FilterType::Partitioned] {
+            let filter_pages = |name: &str, seed: u64| -> Result<Vec<u8>> {
+                let path = TestPath::create("sst", name);
+                std::fs::create_dir_all(&path)?;
+                let fs = &FileSystem::with_seed(&path, 16, 1, 1, Some(seed))?;
+                Sst::create(
+                    (0..1000).map(|i| Ok((i * 2, i))),
+                    10,
+                    filter_type,
+                    filter_type.default_hash(),
+                    None,
+                    Compression::None,
+                    0,
+                    file_id,
+                    fs,
+                )?;
+                // Skip the metadata page, which holds the creation time
+                let bytes = std::fs::read(path.as_ref().join(file_id.name()))?;
+                Ok(bytes[PAGE_SIZE..].to_vec())
+            };
+
+            let first = filter_pages("seeded_filters_1", 1)?;
+            assert_eq!(first, filter_pages("seeded_filters_2", 1)?);
+            assert_ne!(first, filter_pages("seeded_filters_3", 2)?);
+        }
+
+        Ok(())
+    }
+
+    /// Tests that partitioned filters keep only their index in memory,
+    /// read the partitions through the buffer pool until they're pinned, and read them back.
+    #[test]
+    fn test_partitioned_filter() -> Result<()> {
+        let fs = &test_fs("partitioned_filter");
+
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+        let pairs = (0..10_000).map(|i| Ok((i * 2, i)));
+        Sst::create(
+            pairs,
+            10,
+            FilterType::Partitioned,
+            HashType::U64Mixer,
+            None,
+            Compression::None,
+            0,
+            file_id,
+            fs,
+        )?;
+
+        let fs = &FileSystem::new(get_path("sst", "partitioned_filter"), 16, 1, 1)?;
+        let unpinned = Sst::open(file_id, fs)?;
+        let mut sst = Sst::open(file_id, fs)?;
+        assert_eq!(sst.btree_metadata.filter_type(), FilterType::Partitioned);
+        assert_eq!(sst.btree_metadata.num_hashes, 7);
+        // 10 bits per entry fill 4 partitions, after one page of index
+        let n_partitions = (10_000 * 10_usize).div_ceil(PARTITION_SIZE * 8);
+        assert_eq!(
+            sst.btree_metadata.bloom_size,
+            ((1 + n_partitions) * PARTITION_SIZE) as u64
+        );
+        assert_eq!(sst.filter_bytes(), n_partitions * 8);
+
+        for i in 0..10_000 {
+            assert!(sst.query_filter(i * 2, fs)?);
+        }
+        let false_positives = (0..10_000)
+            .filter(|i| sst.query_filter(i * 2 + 1, fs).unwrap())
+            .count();
+        assert!(false_positives < 200, "{false_positives}");
+
+        sst.pin_filter(fs)?;
+        assert_eq!(sst.filter_bytes(), n_partitions * (8 + PAGE_SIZE));
+
+        // Break the checksums of the partitions, on disk and with a new buffer pool
+        let path = get_path("sst", "partitioned_filter").join(file_id.name());
+        let mut bytes = std::fs::read(&path)?;
+        let first_partition = sst.btree_metadata.bloom_offset + 1;
+        for page in first_partition..first_partition + n_partitions as u64 {
+            bytes[page as usize * PAGE_SIZE + 8] ^= 1;
+        }
+        std::fs::write(&path, bytes)?;
+        let fs = &FileSystem::new(get_path("sst", "partitioned_filter"), 16, 1, 1)?;
+
+        for i in (0..10_000).step_by(37) {
+            assert_eq!(sst.get(i * 2, fs)?, Some(i));
+        }
+        assert!(matches!(
+            unpinned.get(0, fs),
+            Err(DbError::Corruption { .. })
+        ));
+        sst.unpin_filter();
+        assert_eq!(sst.filter_bytes(), n_partitions * 8);
+        assert!(matches!(sst.get(0, fs), Err(DbError::Corruption { .. })));
+
+        Ok(())
+    }
+
+    /// Tests that SSTs with blocked bloom filters record their filter type and read it back.
+    #[test]
+    fn test_blocked_filter() -> Result<()> {
+        let fs = &test_fs("blocked_filter");
+
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+        let pairs = (0..10_000).map(|i| Ok((i * 2, i)));
+        let sst = Sst::create(
+            pairs,
+            10,
+            FilterType::Blocked,
+            HashType::U64Mixer,
+            None,
+            Compression::None,
+            0,
+            file_id,
+            fs,
+        )?;
+        assert_eq!(sst.btree_metadata.filter_type(), FilterType::Blocked);
+        assert_eq!(sst.btree_metadata.num_hashes, 7);
+        assert_eq!(
+            sst.btree_metadata.bloom_size,
+            4 + (10_000 * 10_u64).div_ceil(512) * 64
+        );
+
+        let fs = &FileSystem::new(get_path("sst", "blocked_filter"), 16, 1, 1)?;
+        let sst = Sst::open(file_id, fs)?;
+        assert_eq!(sst.btree_metadata.filter_type(), FilterType::Blocked);
+        for i in 0..10_000 {
+            assert!(sst.query_filter(i * 2, fs)?);
+        }
+        let false_positives = (0..10_000)
+            .filter(|i| sst.query_filter(i * 2 + 1, fs).unwrap())
+            .count();
+        assert!(false_positives < 200, "{false_positives}");
+        for i in (0..10_000).step_by(37) {
+            assert_eq!(sst.get(i * 2, fs)?, Some(i));
+            assert_eq!(sst.get(i * 2 + 1, fs)?, None);
+        }
+
+        // An unknown filter type makes the SST corrupt
+        let path = get_path("sst", "blocked_filter").join(file_id.name());
+        let mut bytes = std::fs::read(&path)?;
+        let filter_type = 176 + 8;
+        assert_eq!(bytes[filter_type], 1);
+        bytes[filter_type] = 2;
+        let checksum = MurmurHash::hash32(&bytes[..PAGE_SIZE - 4], 0);
+        bytes[PAGE_SIZE - 4..PAGE_SIZE].copy_from_slice(&checksum.to_le_bytes());
+        std::fs::write(&path, bytes)?;
+        let fs = &FileSystem::new(get_path("sst", "blocked_filter"), 16, 1, 1)?;
+        assert!(matches!(Sst::open(file_id, fs), Err(DbError::CorruptSst)));
+
+        Ok(())
+    }
+
+    /// Tests that SSTs with range filters read them back, and that the range filter rules out
+    /// most short ranges between keys but never a range that holds a key.
+    #[test]
+    fn test_range_filter() -> Result<()> {
+        let fs = &test_fs("range_filter");
+
+        let file_id = FileId {
+            lsm_level: 0,
+            sst_number: 0,
+        };
+        let range_filter = RangeFilterConfiguration {
+            bucket_bits: 4,
+            bits_per_bucket: 10,
+        };
+        let pairs = (0..10_000).map(|i| Ok((i * 1000, i)));
+        let sst = Sst::create(
+            pairs,
+            10,
+            FilterType::Bloom,
+            HashType::Murmur,
+            Some(range_filter),
+            Compression::None,
+            0,
+            file_id,
+            fs,
+        )?;
+        let metadata = sst.btree_metadata.range_filter.unwrap();
+        assert_eq!(metadata.bucket_bits, 4);
+        assert_eq!(
+            sst.btree_metadata.size,
+            sst.btree_metadata.range_filter_offset()
+                + metadata.size.div_ceil(PAGE_DATA_SIZE as u64)
+        );
+
+        let fs = &FileSystem::new(get_path("sst", "range_filter"), 16, 1, 1)?;
+        let sst = Sst::open(file_id, fs)?;
+        assert_eq!(sst.btree_metadata.range_filter, Some(metadata));
+        for i in 0..10_000 {
+            assert!(sst.may_hold(&(i * 1000..=i * 1000 + 10)));
+        }
+        let false_positives = (0..10_000)
+            .filter(|i| sst.may_hold(&(i * 1000 + 100..=i * 1000 + 150)))
+            .count();
+        assert!(false_positives < 800, "{false_positives}");
+        assert_eq!(sst.scan(100..=150, fs)?.count(), 0);
+        // Ranges outside the key range are ruled out without the filter, and wide ranges are never ruled out
+        assert!(!sst.may_hold(&(10_000_000..=u64::MAX)));
+        assert!(sst.may_hold(&(1..=999)));
+
+        // A bucket width that doesn't fit in a key makes the SST corrupt
+        let path = get_path("sst", "range_filter").join(file_id.name());
+        let mut bytes = std::fs::read(&path)?;
+        let bucket_bits = 240 + 8;
+        assert_eq!(bytes[bucket_bits], 4);
+        bytes[bucket_bits] = 64;
+        let checksum = MurmurHash::hash32(&bytes[..PAGE_SIZE - 4], 0);
+        bytes[PAGE_SIZE - 4..PAGE_SIZE].copy_from_slice(&checksum.to_le_bytes());
+        std::fs::write(&path, bytes)?;
+        let fs = &FileSystem::new(get_path("sst", "range_filter"), 16, 1, 1)?;
+        assert!(matches!(Sst::open(file_id, fs), Err(DbError::CorruptSst)));
+
+        Ok(())
+    }
+
+    /// Tests that every search strategy finds the same pairs,
+    /// on evenly spread keys and on skewed keys that interpolation guesses badly.
+    #[test]
+    fn test_search_strategies() -> Result<()> {
+        let fs = &test_fs("search_strategies");
+
+        let even: Vec<(u64, u64)> = (0..50_000).map(|i| (i * 3, i)).collect();
+        // Dense keys followed by a few far apart ones, up to near u64::MAX
+        let skewed: Vec<(u64, u64)> = (0..50_000)
+            .map(|i| (i, i))
+            .chain((16..64).map(|i| ((1 << i) + 60_000, i)))
+            .collect();
+        let single = vec![(42, 1)];
+
+        let cases = [
+            (&even, Compression::None),
+            (&skewed, Compression::None),
+            (&skewed, Compression::DeltaKeys),
+            (&single, Compression::None),
+        ];
+        for (sst_number, (pairs, compression)) in cases.into_iter().enumerate() {
+            let file_id = FileId {
+                lsm_level: 0,
+                sst_number,
+            };
+            let key_values = pairs.iter().copied().map(Ok);
+            let mut sst = Sst::create(
+                key_values,
+                8,
+                FilterType::Bloom,
+                HashType::Murmur,
+                None,
+                compression,
+                0,
+                file_id,
+                fs,
+            )?;
+
+            for strategy in [
+                SearchStrategy::BTree,
+                SearchStrategy::Binary,
+                SearchStrategy::Interpolation,
+            ] {
+                sst.search_strategy = strategy;
+                for &(key, value) in pairs.iter().step_by(89).chain(pairs.last()) {
+                    assert_eq!(sst.get(key, fs)?, Some(value), "{strategy:?}");
+                    let next = pairs.binary_search_by_key(&(key + 1), |x| x.0).is_ok();
+                    assert_eq!(sst.get(key + 1, fs)?.is_some(), next, "{strategy:?}");
+                }
+                assert_eq!(sst.get(u64::MAX, fs)?, None);
+
+                let start = pairs[pairs.len() / 2].0 + 1;
+                let scanned: Vec<(u64, u64)> =
+                    sst.scan(start..=u64::MAX, fs)?.collect::<Result<_, _>>()?;
+                let expected: Vec<(u64, u64)> = pairs
+                    .iter()
+                    .copied()
+                    .filter(|&(key, _)| key >= start)
+                    .collect();
+                assert_eq!(scanned, expected, "{strategy:?}");
+            }
+        }
+
+        Ok(())
+    }
+
     /// Tests basic write and scan functionality with a small dataset.
     ///
     /// Verifies that:
@@ -278,7 +1278,11 @@ mod tests {
             .into_iter()
             .map(Ok),
             8,
-            8,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
             file_id,
             fs,
         )?;
@@ -330,7 +1334,11 @@ mod tests {
             .into_iter()
             .map(Ok),
             8,
-            8,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
             file_id,
             fs,
         )?;
@@ -383,7 +1391,17 @@ mod tests {
             test_vec.push((i, i));
         }
 
-        Sst::create(test_vec.into_iter().map(Ok), 400_000, 8, file_id, fs)?;
+        Sst::create(
+            test_vec.into_iter().map(Ok),
+            8,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
+            file_id,
+            fs,
+        )?;
 
         let sst = Sst::open(file_id, fs)?;
         assert_eq!(sst.num_entries(), (1..400_000).len());
@@ -407,6 +1425,67 @@ mod tests {
         Ok(())
     }
 
+    /// Tests gets and scans over SSTs with compressed leaves,
+    /// which hold many more pairs per page when keys are dense.
+    #[test]
+    fn test_compressed_leaves() -> Result<()> {
+        let fs = &test_fs("compressed_leaves");
+
+        // Timestamps about 10ms apart
+        let start = 1_700_000_000_000;
+        let pairs: Vec<(u64, u64)> = (0..100_000)
+            .map(|i| (start + i * 10 + i % 7, i % 1000))
+            .collect();
+
+        let uncompressed_leaves = pairs.len().div_ceil(255) as u64;
+        let compressions = [
+            (Compression::None, uncompressed_leaves),
+            (Compression::DeltaFor, uncompressed_leaves / 4),
+            (Compression::Lz4, uncompressed_leaves - 1),
+            (Compression::DeltaKeys, uncompressed_leaves / 4),
+        ];
+        for (sst_number, (compression, max_leaves)) in compressions.into_iter().enumerate() {
+            let file_id = FileId {
+                lsm_level: 0,
+                sst_number,
+            };
+            let key_values = pairs.iter().copied().map(Ok);
+            Sst::create(
+                key_values,
+                8,
+                FilterType::Bloom,
+                HashType::Murmur,
+                None,
+                compression,
+                0,
+                file_id,
+                fs,
+            )?;
+
+            let sst = Sst::open(file_id, fs)?;
+            assert_eq!(sst.btree_metadata.compression(), compression);
+            assert_eq!(sst.num_entries(), pairs.len());
+            let leaves = sst.btree_metadata.nodes_offset - sst.btree_metadata.leafs_offset;
+            assert!(leaves <= max_leaves, "{compression:?}: {leaves} leaves");
+
+            for &(key, value) in pairs.iter().step_by(97) {
+                assert_eq!(sst.get(key, fs)?, Some(value));
+                assert_eq!(sst.get(key + 1, fs)?, None);
+            }
+            assert_eq!(sst.get(start - 1, fs)?, None);
+            assert_eq!(sst.get(u64::MAX, fs)?, None);
+
+            let scanned: Vec<(u64, u64)> = sst
+                .scan(pairs[500].0 + 1..=pairs[20_000].0, fs)?
+                .collect::<Result<_, _>>()?;
+            assert_eq!(scanned, pairs[501..=20_000]);
+            let scanned: Vec<(u64, u64)> = sst.scan(0..=u64::MAX, fs)?.collect::<Result<_, _>>()?;
+            assert_eq!(scanned, pairs);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_update_file_names() -> Result<()> {
         let fs = &test_fs("update_file_names");
@@ -424,13 +1503,27 @@ mod tests {
             sst_number: 46,
         };
 
-        let mut sst_0 = Sst::create([(1, 14), (4, 19), (13, 15)].map(Ok), 64, 0, file_id_a, fs)?;
+        let mut sst_0 = Sst::create(
+            [(1, 14), (4, 19), (13, 15)].map(Ok),
+            0,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
+            file_id_a,
+            fs,
+        )?;
         assert_eq!(sst_0.num_entries(), 3);
 
         let mut sst_1 = Sst::create(
             [(1, 12), (9, 4), (12, 25), (13, 15), (14, 15)].map(Ok),
-            256,
             3,
+            FilterType::Bloom,
+            HashType::Murmur,
+            None,
+            Compression::None,
+            0,
             file_id_b,
             fs,
         )?;
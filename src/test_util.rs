@@ -50,6 +50,15 @@ impl Drop for TestPath {
     }
 }
 
+/// The seed used for hash tables and filters in tests.
+/// Set `BEARR_TEST_SEED` to reproduce a run with a different seed.
+pub fn test_seed() -> u64 {
+    std::env::var("BEARR_TEST_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(0xBEA22)
+}
+
 /// Asserts that the given operation panics when executed.
 pub fn assert_panics(mut f: impl FnMut()) {
     assert!(
@@ -74,7 +83,7 @@ impl TestFs {
     pub fn create(base: &str, name: &str) -> Self {
         let path = TestPath::create(base, name);
         fs::create_dir_all(&path).unwrap();
-        let fs = FileSystem::new(&path, 16, 1, 1).unwrap();
+        let fs = FileSystem::with_seed(&path, 16, 1, 1, Some(test_seed())).unwrap();
         Self { _path: path, fs }
     }
 }
@@ -0,0 +1,87 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use bearr::Database;
+use clap::{Parser, Subcommand};
+
+/// Maintenance commands for BEARR databases.
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Rebuild the metadata file of a database from its SST files, then replay its WAL.
+    Repair {
+        /// The folder containing the database.
+        path: PathBuf,
+    },
+    /// Check the integrity of every SST in a database.
+    Verify {
+        /// The folder containing the database.
+        path: PathBuf,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Repair { path } => repair(path),
+        Command::Verify { path } => verify(path),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn repair(path: PathBuf) -> Result<(), bearr::DbError> {
+    let report = Database::repair(&path)?;
+
+    println!("Repaired database at {}", path.display());
+    for (level, num_ssts) in report.ssts_per_level.iter().enumerate() {
+        println!("  level {level}: {num_ssts} SST(s)");
+    }
+    println!("  entries in SSTs: {}", report.sst_entries);
+    println!("  WAL entries replayed: {}", report.wal_entries);
+    println!("  last sequence number: {}", report.last_sequence);
+    if !report.corrupt_files.is_empty() {
+        println!("  corrupt SSTs set aside:");
+        for filename in &report.corrupt_files {
+            println!("    {filename}.corrupt");
+        }
+    }
+    if !report.leftover_files.is_empty() {
+        println!("  leftover SSTs from interrupted merges set aside:");
+        for filename in &report.leftover_files {
+            println!("    {filename}.leftover");
+        }
+    }
+
+    Ok(())
+}
+
+fn verify(path: PathBuf) -> Result<(), bearr::DbError> {
+    let report = Database::open(&path)?.verify();
+
+    println!("Verified database at {}", path.display());
+    println!("  SSTs checked: {}", report.ssts_checked);
+    println!("  pages checked: {}", report.pages_checked);
+    println!("  entries checked: {}", report.entries_checked);
+    if report.is_ok() {
+        println!("  no problems found");
+        return Ok(());
+    }
+
+    println!("  problems found:");
+    for problem in &report.problems {
+        println!("    {problem}");
+    }
+    Err(bearr::DbError::CorruptSst)
+}
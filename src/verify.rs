@@ -0,0 +1,540 @@
+use std::{
+    fmt::Display,
+    fs,
+    os::unix::fs::MetadataExt,
+    path::Path,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    DbError,
+    btree::BTree,
+    file_system::{FileId, FileSystem},
+};
+
+/// The result of checking the integrity of SST files.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// The number of SST files that were checked.
+    pub ssts_checked: usize,
+    /// The number of pages whose checksums were checked.
+    pub pages_checked: usize,
+    /// The number of key-value pairs that were checked, including overwritten entries and tombstones.
+    pub entries_checked: usize,
+    /// Every problem that was found.
+    pub problems: Vec<VerifyProblem>,
+}
+
+impl VerifyReport {
+    /// Returns whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Adds the counts and problems of another report to this one.
+    fn merge(&mut self, other: VerifyReport) {
+        self.ssts_checked += other.ssts_checked;
+        self.pages_checked += other.pages_checked;
+        self.entries_checked += other.entries_checked;
+        self.problems.extend(other.problems);
+    }
+}
+
+/// A problem found in an SST file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyProblem {
+    /// The name of the SST file.
+    pub file: String,
+    /// The page of the file where the problem is, if it is specific to a page.
+    pub page: Option<usize>,
+    pub kind: ProblemKind,
+}
+
+/// The kinds of problems that can be found in an SST file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// The file couldn't be read.
+    Unreadable(String),
+    /// The checksum of the page doesn't match its contents.
+    ChecksumMismatch,
+    /// The metadata page has a bad magic number or inconsistent offsets,
+    /// so the rest of the file can't be interpreted.
+    InvalidMetadata,
+    /// The file is not the size recorded in its metadata.
+    SizeMismatch {
+        expected_pages: u64,
+        actual_pages: u64,
+    },
+    /// The leaf holds no pairs, or more pairs than fit in a page.
+    InvalidLeafLength(u64),
+    /// The compressed leaf could not be decoded.
+    UndecodableLeaf,
+    /// The keys within the leaf are not strictly increasing.
+    UnsortedKeys,
+    /// The first key of the leaf is not greater than the last key of the previous leaf.
+    OverlappingLeaves,
+    /// The B-tree node doesn't match the one built from the maximum keys of the leaves.
+    SeparatorMismatch,
+    /// A key in a leaf is reported as absent by the bloom filter.
+    MissingFromFilter { key: u64 },
+    /// A key in a leaf is in a bucket that the range filter reports as empty.
+    MissingFromRangeFilter { key: u64 },
+    /// The number of pairs in the leaves doesn't match the number recorded in the metadata.
+    EntryCountMismatch { expected: u64, actual: u64 },
+    /// The key range or tombstone count in the SST's properties doesn't match the leaves.
+    PropertiesMismatch,
+}
+
+impl Display for VerifyProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(page) = self.page {
+            write!(f, " page {page}")?;
+        }
+        write!(f, ": ")?;
+        match &self.kind {
+            ProblemKind::Unreadable(e) => write!(f, "unreadable ({e})"),
+            ProblemKind::ChecksumMismatch => write!(f, "checksum mismatch"),
+            ProblemKind::InvalidMetadata => write!(f, "invalid metadata"),
+            ProblemKind::SizeMismatch {
+                expected_pages,
+                actual_pages,
+            } => write!(
+                f,
+                "file is {actual_pages} pages long, expected {expected_pages}"
+            ),
+            ProblemKind::InvalidLeafLength(length) => write!(f, "invalid leaf length {length}"),
+            ProblemKind::UndecodableLeaf => write!(f, "compressed leaf can't be decompressed"),
+            ProblemKind::UnsortedKeys => write!(f, "keys are out of order"),
+            ProblemKind::OverlappingLeaves => write!(f, "keys overlap with the previous leaf"),
+            ProblemKind::SeparatorMismatch => write!(f, "separator keys don't match the leaves"),
+            ProblemKind::MissingFromFilter { key } => {
+                write!(f, "key {key} is missing from the bloom filter")
+            }
+            ProblemKind::MissingFromRangeFilter { key } => {
+                write!(f, "key {key} is missing from the range filter")
+            }
+            ProblemKind::EntryCountMismatch { expected, actual } => {
+                write!(f, "{actual} entries in leaves, expected {expected}")
+            }
+            ProblemKind::PropertiesMismatch => write!(f, "properties don't match the leaves"),
+        }
+    }
+}
+
+/// Checks the SST files with the given IDs, reading them directly from disk.
+///
+/// Problems that prevent a file from being read are added to the report rather than returned.
+pub(crate) fn verify_ssts(
+    file_ids: impl IntoIterator<Item = FileId>,
+    file_system: &FileSystem,
+) -> VerifyReport {
+    let mut report = VerifyReport::default();
+    for file_id in file_ids {
+        if let Err(e) = BTree::verify(file_id, file_system, &mut report, &|| true) {
+            report.problems.push(unreadable(file_id, e));
+        }
+    }
+    report
+}
+
+fn unreadable(file_id: FileId, error: DbError) -> VerifyProblem {
+    VerifyProblem {
+        file: file_id.name(),
+        page: None,
+        kind: ProblemKind::Unreadable(error.to_string()),
+    }
+}
+
+/// A background thread that repeatedly checks every SST file in a database folder,
+/// so that corruption is found before a read runs into it.
+///
+/// The thread runs at the lowest scheduling priority and pauses after each chunk of pages it reads,
+/// so it takes a small share of the disk and CPU away from foreground work.
+///
+/// Files are read directly from disk rather than through the database's buffer pool,
+/// so scrubbing neither pollutes the buffer pool nor trusts pages cached in it.
+/// SSTs that are created, merged away, or renamed while being checked are skipped
+/// until the next pass.
+///
+/// The thread is stopped when the scrubber is dropped.
+pub struct Scrubber {
+    stop: Arc<AtomicBool>,
+    status: Arc<Mutex<ScrubStatus>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// The progress of a `Scrubber`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScrubStatus {
+    /// The number of complete passes over the database folder.
+    pub passes: usize,
+    /// The report of the most recent complete pass.
+    pub last_report: Option<VerifyReport>,
+    /// The most recent error that stopped a pass, such as failing to list the database folder.
+    pub last_error: Option<DbError>,
+}
+
+impl Scrubber {
+    /// Starts scrubbing the database folder at the given path.
+    ///
+    /// The scrubber sleeps for the given pause after each chunk of pages and between passes.
+    pub(crate) fn start(path: &Path, pause: Duration) -> Result<Self, DbError> {
+        // The buffer pool of this file system is never used, since every read is unbuffered
+        let file_system = FileSystem::new(path, 16, 1, 1)?;
+        let path = path.to_path_buf();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let status = Arc::new(Mutex::new(ScrubStatus::default()));
+
+        let handle = {
+            let stop = Arc::clone(&stop);
+            let status = Arc::clone(&status);
+            thread::Builder::new()
+                .name("bearr-scrubber".to_string())
+                .spawn(move || {
+                    lower_thread_priority();
+                    while !stop.load(Ordering::Relaxed) {
+                        let pass = scrub_pass(&path, &file_system, &stop, pause);
+                        let mut status = status.lock().unwrap();
+                        match pass {
+                            Ok(Some(report)) => {
+                                status.passes += 1;
+                                status.last_report = Some(report);
+                            }
+                            Ok(None) => {}
+                            Err(e) => status.last_error = Some(e),
+                        }
+                        drop(status);
+                        thread::park_timeout(pause);
+                    }
+                })?
+        };
+
+        Ok(Self {
+            stop,
+            status,
+            handle: Some(handle),
+        })
+    }
+
+    /// Returns the progress of the scrubber so far.
+    pub fn status(&self) -> ScrubStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Scrubber {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            _ = handle.join();
+        }
+    }
+}
+
+/// Checks every SST file in the folder at the given path once.
+///
+/// Returns None if the scrubber was stopped before the pass completed.
+fn scrub_pass(
+    path: &Path,
+    file_system: &FileSystem,
+    stop: &AtomicBool,
+    pause: Duration,
+) -> Result<Option<VerifyReport>, DbError> {
+    let mut file_ids = Vec::new();
+    for entry in fs::read_dir(path)? {
+        if let Some(file_id) = entry?.file_name().to_str().and_then(FileId::parse) {
+            file_ids.push(file_id);
+        }
+    }
+    file_ids.sort_by_key(|file_id| (file_id.lsm_level, file_id.sst_number));
+
+    let pace = || {
+        thread::park_timeout(pause);
+        !stop.load(Ordering::Relaxed)
+    };
+
+    let mut report = VerifyReport::default();
+    for file_id in file_ids {
+        let file_path = file_system.path(file_id);
+        let Some(before) = file_identity(&file_path) else {
+            continue;
+        };
+
+        let mut sst_report = VerifyReport::default();
+        let result = BTree::verify(file_id, file_system, &mut sst_report, &pace);
+
+        if stop.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        // Anything found in a file that was replaced while being read can't be trusted
+        if file_identity(&file_path) != Some(before) {
+            continue;
+        }
+
+        if let Err(e) = result {
+            sst_report.problems.push(unreadable(file_id, e));
+        }
+        report.merge(sst_report);
+    }
+
+    Ok(Some(report))
+}
+
+/// Returns the inode, size, and modification time of the file at the given path,
+/// or None if there is no such file.
+fn file_identity(path: &Path) -> Option<(u64, u64, i64, i64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((
+        metadata.ino(),
+        metadata.size(),
+        metadata.mtime(),
+        metadata.mtime_nsec(),
+    ))
+}
+
+/// Lowers the scheduling priority of the calling thread as far as possible.
+fn lower_thread_priority() {
+    // On Linux, PRIO_PROCESS with an ID of 0 refers to the calling thread rather than the whole process.
+    // Failing to lower the priority only makes scrubbing more intrusive, so the result is ignored.
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, 19);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use anyhow::Result;
+
+    use crate::{
+        Compression, Database, DbConfiguration, EvictionType, FilterType, LevelCompression,
+        LsmConfiguration, PAGE_SIZE, SearchStrategy,
+        file_system::Aligned,
+        test_util::{TestPath, test_seed},
+    };
+
+    use super::*;
+
+    fn create_db(name: &str, compression: LevelCompression) -> Result<(TestPath, Database)> {
+        let path = TestPath::create("verify", name);
+        let mut db = Database::create(
+            &path,
+            DbConfiguration {
+                buffer_pool_capacity: 64,
+                write_buffering: 4,
+                readahead_buffering: 4,
+                wal_buffer_size: None,
+                wal_archiving: false,
+                seed: Some(test_seed()),
+                buffer_pool_shards: None,
+                eviction: EvictionType::default(),
+                lsm_configuration: LsmConfiguration {
+                    size_ratio: 3,
+                    memtable_capacity: 2000,
+                    bloom_filter_bits: 4,
+                    compression,
+                    pin_internal_nodes: false,
+                    search_strategy: SearchStrategy::BTree,
+                    uniform_bits: false,
+                    workload_aware_bits: false,
+                    filter_type: FilterType::Bloom,
+                    filter_hash: None,
+                    pinned_filter_levels: 0,
+                    range_filter: None,
+                },
+            },
+        )?;
+        for i in 0..9000 {
+            db.put(i * 7 % 9001, i)?;
+        }
+        db.flush()?;
+        Ok((path, db))
+    }
+
+    /// Applies `f` to the given page of the file at the given path, then fixes up its checksum.
+    fn rewrite_page(path: &Path, page_number: usize, f: impl FnOnce(&mut Aligned)) -> Result<()> {
+        let mut bytes = fs::read(path)?;
+        let page_bytes = &mut bytes[page_number * PAGE_SIZE..(page_number + 1) * PAGE_SIZE];
+        let mut page = Aligned([0; PAGE_SIZE]);
+        page.0.copy_from_slice(page_bytes);
+        f(&mut page);
+        page.stamp_checksum(page_number);
+        page_bytes.copy_from_slice(&page.0);
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    fn kinds(report: &VerifyReport) -> Vec<(String, Option<usize>, ProblemKind)> {
+        report
+            .problems
+            .iter()
+            .map(|p| (p.file.clone(), p.page, p.kind.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        let (path, db) = create_db("verify", LevelCompression::default())?;
+        let filenames = db.sst_filenames();
+        assert!(filenames.len() > 1);
+
+        let report = db.verify();
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert_eq!(report.ssts_checked, filenames.len());
+        assert!(report.entries_checked >= 9000);
+        assert!(report.pages_checked > report.entries_checked / 255);
+
+        let file = &filenames[0];
+        let file_path = path.as_ref().join(file);
+
+        // Swap the first two keys of the first leaf
+        rewrite_page(&file_path, 1, |page| {
+            let (first, second) = page.0[8..40].split_at_mut(16);
+            first.swap_with_slice(second);
+        })?;
+        // Change the largest key of the second leaf so it overlaps with the third,
+        // which also breaks the separator key in the bottom internal node
+        rewrite_page(&file_path, 2, |page| {
+            let length = u64::from_le_bytes(page.0[0..8].try_into().unwrap()) as usize;
+            let last_key = 8 + (length - 1) * 16;
+            page.0[last_key..last_key + 8].copy_from_slice(&(u64::MAX - 1).to_le_bytes());
+        })?;
+        // Flip a bit without fixing the checksum
+        let mut bytes = fs::read(&file_path)?;
+        bytes[3 * PAGE_SIZE + 20] ^= 1;
+        fs::write(&file_path, bytes)?;
+
+        let problems = kinds(&db.verify());
+        assert!(problems.contains(&(file.clone(), Some(1), ProblemKind::UnsortedKeys)));
+        assert!(problems.contains(&(file.clone(), Some(3), ProblemKind::ChecksumMismatch)));
+        // The leaf with the bad checksum means the internal nodes can't be checked
+        assert!(
+            problems
+                .iter()
+                .all(|(_, _, kind)| *kind != ProblemKind::SeparatorMismatch)
+        );
+
+        // Restore the flipped bit so the internal nodes get checked
+        let mut bytes = fs::read(&file_path)?;
+        bytes[3 * PAGE_SIZE + 20] ^= 1;
+        fs::write(&file_path, bytes)?;
+        let problems = kinds(&db.verify());
+        assert!(problems.contains(&(file.clone(), Some(3), ProblemKind::OverlappingLeaves)));
+        assert!(problems.iter().any(|(f, page, kind)| f == file
+            && page.is_some()
+            && *kind == ProblemKind::SeparatorMismatch));
+
+        // Clear the bits of the bloom filter of another file, keeping its hash function seeds
+        let other_file = &filenames[1];
+        let other_file_path = path.as_ref().join(other_file);
+        let last_page = fs::metadata(&other_file_path)?.len() as usize / PAGE_SIZE - 1;
+        rewrite_page(&other_file_path, last_page, |page| page.0[64..].fill(0))?;
+        // Record one tombstone too many in its properties
+        rewrite_page(&other_file_path, 0, |page| {
+            let n_tombstones = u64::from_le_bytes(page.0[136..144].try_into().unwrap());
+            page.0[136..144].copy_from_slice(&(n_tombstones + 1).to_le_bytes());
+        })?;
+
+        let problems = kinds(&db.verify());
+        assert!(
+            problems.iter().any(|(f, _, kind)| f == other_file
+                && matches!(kind, ProblemKind::MissingFromFilter { .. }))
+        );
+        assert!(problems.contains(&(other_file.clone(), Some(0), ProblemKind::PropertiesMismatch)));
+
+        // Other files are unaffected
+        assert!(
+            problems
+                .iter()
+                .all(|(f, _, _)| f == file || f == other_file)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_compressed() -> Result<()> {
+        for compression in [Compression::DeltaFor, Compression::DeltaKeys] {
+            let name = format!("verify_compressed_{compression:?}");
+            let (path, db) = create_db(&name, LevelCompression::new(&[compression]))?;
+            let report = db.verify();
+            assert!(report.is_ok(), "{:?}", report.problems);
+            assert!(report.entries_checked >= 9000);
+
+            let filenames = db.sst_filenames();
+            let file = &filenames[0];
+            let file_path = path.as_ref().join(file);
+
+            // Garble the compressed block of the first leaf
+            rewrite_page(&file_path, 1, |page| page.0[16..64].fill(0xff))?;
+            // Claim more pairs than a compressed leaf can hold in the second
+            rewrite_page(&file_path, 2, |page| {
+                page.0[0..8].copy_from_slice(&100_000u64.to_le_bytes());
+            })?;
+
+            let problems = kinds(&db.verify());
+            assert!(problems.contains(&(file.clone(), Some(1), ProblemKind::UndecodableLeaf)));
+            assert!(problems.contains(&(
+                file.clone(),
+                Some(2),
+                ProblemKind::InvalidLeafLength(100_000)
+            )));
+            assert!(
+                problems
+                    .iter()
+                    .all(|(_, _, kind)| *kind != ProblemKind::SeparatorMismatch)
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scrubber() -> Result<()> {
+        let (path, db) = create_db("scrubber", LevelCompression::default())?;
+        let scrubber = db.start_scrubber(Duration::from_millis(1))?;
+
+        let wait_for_passes = |passes: usize| {
+            let start = Instant::now();
+            loop {
+                let status = scrubber.status();
+                if status.passes >= passes {
+                    return status;
+                }
+                assert!(start.elapsed() < Duration::from_secs(30), "{status:?}");
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+
+        let status = wait_for_passes(1);
+        let report = status.last_report.unwrap();
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert_eq!(report.ssts_checked, db.sst_filenames().len());
+        assert_eq!(status.last_error, None);
+
+        let file = &db.sst_filenames()[0];
+        let mut bytes = fs::read(path.as_ref().join(file))?;
+        bytes[PAGE_SIZE + 100] ^= 1;
+        fs::write(path.as_ref().join(file), bytes)?;
+
+        // The pass in progress might have started before the corruption
+        let passes = status.passes;
+        let report = wait_for_passes(passes + 2).last_report.unwrap();
+        assert_eq!(
+            kinds(&report),
+            [(file.clone(), Some(1), ProblemKind::ChecksumMismatch)]
+        );
+
+        drop(scrubber);
+        Ok(())
+    }
+}
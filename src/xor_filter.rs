@@ -0,0 +1,240 @@
+use crate::{filter::Filter, hash::HashType};
+
+/// The number of bytes before the fingerprints: the seed and the block length.
+pub const HEADER_SIZE: usize = 16;
+/// The widest fingerprint, which is taken from one half of a 64-bit hash.
+pub const MAX_FINGERPRINT_BITS: usize = 32;
+/// How many more fingerprint slots than keys an xor filter needs to be built reliably.
+const SLOTS_PER_KEY: f64 = 1.23;
+
+/// A static xor filter (Graf and Lemire, 2020), which is built from all of its keys at once.
+///
+/// Each key is hashed to three slots, one in each third of the table,
+/// and the fingerprints in the slots are chosen so that they xor to the key's fingerprint.
+/// A key that isn't in the filter matches with probability `2^-fingerprint_bits`,
+/// using about `1.23 * fingerprint_bits` bits per key.
+///
+/// Stored as the seed and the block length (the number of slots in each third) as little-endian `u64`s,
+/// followed by the fingerprints packed into little-endian `u64`s, starting from the least significant bit.
+/// The fingerprint width is recorded as `num_hashes` in SST metadata.
+///
+/// Keys are hashed to 64 bits with `HashType::hash64`. Peeling only succeeds if different keys get different hashes,
+/// which `HashType::U64Mixer` guarantees and the other algorithms make very likely, retrying with another seed otherwise.
+#[derive(Clone, Debug)]
+pub struct XorFilter {
+    hash: HashType,
+    seed: u64,
+    block_length: usize,
+    fingerprint_bits: usize,
+    fingerprints: Vec<u64>,
+}
+
+impl XorFilter {
+    /// Builds a filter from the given keys, which must be distinct,
+    /// with fingerprints as wide as fit in the given number of bits per entry, hashing them with the given algorithm.
+    /// The seeds that it tries derive from the given seed.
+    pub fn build(keys: &[u64], bits_per_entry: usize, hash: HashType, seed: u64) -> Self {
+        let fingerprint_bits =
+            ((bits_per_entry as f64 / SLOTS_PER_KEY) as usize).min(MAX_FINGERPRINT_BITS);
+        if fingerprint_bits == 0 {
+            // Matches every key, like a bloom filter without any hash functions
+            return Self {
+                hash,
+                seed: 0,
+                block_length: 0,
+                fingerprint_bits,
+                fingerprints: Vec::new(),
+            };
+        }
+
+        let block_length = ((keys.len() as f64 * SLOTS_PER_KEY) as usize + 32) / 3;
+        let n_slots = block_length * 3;
+
+        // Peel keys off slots that only one key maps to, retrying with another seed if it gets stuck.
+        // This succeeds with high probability for distinct keys, whose hashes are distinct.
+        let mut counts = vec![0u32; n_slots];
+        let mut xors = vec![0u64; n_slots];
+        let mut queue = Vec::new();
+        let mut peeled = Vec::with_capacity(keys.len());
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let seed = loop {
+            let seed = rng.u64(..);
+            counts.fill(0);
+            xors.fill(0);
+            peeled.clear();
+
+            for &key in keys {
+                let key_hash = hash.hash64(key, seed);
+                for slot in slots(key_hash, block_length) {
+                    counts[slot] += 1;
+                    xors[slot] ^= key_hash;
+                }
+            }
+            queue.extend((0..n_slots).filter(|&slot| counts[slot] == 1));
+            while let Some(slot) = queue.pop() {
+                if counts[slot] != 1 {
+                    continue;
+                }
+                let hash = xors[slot];
+                peeled.push((hash, slot));
+                for other in slots(hash, block_length) {
+                    counts[other] -= 1;
+                    xors[other] ^= hash;
+                    if counts[other] == 1 {
+                        queue.push(other);
+                    }
+                }
+            }
+
+            if peeled.len() == keys.len() {
+                break seed;
+            }
+        };
+
+        // Assign fingerprints in the reverse order of peeling,
+        // so that each key's slot is the last of its three to be assigned
+        let mut filter = Self {
+            hash,
+            seed,
+            block_length,
+            fingerprint_bits,
+            fingerprints: vec![0; (n_slots * fingerprint_bits).div_ceil(64)],
+        };
+        for &(key_hash, slot) in peeled.iter().rev() {
+            let fingerprint = slots(key_hash, block_length)
+                .into_iter()
+                .fold(filter.fingerprint(key_hash), |acc, other| {
+                    acc ^ filter.get(other)
+                });
+            filter.set(slot, fingerprint);
+        }
+        filter
+    }
+
+    /// Reads a filter back from the bytes that `turn_to_bytes` returned, with fingerprints of the given width,
+    /// that hashes keys with the algorithm it was built with.
+    pub fn from_bytes(filter_bytes: &[u8], fingerprint_bits: usize, hash: HashType) -> Self {
+        let (words, _) = filter_bytes.as_chunks::<8>();
+        let mut words = words.iter().map(|&word| u64::from_le_bytes(word));
+        Self {
+            hash,
+            seed: words.next().unwrap_or(0),
+            block_length: words.next().unwrap_or(0) as usize,
+            fingerprint_bits,
+            fingerprints: words.collect(),
+        }
+    }
+
+    /// The fingerprint of a key with the given hash.
+    fn fingerprint(&self, hash: u64) -> u64 {
+        (hash ^ (hash >> 32)) & self.mask()
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.fingerprint_bits) - 1
+    }
+
+    /// The fingerprint in the given slot, or 0 if it's past the end.
+    fn get(&self, slot: usize) -> u64 {
+        let bit = slot * self.fingerprint_bits;
+        let (word, offset) = (bit / 64, bit % 64);
+        let low = self.fingerprints.get(word).map_or(0, |word| word >> offset);
+        let high = match offset {
+            0 => 0,
+            _ => self
+                .fingerprints
+                .get(word + 1)
+                .map_or(0, |word| word << (64 - offset)),
+        };
+        (low | high) & self.mask()
+    }
+
+    /// Sets the fingerprint in the given slot, which must still be 0.
+    fn set(&mut self, slot: usize, fingerprint: u64) {
+        let bit = slot * self.fingerprint_bits;
+        let (word, offset) = (bit / 64, bit % 64);
+        self.fingerprints[word] |= fingerprint << offset;
+        if offset + self.fingerprint_bits > 64 {
+            self.fingerprints[word + 1] |= fingerprint >> (64 - offset);
+        }
+    }
+}
+
+/// The three slots of a key with the given hash, one in each block.
+fn slots(hash: u64, block_length: usize) -> [usize; 3] {
+    let reduce = |hash: u64| ((hash as u32 as u64 * block_length as u64) >> 32) as usize;
+    [
+        reduce(hash),
+        reduce(hash.rotate_left(21)) + block_length,
+        reduce(hash.rotate_left(42)) + 2 * block_length,
+    ]
+}
+
+impl Filter for XorFilter {
+    fn query(&self, key: u64) -> bool {
+        if self.fingerprint_bits == 0 || self.block_length == 0 {
+            return true;
+        }
+        let hash = self.hash.hash64(key, self.seed);
+        let [a, b, c] = slots(hash, self.block_length);
+        self.fingerprint(hash) == self.get(a) ^ self.get(b) ^ self.get(c)
+    }
+
+    fn turn_to_bytes(&self) -> Vec<u8> {
+        [self.seed, self.block_length as u64]
+            .into_iter()
+            .chain(self.fingerprints.iter().copied())
+            .flat_map(u64::to_le_bytes)
+            .collect()
+    }
+
+    fn num_hashes(&self) -> usize {
+        self.fingerprint_bits
+    }
+
+    /// `2^-fingerprint_bits`, whatever the number of keys.
+    fn false_positive_rate(&self, _n_entries: usize) -> f64 {
+        if self.fingerprint_bits == 0 || self.block_length == 0 {
+            return 1.0;
+        }
+        0.5_f64.powi(self.fingerprint_bits as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that fingerprints of every width are packed correctly,
+    /// and that the false positive rate halves with every bit of fingerprint.
+    #[test]
+    fn test_fingerprint_bits() {
+        let keys: Vec<u64> = (0..20_000).map(|i| i * 2).collect();
+        for bits_per_entry in [0, 1, 2, 5, 10, 13, 40, 64] {
+            let filter = XorFilter::build(&keys, bits_per_entry, HashType::U64Mixer, 0);
+            let fingerprint_bits = filter.num_hashes();
+            assert_eq!(
+                fingerprint_bits,
+                ((bits_per_entry as f64 / 1.23) as usize).min(32)
+            );
+            assert!(
+                keys.iter().all(|&key| filter.query(key)),
+                "{bits_per_entry}"
+            );
+
+            let bytes = filter.turn_to_bytes();
+            let max_bits = bits_per_entry * keys.len() + 32 * fingerprint_bits + 64;
+            assert!((bytes.len() - HEADER_SIZE) * 8 <= max_bits);
+
+            let false_positives = (0..20_000).filter(|i| filter.query(i * 2 + 1)).count();
+            let expected = 20_000.0 / (1u64 << fingerprint_bits) as f64;
+            assert!(
+                (false_positives as f64) < expected * 1.5 + 10.0,
+                "{bits_per_entry}: {false_positives} false positives, expected {expected}"
+            );
+        }
+
+        let filter = XorFilter::build(&[], 10, HashType::U64Mixer, 0);
+        assert!(!filter.query(0) || !filter.query(1));
+    }
+}